@@ -0,0 +1,60 @@
+use std::net::SocketAddr;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use simple_redis::network::{stream_handler_with_options, ConnectionOptions};
+use simple_redis::Backend;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::Runtime;
+
+const GET: &[u8] = b"*2\r\n$3\r\nget\r\n$3\r\nkey\r\n";
+const NULL_REPLY: &[u8] = b"$-1\r\n";
+
+async fn serve(options: ConnectionOptions) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let backend = Backend::new();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (backend, options) = (backend.clone(), options.clone());
+            tokio::spawn(stream_handler_with_options(stream, backend, options));
+        }
+    });
+    addr
+}
+
+// send the whole pipeline, then wait for every reply
+async fn round_trip(client: &mut TcpStream, request: &[u8], reply: &mut [u8]) {
+    let (mut rd, mut wr) = client.split();
+    let (written, read) = tokio::join!(wr.write_all(request), rd.read_exact(reply));
+    written.unwrap();
+    read.unwrap();
+}
+
+fn bench_pipelined_gets(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("pipelined_gets");
+    for commands in [1, 100, 1_000] {
+        let request = GET.repeat(commands);
+        let mut reply = vec![0; NULL_REPLY.len() * commands];
+        group.throughput(Throughput::Elements(commands as u64));
+
+        // a high-water mark of 0 writes every reply on its own, the behaviour before batching
+        for (name, output_high_water) in [("per_reply", 0), ("batched", 64 * 1024)] {
+            let options = ConnectionOptions {
+                output_high_water,
+                ..Default::default()
+            };
+            let mut client =
+                rt.block_on(async { TcpStream::connect(serve(options).await).await.unwrap() });
+            group.bench_with_input(BenchmarkId::new(name, commands), &request, |b, request| {
+                b.iter(|| rt.block_on(round_trip(&mut client, request, &mut reply)))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_pipelined_gets);
+criterion_main!(benches);
@@ -0,0 +1,63 @@
+use bytes::BytesMut;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use simple_redis::{BulkString, RespArray, RespDecoder, RespEncode, RespFrame};
+
+fn set_command(i: usize) -> Vec<u8> {
+    let frame: RespFrame = RespArray::new([
+        BulkString::from("SET").into(),
+        BulkString::new(format!("key:{}", i)).into(),
+        BulkString::new(vec![b'x'; 64]).into(),
+    ])
+    .into();
+    frame.encode()
+}
+
+fn big_array(len: usize) -> Vec<u8> {
+    let items = (0..len)
+        .map(|i| BulkString::new(format!("member:{}", i)).into())
+        .collect::<Vec<RespFrame>>();
+    RespFrame::from(RespArray::new(items)).encode()
+}
+
+// decode everything in input, handing it to the decoder in reads of `chunk` bytes
+fn decode_all(input: &[u8], chunk: usize) -> usize {
+    let mut decoder = RespDecoder::new();
+    let mut buf = BytesMut::with_capacity(input.len());
+    let mut frames = 0;
+    for read in input.chunks(chunk) {
+        buf.extend_from_slice(read);
+        while let Some(frame) = decoder.decode(&mut buf).unwrap() {
+            black_box(frame);
+            frames += 1;
+        }
+    }
+    frames
+}
+
+fn bench_pipeline(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pipeline");
+    for commands in [1_000, 10_000] {
+        let input = (0..commands).flat_map(set_command).collect::<Vec<u8>>();
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(commands), &input, |b, input| {
+            b.iter(|| assert_eq!(decode_all(input, 16 * 1024), commands))
+        });
+    }
+    group.finish();
+}
+
+fn bench_big_array(c: &mut Criterion) {
+    let mut group = c.benchmark_group("big_array");
+    for len in [10_000, 100_000] {
+        let input = big_array(len);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        // a single frame arriving over many socket reads is the worst case for rescanning
+        group.bench_with_input(BenchmarkId::from_parameter(len), &input, |b, input| {
+            b.iter(|| assert_eq!(decode_all(input, 4 * 1024), 1))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_pipeline, bench_big_array);
+criterion_main!(benches);
@@ -0,0 +1,869 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use ring::digest::{digest, SHA256};
+use thiserror::Error;
+
+use crate::cmd::{CommandFlag, CommandSpec, COMMAND_TABLE};
+use crate::network::split_args;
+use crate::{glob_match, RespFrame};
+
+pub const DEFAULT_USER: &str = "default";
+
+/// Every category ACL rules can name: redis' own, then those of the module commands.
+pub const ACL_CATEGORIES: &[&str] = &[
+    "keyspace",
+    "read",
+    "write",
+    "set",
+    "sortedset",
+    "list",
+    "hash",
+    "string",
+    "bitmap",
+    "hyperloglog",
+    "geo",
+    "stream",
+    "pubsub",
+    "admin",
+    "fast",
+    "slow",
+    "blocking",
+    "dangerous",
+    "connection",
+    "transaction",
+    "scripting",
+    "timeseries",
+    "vectorset",
+    "search",
+];
+
+// like redis, repeated denials within this long count towards one ACL LOG entry
+const LOG_ENTRY_GROUPING: Duration = Duration::from_secs(60);
+
+/// A named user: whether and how it may log in, and what it may run and touch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct User {
+    pub name: String,
+    pub enabled: bool,
+    /// Any password logs in.
+    pub nopass: bool,
+    /// Hex SHA-256 of each password, in the order they were added.
+    passwords: Vec<String>,
+    /// The command rules as given since the last +@all or -@all, which is how ACL LIST
+    /// shows them.
+    command_rules: Vec<String>,
+    /// Full names of the commands the rules allow, `config|get` for subcommands.
+    allowed: HashSet<&'static str>,
+    keys: Vec<KeyPattern>,
+    channels: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct KeyPattern {
+    pattern: String,
+    read: bool,
+    write: bool,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum AclError {
+    #[error("Syntax error")]
+    Syntax,
+    #[error("Unknown command or category name in ACL")]
+    UnknownCommand,
+    #[error("The password hash must be exactly 64 characters and contain only lowercase hexadecimal characters")]
+    BadHash,
+    #[error("The password you are trying to remove from the user does not exist")]
+    NoSuchPassword,
+}
+
+/// Why a command was refused, as ACL LOG reports it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AclDenial {
+    Command,
+    Key,
+    Channel,
+    Auth,
+}
+
+/// One ACL LOG entry, standing for `count` denials alike.
+#[derive(Debug, Clone)]
+pub struct AclLogEntry {
+    pub count: u64,
+    pub reason: AclDenial,
+    /// The command, key or channel refused, or `AUTH` for a failed login.
+    pub object: String,
+    pub username: String,
+    pub client_info: String,
+    pub entry_id: u64,
+    /// Milliseconds since the epoch.
+    pub created_ms: i64,
+    pub updated_ms: i64,
+    last_seen: Instant,
+}
+
+/// The users and the log of what they were refused.
+#[derive(Debug)]
+pub struct Acl {
+    users: RwLock<BTreeMap<String, User>>,
+    log: Mutex<VecDeque<AclLogEntry>>,
+    next_entry_id: AtomicU64,
+}
+
+impl User {
+    /// A user as ACL SETUSER creates it: disabled and allowed nothing.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            enabled: false,
+            nopass: false,
+            passwords: vec![],
+            command_rules: vec!["-@all".to_string()],
+            allowed: HashSet::new(),
+            keys: vec![],
+            channels: vec![],
+        }
+    }
+
+    /// The default user of a fresh server, which anyone is logged in as.
+    pub fn superuser(name: &str) -> Self {
+        let mut user = Self::new(name);
+        for rule in ["on", "nopass", "allkeys", "allchannels", "allcommands"] {
+            user.apply(rule).expect("valid rule");
+        }
+        user
+    }
+
+    /// Apply one ACL SETUSER rule.
+    pub fn apply(&mut self, rule: &str) -> Result<(), AclError> {
+        match rule.to_ascii_lowercase().as_str() {
+            "on" => self.enabled = true,
+            "off" => self.enabled = false,
+            "nopass" => {
+                self.nopass = true;
+                self.passwords.clear();
+            }
+            "resetpass" => {
+                self.nopass = false;
+                self.passwords.clear();
+            }
+            "allkeys" => self.apply("~*")?,
+            "resetkeys" => self.keys.clear(),
+            "allchannels" => self.apply("&*")?,
+            "resetchannels" => self.channels.clear(),
+            "allcommands" => self.apply("+@all")?,
+            "nocommands" => self.apply("-@all")?,
+            "reset" => {
+                for rule in ["resetpass", "resetkeys", "resetchannels", "off", "-@all"] {
+                    self.apply(rule)?;
+                }
+            }
+            // written by redis into its ACL files, payloads are never checked here
+            "sanitize-payload" | "skip-sanitize-payload" => {}
+            _ => return self.apply_pattern(rule),
+        }
+        Ok(())
+    }
+
+    // the rules that take an argument, which keeps its case
+    fn apply_pattern(&mut self, rule: &str) -> Result<(), AclError> {
+        if let Some(password) = rule.strip_prefix('>') {
+            self.add_hash(hash_password(password));
+        } else if let Some(password) = rule.strip_prefix('<') {
+            self.remove_hash(&hash_password(password))?;
+        } else if let Some(hash) = rule.strip_prefix('#') {
+            self.add_hash(check_hash(hash)?);
+        } else if let Some(hash) = rule.strip_prefix('!') {
+            self.remove_hash(&check_hash(hash)?)?;
+        } else if let Some(pattern) = rule.strip_prefix('~') {
+            self.add_keys(pattern, true, true);
+        } else if let Some(rest) = rule.strip_prefix('%') {
+            let (perms, pattern) = rest.split_once('~').ok_or(AclError::Syntax)?;
+            let perms = perms.to_ascii_uppercase();
+            if perms.is_empty() || perms.chars().any(|c| c != 'R' && c != 'W') {
+                return Err(AclError::Syntax);
+            }
+            self.add_keys(pattern, perms.contains('R'), perms.contains('W'));
+        } else if let Some(pattern) = rule.strip_prefix('&') {
+            if !self.channels.iter().any(|c| c == pattern) {
+                self.channels.push(pattern.to_string());
+            }
+        } else if let Some(name) = rule.strip_prefix('+') {
+            self.allow(name, true)?;
+        } else if let Some(name) = rule.strip_prefix('-') {
+            self.allow(name, false)?;
+        } else {
+            return Err(AclError::Syntax);
+        }
+        Ok(())
+    }
+
+    fn add_hash(&mut self, hash: String) {
+        self.nopass = false;
+        if !self.passwords.contains(&hash) {
+            self.passwords.push(hash);
+        }
+    }
+
+    fn remove_hash(&mut self, hash: &str) -> Result<(), AclError> {
+        let before = self.passwords.len();
+        self.passwords.retain(|h| h != hash);
+        match self.passwords.len() == before {
+            true => Err(AclError::NoSuchPassword),
+            false => Ok(()),
+        }
+    }
+
+    fn add_keys(&mut self, pattern: &str, read: bool, write: bool) {
+        match self.keys.iter_mut().find(|k| k.pattern == pattern) {
+            Some(key) => {
+                key.read |= read;
+                key.write |= write;
+            }
+            None => self.keys.push(KeyPattern {
+                pattern: pattern.to_string(),
+                read,
+                write,
+            }),
+        }
+    }
+
+    // `+name`/`-name` for a command, subcommand or `@category`
+    fn allow(&mut self, name: &str, allow: bool) -> Result<(), AclError> {
+        let name = name.to_ascii_lowercase();
+        let specs: Vec<&'static CommandSpec> = match name.strip_prefix('@') {
+            Some("all") => {
+                self.command_rules.clear();
+                all_specs().collect()
+            }
+            Some(category) if ACL_CATEGORIES.contains(&category) => all_specs()
+                .filter(|spec| spec.acl_categories.contains(&category))
+                .collect(),
+            Some(_) => return Err(AclError::UnknownCommand),
+            None => {
+                let spec = all_specs()
+                    .find(|spec| spec.name == name)
+                    .ok_or(AclError::UnknownCommand)?;
+                // a container brings its subcommands along
+                std::iter::once(spec).chain(spec.subcommands).collect()
+            }
+        };
+        for spec in specs {
+            match allow {
+                true => self.allowed.insert(spec.name),
+                false => self.allowed.remove(spec.name),
+            };
+        }
+        let sign = if allow { '+' } else { '-' };
+        self.command_rules.push(format!("{}{}", sign, name));
+        Ok(())
+    }
+
+    /// Whether `password` logs this user in.
+    pub fn check_password(&self, password: &str) -> bool {
+        self.enabled && (self.nopass || self.passwords.contains(&hash_password(password)))
+    }
+
+    /// Whether the user may run a command line, and if not, what was refused.
+    pub fn check_command(
+        &self,
+        spec: &CommandSpec,
+        args: &[RespFrame],
+    ) -> Result<(), (AclDenial, String)> {
+        // AUTH, HELLO and the like are for everyone, as they are before logging in
+        if spec.flags.contains(&CommandFlag::NoAuth) {
+            return Ok(());
+        }
+        if !self.allowed.contains(spec.name) {
+            return Err((AclDenial::Command, spec.name.to_string()));
+        }
+        for (key, key_spec) in spec.keys_with_specs(args) {
+            let flags = key_spec.flags;
+            let RespFrame::BulkString(key) = key else {
+                continue;
+            };
+            // shard channels, checked against the channel rules below
+            if flags.contains(&"not_key") {
+                continue;
+            }
+            let read = flags.contains(&"RO") || flags.contains(&"RW");
+            let write = flags.iter().any(|flag| matches!(*flag, "RW" | "OW" | "RM"));
+            let allowed = self.keys.iter().any(|k| {
+                (!read || k.read)
+                    && (!write || k.write)
+                    && glob_match(k.pattern.as_bytes(), key, false)
+            });
+            if !allowed {
+                return Err((AclDenial::Key, String::from_utf8_lossy(key).into_owned()));
+            }
+        }
+        // there is no telling beforehand which keys these read, so they take ~* or %R~*
+        if spec.reads_unnamed_keys() && !self.keys.iter().any(|k| k.read && k.pattern == "*") {
+            return Err((AclDenial::Key, spec.name.to_string()));
+        }
+        for (channel, is_pattern) in channel_args(spec, args) {
+            if !self.check_channel(channel, is_pattern) {
+                let channel = String::from_utf8_lossy(channel).into_owned();
+                return Err((AclDenial::Channel, channel));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the user may use a channel; a pattern subscription must match one of the
+    /// user's patterns exactly, as in redis.
+    pub fn check_channel(&self, channel: &[u8], is_pattern: bool) -> bool {
+        self.channels.iter().any(|allowed| match is_pattern {
+            true => allowed == "*" || allowed.as_bytes() == channel,
+            false => glob_match(allowed.as_bytes(), channel, false),
+        })
+    }
+
+    pub fn flags(&self) -> Vec<&'static str> {
+        let mut flags = vec![if self.enabled { "on" } else { "off" }];
+        if self.nopass {
+            flags.push("nopass");
+        }
+        flags
+    }
+
+    pub fn passwords(&self) -> &[String] {
+        &self.passwords
+    }
+
+    pub fn command_rules(&self) -> String {
+        self.command_rules.join(" ")
+    }
+
+    pub fn key_rules(&self) -> String {
+        self.keys
+            .iter()
+            .map(|k| match (k.read, k.write) {
+                (true, true) => format!("~{}", k.pattern),
+                (true, false) => format!("%R~{}", k.pattern),
+                _ => format!("%W~{}", k.pattern),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn channel_rules(&self) -> String {
+        self.channels
+            .iter()
+            .map(|c| format!("&{}", c))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+// the channels a command names and whether they are patterns, like redis'
+// getChannelsFromCommand; leaving a channel is always allowed
+fn channel_args<'a>(spec: &CommandSpec, args: &'a [RespFrame]) -> Vec<(&'a [u8], bool)> {
+    let (count, is_pattern) = match spec.name {
+        "publish" | "spublish" => (1, false),
+        "subscribe" | "ssubscribe" => (args.len(), false),
+        "psubscribe" => (args.len(), true),
+        _ => return vec![],
+    };
+    args.iter()
+        .skip(1)
+        .take(count)
+        .filter_map(|arg| match arg {
+            RespFrame::BulkString(channel) => Some((channel.as_ref(), is_pattern)),
+            _ => None,
+        })
+        .collect()
+}
+
+// one ACL LIST line, also how users are written to the ACL file:
+// "user default on nopass ~* &* +@all"
+impl fmt::Display for User {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "user {} {}", self.name, self.flags().join(" "))?;
+        for hash in &self.passwords {
+            write!(f, " #{}", hash)?;
+        }
+        if !self.keys.is_empty() {
+            write!(f, " {}", self.key_rules())?;
+        }
+        match self.channels.is_empty() {
+            true => write!(f, " resetchannels")?,
+            false => write!(f, " {}", self.channel_rules())?,
+        }
+        write!(f, " {}", self.command_rules())
+    }
+}
+
+impl AclDenial {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AclDenial::Command => "command",
+            AclDenial::Key => "key",
+            AclDenial::Channel => "channel",
+            AclDenial::Auth => "auth",
+        }
+    }
+}
+
+impl Default for Acl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Acl {
+    pub fn new() -> Self {
+        let users = BTreeMap::from([(DEFAULT_USER.to_string(), User::superuser(DEFAULT_USER))]);
+        Self {
+            users: RwLock::new(users),
+            log: Mutex::new(VecDeque::new()),
+            next_entry_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether a new connection has to AUTH before it can run anything; it doesn't while
+    /// the default user takes any password.
+    pub fn auth_required(&self) -> bool {
+        let users = self.users.read().unwrap();
+        !users
+            .get(DEFAULT_USER)
+            .is_some_and(|user| user.enabled && user.nopass)
+    }
+
+    /// Whether `password` logs in as `username`.
+    pub fn authenticate(&self, username: &str, password: &str) -> bool {
+        let users = self.users.read().unwrap();
+        users
+            .get(username)
+            .is_some_and(|user| user.check_password(password))
+    }
+
+    /// Check a command line against `username`'s permissions.
+    pub fn check_command(
+        &self,
+        username: &str,
+        spec: &CommandSpec,
+        args: &[RespFrame],
+    ) -> Result<(), (AclDenial, String)> {
+        let users = self.users.read().unwrap();
+        match users.get(username) {
+            Some(user) => user.check_command(spec, args),
+            // deleted while logged in, its clients are on their way out
+            None => Err((AclDenial::Command, spec.name.to_string())),
+        }
+    }
+
+    pub fn check_channel(&self, username: &str, channel: &[u8], is_pattern: bool) -> bool {
+        let users = self.users.read().unwrap();
+        users
+            .get(username)
+            .is_some_and(|user| user.check_channel(channel, is_pattern))
+    }
+
+    /// `requirepass`: the default user's only password, or none at all.
+    pub fn set_requirepass(&self, password: Option<&str>) {
+        let mut users = self.users.write().unwrap();
+        let user = users
+            .entry(DEFAULT_USER.to_string())
+            .or_insert_with(|| User::superuser(DEFAULT_USER));
+        let rule = match password {
+            Some(password) => format!(">{}", password),
+            None => "nopass".to_string(),
+        };
+        user.passwords.clear();
+        user.apply(&rule).expect("valid rule");
+    }
+
+    pub fn user(&self, name: &str) -> Option<User> {
+        self.users.read().unwrap().get(name).cloned()
+    }
+
+    pub fn usernames(&self) -> Vec<String> {
+        self.users.read().unwrap().keys().cloned().collect()
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        let users = self.users.read().unwrap();
+        users.values().map(|user| user.to_string()).collect()
+    }
+
+    /// ACL SETUSER: create the user if needed and apply every rule, or none if one fails;
+    /// the error comes with the rule that failed.
+    pub fn set_user(&self, name: &str, rules: &[String]) -> Result<(), (String, AclError)> {
+        let mut users = self.users.write().unwrap();
+        let mut user = users.get(name).cloned().unwrap_or_else(|| User::new(name));
+        for rule in rules {
+            user.apply(rule).map_err(|e| (rule.clone(), e))?;
+        }
+        users.insert(name.to_string(), user);
+        Ok(())
+    }
+
+    /// ACL DELUSER: how many of the users existed.
+    pub fn delete_users(&self, names: &[String]) -> usize {
+        let mut users = self.users.write().unwrap();
+        names
+            .iter()
+            .filter(|name| users.remove(name.as_str()).is_some())
+            .count()
+    }
+
+    /// Replace every user with those of an ACL file; nothing changes if it has an error.
+    /// Returns the users that went away or changed, whose clients should be disconnected.
+    pub fn load(&self, text: &str) -> Result<Vec<String>> {
+        let mut loaded = BTreeMap::new();
+        for (n, line) in text.lines().enumerate() {
+            let fail = |reason: &dyn fmt::Display| anyhow!("line {}: {}", n + 1, reason);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let args = split_args(line.as_bytes())
+                .map_err(|e| fail(&e))?
+                .into_iter()
+                .map(|arg| String::from_utf8_lossy(&arg).into_owned())
+                .collect::<Vec<_>>();
+            let [directive, name, rules @ ..] = args.as_slice() else {
+                return Err(fail(&"should start with user <username>"));
+            };
+            if directive != "user" {
+                return Err(fail(&"should start with user <username>"));
+            }
+            let mut user = User::new(name);
+            for rule in rules {
+                user.apply(rule)
+                    .map_err(|e| fail(&format!("Error in user declaration '{}': {}", rule, e)))?;
+            }
+            if loaded.insert(name.clone(), user).is_some() {
+                return Err(fail(&format!("Duplicate user '{}' found", name)));
+            }
+        }
+        // like redis, a file without the default user leaves it with every permission
+        loaded
+            .entry(DEFAULT_USER.to_string())
+            .or_insert_with(|| User::superuser(DEFAULT_USER));
+
+        let mut users = self.users.write().unwrap();
+        let changed = users
+            .iter()
+            .filter(|(name, user)| loaded.get(*name) != Some(user))
+            .map(|(name, _)| name.clone())
+            .collect();
+        *users = loaded;
+        Ok(changed)
+    }
+
+    pub fn load_file(&self, path: &Path) -> Result<Vec<String>> {
+        let text = std::fs::read_to_string(path)?;
+        self.load(&text)
+            .map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+
+    /// ACL SAVE: write every user out, replacing the file in one go.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut text = self.list().join("\n");
+        text.push('\n');
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let tmp = path.with_file_name(format!(".{}.save", name));
+        std::fs::write(&tmp, text)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Record a denial in ACL LOG, folding it into a recent entry for the same thing.
+    pub fn log(
+        &self,
+        reason: AclDenial,
+        object: &str,
+        username: &str,
+        client_info: String,
+        max_len: usize,
+    ) {
+        let mut log = self.log.lock().unwrap();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or_default();
+        let same = log.iter().position(|entry| {
+            entry.reason == reason
+                && entry.object == object
+                && entry.username == username
+                && entry.last_seen.elapsed() < LOG_ENTRY_GROUPING
+        });
+        let entry = match same.and_then(|i| log.remove(i)) {
+            Some(mut entry) => {
+                entry.count += 1;
+                entry.client_info = client_info;
+                entry.updated_ms = now;
+                entry.last_seen = Instant::now();
+                entry
+            }
+            None => AclLogEntry {
+                count: 1,
+                reason,
+                object: object.to_string(),
+                username: username.to_string(),
+                client_info,
+                entry_id: self.next_entry_id.fetch_add(1, Ordering::Relaxed),
+                created_ms: now,
+                updated_ms: now,
+                last_seen: Instant::now(),
+            },
+        };
+        log.push_front(entry);
+        log.truncate(max_len);
+    }
+
+    /// The latest `count` entries, newest first.
+    pub fn log_entries(&self, count: usize) -> Vec<AclLogEntry> {
+        let log = self.log.lock().unwrap();
+        log.iter().take(count).cloned().collect()
+    }
+
+    pub fn reset_log(&self) {
+        self.log.lock().unwrap().clear();
+    }
+}
+
+impl AclLogEntry {
+    /// Since the last denial it stands for.
+    pub fn age(&self) -> Duration {
+        self.last_seen.elapsed()
+    }
+}
+
+// every command and subcommand in the table
+fn all_specs() -> impl Iterator<Item = &'static CommandSpec> {
+    COMMAND_TABLE
+        .iter()
+        .flat_map(|spec| std::iter::once(spec).chain(spec.subcommands))
+}
+
+fn hash_password(password: &str) -> String {
+    digest(&SHA256, password.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn check_hash(hash: &str) -> Result<String, AclError> {
+    match hash.len() == 64 && hash.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f')) {
+        true => Ok(hash.to_string()),
+        false => Err(AclError::BadHash),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::{lookup_command, resolve_command};
+    use crate::BulkString;
+
+    fn args(args: &[&str]) -> Vec<RespFrame> {
+        args.iter().map(|a| BulkString::from(*a).into()).collect()
+    }
+
+    fn check(user: &User, line: &[&str]) -> Result<(), (AclDenial, String)> {
+        let line = args(line);
+        let spec = resolve_command(&line).expect("a valid command line");
+        user.check_command(spec, &line)
+    }
+
+    #[test]
+    fn test_user_rules() -> Result<(), AclError> {
+        let mut user = User::new("alice");
+        assert_eq!(user.to_string(), "user alice off resetchannels -@all");
+        for rule in [
+            "on",
+            ">secret",
+            "~cache:*",
+            "%R~config:*",
+            "&news.*",
+            "+@read",
+            "-hgetall",
+            "+config|get",
+        ] {
+            user.apply(rule)?;
+        }
+        assert!(user.check_password("secret"));
+        assert!(!user.check_password("guess"));
+        assert_eq!(
+            user.to_string(),
+            format!(
+                "user alice on #{} ~cache:* %R~config:* &news.* -@all +@read -hgetall +config|get",
+                hash_password("secret")
+            )
+        );
+
+        assert_eq!(check(&user, &["get", "cache:1"]), Ok(()));
+        assert_eq!(check(&user, &["get", "config:1"]), Ok(()));
+        assert_eq!(
+            check(&user, &["get", "other"]),
+            Err((AclDenial::Key, "other".to_string()))
+        );
+        assert_eq!(
+            check(&user, &["hgetall", "cache:1"]),
+            Err((AclDenial::Command, "hgetall".to_string()))
+        );
+        assert_eq!(
+            check(&user, &["set", "cache:1", "v"]),
+            Err((AclDenial::Command, "set".to_string()))
+        );
+        assert_eq!(check(&user, &["config", "get", "port"]), Ok(()));
+        assert_eq!(
+            check(&user, &["config", "set", "port", "1"]),
+            Err((AclDenial::Command, "config|set".to_string()))
+        );
+        // logging in is never refused
+        assert_eq!(check(&user, &["auth", "alice", "secret"]), Ok(()));
+
+        // searches read whatever keys they find, which takes read access to all of them
+        user.apply("+ts.mrange")?;
+        assert_eq!(
+            check(&user, &["ts.mrange", "-", "+", "FILTER", "host=a"]),
+            Err((AclDenial::Key, "ts.mrange".to_string()))
+        );
+        user.apply("+ft.search")?;
+        assert_eq!(
+            check(&user, &["ft.search", "idx", "*"]),
+            Err((AclDenial::Key, "ft.search".to_string()))
+        );
+
+        // write access only where the pattern grants it
+        user.apply("+set")?;
+        assert_eq!(check(&user, &["set", "cache:1", "v"]), Ok(()));
+        assert_eq!(
+            check(&user, &["set", "config:1", "v"]),
+            Err((AclDenial::Key, "config:1".to_string()))
+        );
+
+        assert!(user.check_channel(b"news.tech", false));
+        assert!(!user.check_channel(b"sports", false));
+        assert!(user.check_channel(b"news.*", true));
+        assert!(!user.check_channel(b"news.t*", true));
+
+        assert_eq!(user.apply("<guess"), Err(AclError::NoSuchPassword));
+        assert_eq!(user.apply("#abc"), Err(AclError::BadHash));
+        assert_eq!(user.apply("+nope"), Err(AclError::UnknownCommand));
+        assert_eq!(user.apply("+@nope"), Err(AclError::UnknownCommand));
+        assert_eq!(user.apply("%X~key"), Err(AclError::Syntax));
+        assert_eq!(user.apply("bogus"), Err(AclError::Syntax));
+
+        user.apply("%R~*")?;
+        assert_eq!(check(&user, &["ft.search", "idx", "*"]), Ok(()));
+        assert_eq!(
+            check(&user, &["ts.mrange", "-", "+", "FILTER", "host=a"]),
+            Ok(())
+        );
+
+        user.apply("reset")?;
+        assert_eq!(user, User::new("alice"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_acl_users_and_requirepass() {
+        let acl = Acl::new();
+        assert!(!acl.auth_required());
+        assert_eq!(acl.list(), vec!["user default on nopass ~* &* +@all"]);
+
+        acl.set_requirepass(Some("pw"));
+        assert!(acl.auth_required());
+        assert!(acl.authenticate(DEFAULT_USER, "pw"));
+        assert!(!acl.authenticate(DEFAULT_USER, "nope"));
+        acl.set_requirepass(None);
+        assert!(!acl.auth_required());
+
+        let rules = ["on", "nopass", "+get", "bogus", "~*"].map(String::from);
+        assert_eq!(
+            acl.set_user("bob", &rules),
+            Err(("bogus".to_string(), AclError::Syntax))
+        );
+        assert_eq!(acl.user("bob"), None);
+        acl.set_user("bob", &rules[..3]).unwrap();
+        assert!(acl.authenticate("bob", "anything"));
+        let get = lookup_command(b"get").unwrap();
+        assert_eq!(
+            acl.check_command("bob", get, &args(&["get", "k"])),
+            Err((AclDenial::Key, "k".to_string()))
+        );
+        assert_eq!(acl.usernames(), vec!["bob", "default"]);
+        assert_eq!(
+            acl.delete_users(&["bob".to_string(), "carol".to_string()]),
+            1
+        );
+        assert!(!acl.authenticate("bob", "anything"));
+    }
+
+    #[test]
+    fn test_acl_load_and_save() -> Result<()> {
+        let acl = Acl::new();
+        acl.set_user("stale", &["on".to_string()]).unwrap();
+        let changed = acl.load(
+            "# users\n\
+             user default on >pw ~* &* +@all\n\
+             user alice on nopass sanitize-payload %R~* resetchannels -@all +get\n",
+        )?;
+        assert_eq!(changed, vec!["default", "stale"]);
+        assert!(acl.auth_required());
+        assert!(acl.authenticate("alice", "x"));
+        assert_eq!(acl.usernames(), vec!["alice", "default"]);
+
+        let err = acl.load("user bob on\nuser bob off\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: Duplicate user 'bob' found");
+        let err = acl.load("user bob on +nope\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 1: Error in user declaration '+nope': Unknown command or category name in ACL"
+        );
+        assert!(acl.load("alice on\n").is_err());
+        // nothing changed, and a file without the default user gets a permissive one
+        assert_eq!(acl.usernames(), vec!["alice", "default"]);
+        acl.load("user alice on nopass\n")?;
+        assert!(!acl.auth_required());
+
+        let path =
+            std::env::temp_dir().join(format!("simple-redis-users-{}.acl", std::process::id()));
+        acl.save(&path)?;
+        let saved = std::fs::read_to_string(&path)?;
+        let reloaded = Acl::new();
+        reloaded.load_file(&path)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(
+            saved,
+            "user alice on nopass resetchannels -@all\n\
+             user default on nopass ~* &* +@all\n"
+        );
+        assert_eq!(reloaded.list(), acl.list());
+        Ok(())
+    }
+
+    #[test]
+    fn test_acl_log() {
+        let acl = Acl::new();
+        acl.log(AclDenial::Command, "get", "alice", "id=1".to_string(), 2);
+        acl.log(AclDenial::Key, "k", "alice", "id=1".to_string(), 2);
+        acl.log(AclDenial::Command, "get", "alice", "id=2".to_string(), 2);
+        let entries = acl.log_entries(10);
+        assert_eq!(entries.len(), 2);
+        // the repeat moved to the front and counts both
+        assert_eq!((entries[0].object.as_str(), entries[0].count), ("get", 2));
+        assert_eq!(entries[0].client_info, "id=2");
+        assert_eq!((entries[1].object.as_str(), entries[1].entry_id), ("k", 1));
+
+        acl.log(AclDenial::Auth, "AUTH", "bob", "id=3".to_string(), 2);
+        let entries = acl.log_entries(10);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].reason, AclDenial::Auth);
+        assert_eq!(acl.log_entries(1).len(), 1);
+        acl.reset_log();
+        assert!(acl.log_entries(10).is_empty());
+    }
+}
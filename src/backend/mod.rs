@@ -1,38 +1,155 @@
-use crate::RespFrame;
+mod search;
+mod timeseries;
+mod vset;
+
+pub use search::*;
+pub use timeseries::*;
+pub use vset::*;
+
+use crate::{Acl, ClientRegistry, Config, ConfigError, PubSub, RespFrame, ShutdownCoordinator};
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
 use std::ops::Deref;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+pub const DEFAULT_DATABASES: usize = 16;
 
+/// A handle on the server's data, working on one of its databases.
+///
+/// Cloning is cheap; [`Backend::select`] gives a handle on another database that shares the
+/// same databases, client registry and configuration.
 #[derive(Debug, Clone)]
+pub struct Backend {
+    shared: Arc<BackendInner>,
+    index: usize,
+    db: Arc<Db>,
+}
 
-pub struct Backend(Arc<BackendInner>);
+/// The kind of value a key holds; each lives in its own map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    String,
+    Hash,
+    TimeSeries,
+    VectorSet,
+}
 
 #[derive(Debug)]
 pub struct BackendInner {
+    dbs: RwLock<Vec<Arc<Db>>>,
+    clients: ClientRegistry,
+    config: RwLock<Config>,
+    acl: Acl,
+    pubsub: PubSub,
+    stats: ServerStats,
+    shutdown: ShutdownCoordinator,
+}
+
+/// Server wide counters, CONFIG RESETSTAT sets them back to zero.
+#[derive(Debug, Default)]
+pub struct ServerStats {
+    pub connections_received: AtomicU64,
+    pub commands_processed: AtomicU64,
+    /// Turned away because of maxclients.
+    pub rejected_connections: AtomicU64,
+    /// Closed for going over client-output-buffer-limit.
+    pub output_buffer_limit_disconnections: AtomicU64,
+}
+
+/// One logical database, as picked with SELECT.
+#[derive(Debug, Default)]
+pub struct Db {
     pub(crate) map: DashMap<String, RespFrame>,
     pub(crate) hmap: DashMap<String, DashMap<String, RespFrame>>,
+    pub(crate) ts: DashMap<String, TimeSeries>,
+    pub(crate) vset: DashMap<String, VectorSet>,
+    pub(crate) indexes: DashMap<String, SearchIndex>,
 }
 
 impl Deref for Backend {
-    type Target = BackendInner;
+    type Target = Db;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.db
     }
 }
 
-impl Default for BackendInner {
+impl Default for Backend {
     fn default() -> Self {
-        Self {
-            map: DashMap::new(),
-            hmap: DashMap::new(),
+        Self::with_config(Config::default())
+    }
+}
+
+impl ServerStats {
+    pub fn reset(&self) {
+        self.connections_received.store(0, Ordering::Relaxed);
+        self.commands_processed.store(0, Ordering::Relaxed);
+        self.rejected_connections.store(0, Ordering::Relaxed);
+        self.output_buffer_limit_disconnections
+            .store(0, Ordering::Relaxed);
+    }
+}
+
+impl Db {
+    // an empty database for FLUSHDB to put in place of this one, FT indexes aren't keys so
+    // their definitions stay, with nothing indexed
+    fn emptied(&self) -> Db {
+        let indexes = self
+            .indexes
+            .iter()
+            .map(|index| (index.key().clone(), index.emptied()))
+            .collect();
+        Db {
+            indexes,
+            ..Default::default()
         }
     }
+
+    fn clear(&self) {
+        self.map.clear();
+        self.hmap.clear();
+        self.ts.clear();
+        self.vset.clear();
+        self.indexes.clear();
+    }
 }
 
-impl Default for Backend {
-    fn default() -> Self {
-        Self(Arc::new(BackendInner::default()))
+// both slots stay locked from the checks to the insert, so a key written to the destination
+// meanwhile is never overwritten; they are taken in database order so two MOVEs the other
+// way round can't deadlock
+fn move_entry<V>(
+    src: &DashMap<String, V>,
+    dst: &DashMap<String, V>,
+    key: &str,
+    src_first: bool,
+) -> bool {
+    let (src_entry, dst_entry) = match src_first {
+        true => {
+            let src_entry = src.entry(key.to_string());
+            (src_entry, dst.entry(key.to_string()))
+        }
+        false => {
+            let dst_entry = dst.entry(key.to_string());
+            (src.entry(key.to_string()), dst_entry)
+        }
+    };
+    match (src_entry, dst_entry) {
+        (Entry::Occupied(src_entry), Entry::Vacant(dst_entry)) => {
+            dst_entry.insert(src_entry.remove());
+            true
+        }
+        _ => false,
+    }
+}
+
+// handles still working on a flushed database keep it alive, so empty it rather than
+// waiting for the last one to go; lazily on a thread of its own
+fn free(dbs: Vec<Arc<Db>>, lazy: bool) {
+    let clear = move || dbs.iter().for_each(|db| db.clear());
+    match lazy {
+        true => drop(std::thread::spawn(clear)),
+        false => clear(),
     }
 }
 
@@ -41,11 +158,168 @@ impl Backend {
         Self::default()
     }
 
+    pub fn with_databases(databases: usize) -> Self {
+        Self::with_config(Config {
+            databases,
+            ..Default::default()
+        })
+    }
+
+    pub fn with_config(config: Config) -> Self {
+        let dbs = (0..config.databases.max(1))
+            .map(|_| Arc::new(Db::default()))
+            .collect::<Vec<_>>();
+        let db = dbs[0].clone();
+        let acl = Acl::new();
+        acl.set_requirepass(config.requirepass.as_deref());
+        let shared = Arc::new(BackendInner {
+            dbs: RwLock::new(dbs),
+            clients: ClientRegistry::new(),
+            config: RwLock::new(config),
+            acl,
+            pubsub: PubSub::new(),
+            stats: ServerStats::default(),
+            shutdown: ShutdownCoordinator::new(),
+        });
+        Self {
+            shared,
+            index: 0,
+            db,
+        }
+    }
+
+    pub fn databases(&self) -> usize {
+        self.shared.dbs.read().unwrap().len()
+    }
+
+    /// The index of the database this handle works on.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// A handle on another database sharing everything else, `None` if out of range.
+    pub fn select(&self, index: usize) -> Option<Backend> {
+        let db = self.shared.dbs.read().unwrap().get(index)?.clone();
+        Some(Self {
+            shared: self.shared.clone(),
+            index,
+            db,
+        })
+    }
+
+    pub fn clients(&self) -> &ClientRegistry {
+        &self.shared.clients
+    }
+
+    /// The current settings; don't hold on to the guard across an await.
+    pub fn config(&self) -> RwLockReadGuard<'_, Config> {
+        self.shared.config.read().unwrap()
+    }
+
+    /// CONFIG SET: change mutable parameters, all of them or none.
+    pub fn update_config(&self, changes: &[(String, String)]) -> Result<(), ConfigError> {
+        let mut config = self.shared.config.write().unwrap();
+        let requirepass = config.requirepass.clone();
+        config.update(changes)?;
+        // requirepass is the default user's password
+        if config.requirepass != requirepass {
+            self.shared
+                .acl
+                .set_requirepass(config.requirepass.as_deref());
+        }
+        Ok(())
+    }
+
+    pub fn acl(&self) -> &Acl {
+        &self.shared.acl
+    }
+
+    pub fn pubsub(&self) -> &PubSub {
+        &self.shared.pubsub
+    }
+
+    pub fn stats(&self) -> &ServerStats {
+        &self.shared.stats
+    }
+
+    pub fn shutdown(&self) -> &ShutdownCoordinator {
+        &self.shared.shutdown
+    }
+
+    pub fn dbsize(&self) -> usize {
+        self.map.len() + self.hmap.len() + self.ts.len() + self.vset.len()
+    }
+
+    /// Move a key to another database, unless it is missing here or already exists there.
+    pub fn move_key(&self, key: &str, dst: &Backend) -> bool {
+        if dst.key_type(key).is_some() {
+            return false;
+        }
+        let src_first = self.index < dst.index;
+        match self.key_type(key) {
+            Some(KeyType::String) => move_entry(&self.map, &dst.map, key, src_first),
+            Some(KeyType::Hash) => {
+                let moved = move_entry(&self.hmap, &dst.hmap, key, src_first);
+                self.reindex(key);
+                dst.reindex(key);
+                moved
+            }
+            Some(KeyType::TimeSeries) => move_entry(&self.ts, &dst.ts, key, src_first),
+            Some(KeyType::VectorSet) => move_entry(&self.vset, &dst.vset, key, src_first),
+            None => false,
+        }
+    }
+
+    /// Exchange two databases, clients that selected one now see the other.
+    pub fn swap_db(&self, first: usize, second: usize) {
+        self.shared.dbs.write().unwrap().swap(first, second);
+    }
+
+    pub fn flush_db(&self, lazy: bool) {
+        let mut dbs = self.shared.dbs.write().unwrap();
+        let db = &mut dbs[self.index];
+        let old = std::mem::replace(db, Arc::new(db.emptied()));
+        drop(dbs);
+        free(vec![old], lazy);
+    }
+
+    pub fn flush_all(&self, lazy: bool) {
+        let old = self
+            .shared
+            .dbs
+            .write()
+            .unwrap()
+            .iter_mut()
+            .map(|db| std::mem::replace(db, Arc::new(db.emptied())))
+            .collect();
+        free(old, lazy);
+    }
+
+    pub fn key_type(&self, key: &str) -> Option<KeyType> {
+        if self.map.contains_key(key) {
+            Some(KeyType::String)
+        } else if self.hmap.contains_key(key) {
+            Some(KeyType::Hash)
+        } else if self.ts.contains_key(key) {
+            Some(KeyType::TimeSeries)
+        } else if self.vset.contains_key(key) {
+            Some(KeyType::VectorSet)
+        } else {
+            None
+        }
+    }
+
     pub fn get(&self, key: &str) -> Option<RespFrame> {
         self.map.get(key).map(|v| v.value().clone())
     }
 
     pub fn set(&self, key: String, value: RespFrame) {
+        // SET replaces the key whatever it held before
+        if self.hmap.remove(&key).is_some() {
+            self.reindex(&key);
+        }
+        self.ts.remove(&key);
+        self.vset.remove(&key);
         self.map.insert(key, value);
     }
 
@@ -56,8 +330,10 @@ impl Backend {
     }
 
     pub fn hset(&self, key: String, field: String, value: RespFrame) {
-        let hmap = self.hmap.entry(key).or_default();
+        let hmap = self.hmap.entry(key.clone()).or_default();
         hmap.insert(field, value);
+        drop(hmap);
+        self.reindex(&key);
     }
 
     pub fn hgetall(&self, key: &str) -> Option<DashMap<String, RespFrame>> {
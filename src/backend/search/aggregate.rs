@@ -0,0 +1,188 @@
+use super::{compare_values, Document, Query, SearchError, SearchIndex};
+use std::collections::{BTreeMap, HashSet};
+
+/// The FT.AGGREGATE pipeline subset: LOAD, a single GROUPBY with reducers, SORTBY and LIMIT.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateRequest {
+    pub query: Query,
+    pub load: Vec<String>,
+    pub group_by: Option<(Vec<String>, Vec<Reducer>)>,
+    pub sort_by: Vec<(String, bool)>,
+    pub limit: Option<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reducer {
+    pub kind: ReducerKind,
+    pub alias: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReducerKind {
+    Count,
+    CountDistinct(String),
+    Sum(String),
+    Avg(String),
+    Min(String),
+    Max(String),
+}
+
+impl AggregateRequest {
+    pub(crate) fn run(&self, index: &SearchIndex) -> Result<Vec<Document>, SearchError> {
+        let docs = self.query.eval(index);
+
+        let mut rows = docs
+            .iter()
+            .map(|key| {
+                self.load
+                    .iter()
+                    .chain(self.group_by.iter().flat_map(|(fields, _)| fields))
+                    .chain(
+                        self.group_by
+                            .iter()
+                            .flat_map(|(_, r)| r.iter().filter_map(|r| r.kind.field())),
+                    )
+                    .filter_map(|attr| {
+                        let field = index.field_of(attr).unwrap_or_else(|_| attr.clone());
+                        index
+                            .value(key, &field)
+                            .map(|v| (attr.clone(), v.to_string()))
+                    })
+                    .collect::<Document>()
+            })
+            .collect::<Vec<_>>();
+
+        if let Some((fields, reducers)) = &self.group_by {
+            rows = group(rows, fields, reducers);
+        }
+
+        if let Some((attr, _)) = self.sort_by.iter().find(|(attr, _)| !self.produces(attr)) {
+            return Err(SearchError::UnknownProperty(attr.clone()));
+        }
+        if !self.sort_by.is_empty() {
+            rows.sort_by(|a, b| {
+                self.sort_by
+                    .iter()
+                    .map(|(attr, asc)| {
+                        let ord = compare_values(get(a, attr), get(b, attr));
+                        match asc {
+                            true => ord,
+                            false => ord.reverse(),
+                        }
+                    })
+                    .find(|o| o.is_ne())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+        }
+
+        if let Some((offset, num)) = self.limit {
+            rows = rows.into_iter().skip(offset).take(num).collect();
+        }
+
+        Ok(rows)
+    }
+
+    fn produces(&self, attr: &str) -> bool {
+        match &self.group_by {
+            Some((fields, reducers)) => {
+                fields.iter().any(|f| f == attr) || reducers.iter().any(|r| r.name() == attr)
+            }
+            None => self.load.iter().any(|f| f == attr),
+        }
+    }
+}
+
+impl Reducer {
+    /// The output property name, e.g. `__generated_aliassumprice` without AS.
+    pub fn name(&self) -> String {
+        if let Some(alias) = &self.alias {
+            return alias.clone();
+        }
+        match &self.kind {
+            ReducerKind::Count => "__generated_aliascount".to_string(),
+            ReducerKind::CountDistinct(f) => format!("__generated_aliascount_distinct{}", f),
+            ReducerKind::Sum(f) => format!("__generated_aliassum{}", f),
+            ReducerKind::Avg(f) => format!("__generated_aliasavg{}", f),
+            ReducerKind::Min(f) => format!("__generated_aliasmin{}", f),
+            ReducerKind::Max(f) => format!("__generated_aliasmax{}", f),
+        }
+    }
+
+    fn reduce(&self, rows: &[&Document]) -> String {
+        let numbers = |f: &str| {
+            rows.iter()
+                .filter_map(|r| get(r, f).and_then(|v| v.parse::<f64>().ok()))
+                .collect::<Vec<_>>()
+        };
+        let value = match &self.kind {
+            ReducerKind::Count => rows.len() as f64,
+            ReducerKind::CountDistinct(f) => rows
+                .iter()
+                .filter_map(|r| get(r, f))
+                .collect::<HashSet<_>>()
+                .len() as f64,
+            ReducerKind::Sum(f) => numbers(f).iter().sum(),
+            ReducerKind::Avg(f) => {
+                let n = numbers(f);
+                match n.is_empty() {
+                    true => 0.0,
+                    false => n.iter().sum::<f64>() / n.len() as f64,
+                }
+            }
+            ReducerKind::Min(f) => numbers(f).into_iter().fold(f64::INFINITY, f64::min),
+            ReducerKind::Max(f) => numbers(f).into_iter().fold(f64::NEG_INFINITY, f64::max),
+        };
+        format_number(value)
+    }
+}
+
+impl ReducerKind {
+    fn field(&self) -> Option<&String> {
+        match self {
+            ReducerKind::Count => None,
+            ReducerKind::CountDistinct(f)
+            | ReducerKind::Sum(f)
+            | ReducerKind::Avg(f)
+            | ReducerKind::Min(f)
+            | ReducerKind::Max(f) => Some(f),
+        }
+    }
+}
+
+fn group(rows: Vec<Document>, fields: &[String], reducers: &[Reducer]) -> Vec<Document> {
+    let mut groups: BTreeMap<Vec<Option<String>>, Vec<&Document>> = BTreeMap::new();
+    for row in &rows {
+        let key = fields
+            .iter()
+            .map(|f| get(row, f).map(|v| v.to_string()))
+            .collect();
+        groups.entry(key).or_default().push(row);
+    }
+
+    groups
+        .into_iter()
+        .map(|(key, members)| {
+            let mut row = fields
+                .iter()
+                .zip(key)
+                .filter_map(|(f, v)| v.map(|v| (f.clone(), v)))
+                .collect::<Document>();
+            for r in reducers {
+                row.push((r.name(), r.reduce(&members)));
+            }
+            row
+        })
+        .collect()
+}
+
+fn get<'a>(row: &'a Document, attr: &str) -> Option<&'a str> {
+    row.iter().find(|(k, _)| k == attr).map(|(_, v)| v.as_str())
+}
+
+fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        n.to_string()
+    }
+}
@@ -0,0 +1,469 @@
+mod aggregate;
+mod query;
+
+pub use aggregate::{AggregateRequest, Reducer, ReducerKind};
+pub use query::Query;
+
+use super::Backend;
+use crate::RespFrame;
+use dashmap::mapref::entry::Entry;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Bound;
+use thiserror::Error;
+
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "if", "in", "into", "is", "it",
+    "no", "not", "of", "on", "or", "such", "that", "the", "their", "then", "there", "these",
+    "they", "this", "to", "was", "will", "with",
+];
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SearchError {
+    #[error("Index already exists")]
+    IndexExists,
+    #[error("{0}: no such index")]
+    UnknownIndex(String),
+    #[error("Unknown field `{0}`")]
+    UnknownField(String),
+    #[error("Property `{0}` not loaded nor in schema")]
+    UnknownProperty(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldType {
+    Text,
+    Numeric,
+    Tag { separator: char },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaField {
+    pub field: String,
+    pub attribute: String,
+    pub kind: FieldType,
+    pub sortable: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexDefinition {
+    pub prefixes: Vec<String>,
+    pub schema: Vec<SchemaField>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchOptions {
+    pub no_content: bool,
+    pub return_fields: Option<Vec<String>>,
+    pub sort_by: Option<(String, bool)>,
+    pub offset: usize,
+    pub num: usize,
+}
+
+pub type Document = Vec<(String, String)>;
+
+/// The total number of matches and the requested page of `(key, fields)`.
+pub type SearchResult = (usize, Vec<(String, Option<Document>)>);
+
+/// Secondary indexes over hashes whose keys match one of the definition's prefixes.
+#[derive(Debug, Clone, Default)]
+pub struct SearchIndex {
+    def: IndexDefinition,
+    docs: HashMap<String, HashMap<String, String>>,
+    text: HashMap<String, HashMap<String, BTreeSet<String>>>,
+    numeric: HashMap<String, BTreeMap<NumKey, BTreeSet<String>>>,
+    tags: HashMap<String, HashMap<String, BTreeSet<String>>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct NumKey(f64);
+
+impl SearchIndex {
+    pub fn new(def: IndexDefinition) -> Self {
+        Self {
+            def,
+            ..Default::default()
+        }
+    }
+
+    /// The same index with nothing indexed yet, as a flushed database keeps it.
+    pub fn emptied(&self) -> Self {
+        Self::new(self.def.clone())
+    }
+
+    pub fn len(&self) -> usize {
+        self.docs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.docs.is_empty()
+    }
+
+    fn covers(&self, key: &str) -> bool {
+        self.def.prefixes.is_empty() || self.def.prefixes.iter().any(|p| key.starts_with(p))
+    }
+
+    /// Replace whatever was indexed for `key` with the given hash contents.
+    pub fn index(&mut self, key: &str, hash: Option<HashMap<String, String>>) {
+        self.unindex(key);
+        let Some(hash) = hash else {
+            return;
+        };
+        if !self.covers(key) {
+            return;
+        }
+
+        let mut indexed = false;
+        for f in &self.def.schema {
+            let Some(value) = hash.get(&f.field) else {
+                continue;
+            };
+            match f.kind {
+                FieldType::Text => {
+                    let postings = self.text.entry(f.attribute.clone()).or_default();
+                    for term in tokenize(value) {
+                        postings.entry(term).or_default().insert(key.to_string());
+                    }
+                    indexed = true;
+                }
+                FieldType::Numeric => {
+                    if let Ok(n) = value.trim().parse::<f64>() {
+                        self.numeric
+                            .entry(f.attribute.clone())
+                            .or_default()
+                            .entry(NumKey(n))
+                            .or_default()
+                            .insert(key.to_string());
+                        indexed = true;
+                    }
+                }
+                FieldType::Tag { separator } => {
+                    let postings = self.tags.entry(f.attribute.clone()).or_default();
+                    for tag in split_tags(value, separator) {
+                        postings.entry(tag).or_default().insert(key.to_string());
+                    }
+                    indexed = true;
+                }
+            }
+        }
+
+        if indexed {
+            self.docs.insert(key.to_string(), hash);
+        }
+    }
+
+    fn unindex(&mut self, key: &str) {
+        let Some(old) = self.docs.remove(key) else {
+            return;
+        };
+        for f in &self.def.schema {
+            let Some(value) = old.get(&f.field) else {
+                continue;
+            };
+            match f.kind {
+                FieldType::Text => {
+                    if let Some(postings) = self.text.get_mut(&f.attribute) {
+                        for term in tokenize(value) {
+                            remove_posting(postings, &term, key);
+                        }
+                    }
+                }
+                FieldType::Numeric => {
+                    if let (Some(postings), Ok(n)) =
+                        (self.numeric.get_mut(&f.attribute), value.trim().parse())
+                    {
+                        let n = NumKey(n);
+                        if let Some(docs) = postings.get_mut(&n) {
+                            docs.remove(key);
+                            if docs.is_empty() {
+                                postings.remove(&n);
+                            }
+                        }
+                    }
+                }
+                FieldType::Tag { separator } => {
+                    if let Some(postings) = self.tags.get_mut(&f.attribute) {
+                        for tag in split_tags(value, separator) {
+                            remove_posting(postings, &tag, key);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn search(&self, query: &Query, opts: &SearchOptions) -> Result<SearchResult, SearchError> {
+        let mut docs = query.eval(self).into_iter().collect::<Vec<_>>();
+        let total = docs.len();
+
+        if let Some((attr, asc)) = &opts.sort_by {
+            let field = self.field_of(attr)?;
+            docs.sort_by(|a, b| {
+                let ord = compare_values(self.value(a, &field), self.value(b, &field));
+                match asc {
+                    true => ord,
+                    false => ord.reverse(),
+                }
+            });
+        }
+
+        let ret = docs
+            .into_iter()
+            .skip(opts.offset)
+            .take(opts.num)
+            .map(|key| {
+                if opts.no_content {
+                    return (key, None);
+                }
+                let doc = self.document(&key, opts.return_fields.as_deref());
+                (key, Some(doc))
+            })
+            .collect();
+        Ok((total, ret))
+    }
+
+    fn document(&self, key: &str, fields: Option<&[String]>) -> Document {
+        let Some(hash) = self.docs.get(key) else {
+            return vec![];
+        };
+        match fields {
+            Some(fields) => fields
+                .iter()
+                .filter_map(|attr| {
+                    let field = self.field_of(attr).unwrap_or_else(|_| attr.clone());
+                    hash.get(&field).map(|v| (attr.clone(), v.clone()))
+                })
+                .collect(),
+            None => {
+                let mut doc = hash
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect::<Vec<_>>();
+                doc.sort();
+                doc
+            }
+        }
+    }
+
+    fn value(&self, key: &str, field: &str) -> Option<&str> {
+        self.docs
+            .get(key)
+            .and_then(|h| h.get(field))
+            .map(|v| v.as_str())
+    }
+
+    /// Map a query attribute (schema alias) to the underlying hash field.
+    fn field_of(&self, attr: &str) -> Result<String, SearchError> {
+        self.def
+            .schema
+            .iter()
+            .find(|f| f.attribute == attr)
+            .map(|f| f.field.clone())
+            .ok_or_else(|| SearchError::UnknownField(attr.to_string()))
+    }
+
+    fn term_postings(&self, attr: Option<&str>, term: &str) -> BTreeSet<String> {
+        self.text
+            .iter()
+            .filter(|(a, _)| attr.map(|x| x == a.as_str()).unwrap_or(true))
+            .filter_map(|(_, postings)| postings.get(term))
+            .flat_map(|docs| docs.iter().cloned())
+            .collect()
+    }
+
+    // every term of the dictionary has to be looked at, so only for prefixes
+    fn prefix_postings(&self, attr: Option<&str>, prefix: &str) -> BTreeSet<String> {
+        self.text
+            .iter()
+            .filter(|(a, _)| attr.map(|x| x == a.as_str()).unwrap_or(true))
+            .flat_map(|(_, postings)| {
+                postings
+                    .iter()
+                    .filter(|(t, _)| t.starts_with(prefix))
+                    .flat_map(|(_, docs)| docs.iter().cloned())
+            })
+            .collect()
+    }
+
+    fn has_phrase(&self, key: &str, attr: Option<&str>, terms: &[String]) -> bool {
+        if terms.is_empty() {
+            return true;
+        }
+        self.def
+            .schema
+            .iter()
+            .filter(|f| f.kind == FieldType::Text)
+            .filter(|f| attr.map(|a| a == f.attribute).unwrap_or(true))
+            .filter_map(|f| self.value(key, &f.field))
+            .any(|v| tokenize(v).windows(terms.len()).any(|w| w == terms))
+    }
+
+    fn numeric_range(&self, attr: &str, min: Bound<f64>, max: Bound<f64>) -> BTreeSet<String> {
+        let Some(postings) = self.numeric.get(attr) else {
+            return BTreeSet::new();
+        };
+        // BTreeMap::range panics on inverted bounds; compared the way NumKey orders them, so
+        // e.g. [0 -0] counts as inverted too
+        if let (Some(lo), Some(hi)) = (value_of(min), value_of(max)) {
+            let exclusive = matches!(min, Bound::Excluded(_)) || matches!(max, Bound::Excluded(_));
+            match lo.total_cmp(&hi) {
+                Ordering::Greater => return BTreeSet::new(),
+                Ordering::Equal if exclusive => return BTreeSet::new(),
+                _ => {}
+            }
+        }
+        postings
+            .range((min.map(NumKey), max.map(NumKey)))
+            .flat_map(|(_, docs)| docs.iter().cloned())
+            .collect()
+    }
+
+    fn tag_postings(&self, attr: &str, tags: &[String]) -> BTreeSet<String> {
+        let Some(postings) = self.tags.get(attr) else {
+            return BTreeSet::new();
+        };
+        tags.iter()
+            .filter_map(|t| postings.get(t))
+            .flat_map(|docs| docs.iter().cloned())
+            .collect()
+    }
+}
+
+fn value_of(b: Bound<f64>) -> Option<f64> {
+    match b {
+        Bound::Included(v) | Bound::Excluded(v) => Some(v),
+        Bound::Unbounded => None,
+    }
+}
+
+fn remove_posting(postings: &mut HashMap<String, BTreeSet<String>>, term: &str, key: &str) {
+    if let Some(docs) = postings.get_mut(term) {
+        docs.remove(key);
+        if docs.is_empty() {
+            postings.remove(term);
+        }
+    }
+}
+
+pub(crate) fn tokenize(s: &str) -> Vec<String> {
+    s.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .filter(|t| !STOPWORDS.contains(&t.as_str()))
+        .collect()
+}
+
+fn split_tags(s: &str, separator: char) -> Vec<String> {
+    s.split(separator)
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// Numbers sort numerically, everything else lexicographically, missing values last.
+pub(crate) fn compare_values(a: Option<&str>, b: Option<&str>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => match (a.parse::<f64>(), b.parse::<f64>()) {
+            (Ok(x), Ok(y)) => x.total_cmp(&y),
+            _ => a.cmp(b),
+        },
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn frame_to_string(frame: &RespFrame) -> Option<String> {
+    match frame {
+        RespFrame::BulkString(s) => Some(String::from_utf8_lossy(s).to_string()),
+        RespFrame::SimpleString(s) => Some(s.to_string()),
+        RespFrame::Integer(n) => Some(n.to_string()),
+        RespFrame::Double(n) => Some(n.to_string()),
+        RespFrame::Boolean(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+impl Eq for NumKey {}
+
+impl PartialOrd for NumKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for NumKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl Backend {
+    pub fn ft_create(&self, name: String, def: IndexDefinition) -> Result<(), SearchError> {
+        // claimed before anything is scanned, so a duplicate fails cheaply
+        match self.indexes.entry(name.clone()) {
+            Entry::Occupied(_) => return Err(SearchError::IndexExists),
+            Entry::Vacant(entry) => entry.insert(SearchIndex::new(def)),
+        };
+        // like `reindex`, each hash is indexed under its lock, so a write racing with the
+        // scan is either seen here or reindexed after it
+        for entry in self.hmap.iter() {
+            if let Some(mut index) = self.indexes.get_mut(&name) {
+                if index.covers(entry.key()) {
+                    index.index(entry.key(), Some(hash_snapshot(entry.value())));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn ft_search(
+        &self,
+        name: &str,
+        query: &Query,
+        opts: &SearchOptions,
+    ) -> Result<SearchResult, SearchError> {
+        let index = self
+            .indexes
+            .get(name)
+            .ok_or_else(|| SearchError::UnknownIndex(name.to_string()))?;
+        index.search(query, opts)
+    }
+
+    pub fn ft_aggregate(
+        &self,
+        name: &str,
+        req: &AggregateRequest,
+    ) -> Result<Vec<Document>, SearchError> {
+        let index = self
+            .indexes
+            .get(name)
+            .ok_or_else(|| SearchError::UnknownIndex(name.to_string()))?;
+        req.run(&index)
+    }
+
+    /// Bring every index covering `key` in line with the current contents of the hash.
+    pub(crate) fn reindex(&self, key: &str) {
+        if self.indexes.is_empty() {
+            return;
+        }
+        // the hash stays locked until every index is updated, so writers racing on the same
+        // key can't leave an older snapshot indexed last
+        let entry = self.hmap.entry(key.to_string());
+        let hash = match &entry {
+            Entry::Occupied(hash) => Some(hash_snapshot(hash.get())),
+            Entry::Vacant(_) => None,
+        };
+        for mut index in self.indexes.iter_mut() {
+            if index.covers(key) {
+                index.index(key, hash.clone());
+            }
+        }
+    }
+}
+
+fn hash_snapshot(hash: &dashmap::DashMap<String, RespFrame>) -> HashMap<String, String> {
+    hash.iter()
+        .filter_map(|e| frame_to_string(e.value()).map(|v| (e.key().clone(), v)))
+        .collect()
+}
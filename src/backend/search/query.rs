@@ -0,0 +1,379 @@
+use super::{tokenize, SearchIndex, STOPWORDS};
+use std::collections::BTreeSet;
+use std::ops::Bound;
+
+/// A parsed FT.SEARCH query.
+///
+/// As in RediSearch, juxtaposed terms are intersected and `|` binds tighter than
+/// the implicit AND, so `hello world|planet` means `hello (world|planet)`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    All,
+    Term {
+        field: Option<String>,
+        term: String,
+    },
+    Prefix {
+        field: Option<String>,
+        prefix: String,
+    },
+    Phrase {
+        field: Option<String>,
+        terms: Vec<String>,
+    },
+    Numeric {
+        field: String,
+        min: Bound<f64>,
+        max: Bound<f64>,
+    },
+    Tag {
+        field: String,
+        tags: Vec<String>,
+    },
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    Field(String),
+    Range(String),
+    Tags(String),
+    Colon,
+    Pipe,
+    Minus,
+    LParen,
+    RParen,
+    Star,
+}
+
+impl Query {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let tokens = lex(s)?;
+        if tokens.is_empty() {
+            return Err("empty query".to_string());
+        }
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            depth: 0,
+        };
+        let query = parser.and(None)?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(query),
+            Some(t) => Err(format!("syntax error near {:?}", t)),
+        }
+    }
+
+    pub(crate) fn eval(&self, index: &SearchIndex) -> BTreeSet<String> {
+        match self {
+            Query::All => index.docs.keys().cloned().collect(),
+            Query::Term { field, term } => index.term_postings(field.as_deref(), term),
+            Query::Prefix { field, prefix } => index.prefix_postings(field.as_deref(), prefix),
+            Query::Phrase { field, terms } => {
+                let mut docs: Option<BTreeSet<String>> = None;
+                for term in terms {
+                    let found = index.term_postings(field.as_deref(), term);
+                    docs = Some(match docs {
+                        Some(d) => d.intersection(&found).cloned().collect(),
+                        None => found,
+                    });
+                }
+                docs.unwrap_or_default()
+                    .into_iter()
+                    .filter(|doc| index.has_phrase(doc, field.as_deref(), terms))
+                    .collect()
+            }
+            Query::Numeric { field, min, max } => index.numeric_range(field, *min, *max),
+            Query::Tag { field, tags } => index.tag_postings(field, tags),
+            Query::And(items) => {
+                let mut iter = items.iter();
+                let Some(first) = iter.next() else {
+                    return BTreeSet::new();
+                };
+                let mut docs = first.eval(index);
+                for item in iter {
+                    if docs.is_empty() {
+                        break;
+                    }
+                    let other = item.eval(index);
+                    docs.retain(|d| other.contains(d));
+                }
+                docs
+            }
+            Query::Or(items) => items.iter().flat_map(|q| q.eval(index)).collect(),
+            Query::Not(q) => {
+                let excluded = q.eval(index);
+                index
+                    .docs
+                    .keys()
+                    .filter(|d| !excluded.contains(*d))
+                    .cloned()
+                    .collect()
+            }
+        }
+    }
+}
+
+fn lex(s: &str) -> Result<Vec<Token>, String> {
+    let chars = s.chars().collect::<Vec<_>>();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    let until = |i: usize, end: char| -> Result<(String, usize), String> {
+        let rest = &chars[i + 1..];
+        let len = rest
+            .iter()
+            .position(|c| *c == end)
+            .ok_or_else(|| format!("missing '{}'", end))?;
+        Ok((rest[..len].iter().collect(), i + len + 2))
+    };
+
+    while i < chars.len() {
+        match chars[i] {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            '|' => {
+                tokens.push(Token::Pipe);
+                i += 1;
+            }
+            ':' => {
+                tokens.push(Token::Colon);
+                i += 1;
+            }
+            '-' => {
+                tokens.push(Token::Minus);
+                i += 1;
+            }
+            '*' => {
+                tokens.push(Token::Star);
+                i += 1;
+            }
+            '"' => {
+                let (phrase, next) = until(i, '"')?;
+                tokens.push(Token::Phrase(phrase));
+                i = next;
+            }
+            '[' => {
+                let (range, next) = until(i, ']')?;
+                tokens.push(Token::Range(range));
+                i = next;
+            }
+            '{' => {
+                let (tags, next) = until(i, '}')?;
+                tokens.push(Token::Tags(tags));
+                i = next;
+            }
+            '@' => {
+                let start = i + 1;
+                i += 1;
+                while chars.get(i).is_some_and(|c| is_word(*c)) {
+                    i += 1;
+                }
+                if start == i {
+                    return Err("missing field name after '@'".to_string());
+                }
+                tokens.push(Token::Field(chars[start..i].iter().collect()));
+            }
+            c if is_word(c) => {
+                let start = i;
+                while chars.get(i).is_some_and(|c| is_word(*c)) {
+                    i += 1;
+                }
+                tokens.push(Token::Word(chars[start..i].iter().collect()));
+            }
+            c => return Err(format!("unexpected character '{}'", c)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+// how deep `(`, `-` and `@field:` may nest, the parser recurses on each
+const MAX_DEPTH: usize = 128;
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Query, String>,
+    ) -> Result<Query, String> {
+        if self.depth >= MAX_DEPTH {
+            return Err("query nested too deeply".to_string());
+        }
+        self.depth += 1;
+        let ret = parse(self);
+        self.depth -= 1;
+        ret
+    }
+
+    fn and(&mut self, field: Option<&str>) -> Result<Query, String> {
+        let mut items = vec![];
+        let mut dropped = false;
+        while !matches!(self.peek(), None | Some(Token::RParen)) {
+            let item = self.not(field)?;
+            // stopwords are never indexed, so they are left out of queries just the same
+            match is_stopword(&item) {
+                true => dropped = true,
+                false => items.push(item),
+            }
+        }
+        match items.len() {
+            // nothing but stopwords matches nothing
+            0 if dropped => Ok(Query::Or(vec![])),
+            0 => Err("empty expression".to_string()),
+            1 => Ok(items.remove(0)),
+            _ => Ok(Query::And(items)),
+        }
+    }
+
+    fn not(&mut self, field: Option<&str>) -> Result<Query, String> {
+        if self.peek() == Some(&Token::Minus) {
+            self.pos += 1;
+            let q = self.nested(|parser| parser.not(field))?;
+            return Ok(Query::Not(Box::new(q)));
+        }
+        self.or(field)
+    }
+
+    fn or(&mut self, field: Option<&str>) -> Result<Query, String> {
+        let mut items = vec![self.atom(field)?];
+        while self.peek() == Some(&Token::Pipe) {
+            self.pos += 1;
+            items.push(self.atom(field)?);
+        }
+        // an alternative of nothing but stopwords is one, for `and` to drop
+        if items.iter().any(|q| !is_stopword(q)) {
+            items.retain(|q| !is_stopword(q));
+        }
+        match items.len() {
+            1 => Ok(items.remove(0)),
+            _ => Ok(Query::Or(items)),
+        }
+    }
+
+    fn atom(&mut self, field: Option<&str>) -> Result<Query, String> {
+        match self.next() {
+            Some(Token::Star) => Ok(Query::All),
+            Some(Token::LParen) => self.nested(|parser| {
+                let q = parser.and(field)?;
+                match parser.next() {
+                    Some(Token::RParen) => Ok(q),
+                    _ => Err("missing ')'".to_string()),
+                }
+            }),
+            Some(Token::Word(w)) => {
+                let field = field.map(|f| f.to_string());
+                let prefix = self.peek() == Some(&Token::Star);
+                let term = w.to_lowercase();
+                match prefix {
+                    true => {
+                        self.pos += 1;
+                        Ok(Query::Prefix {
+                            field,
+                            prefix: term,
+                        })
+                    }
+                    false => Ok(Query::Term { field, term }),
+                }
+            }
+            Some(Token::Phrase(p)) => Ok(Query::Phrase {
+                field: field.map(|f| f.to_string()),
+                terms: tokenize(&p),
+            }),
+            Some(Token::Field(f)) => {
+                if self.next() != Some(Token::Colon) {
+                    return Err(format!("missing ':' after @{}", f));
+                }
+                match self.peek() {
+                    Some(Token::Range(_)) => {
+                        let Some(Token::Range(r)) = self.next() else {
+                            unreachable!()
+                        };
+                        parse_range(f, &r)
+                    }
+                    Some(Token::Tags(_)) => {
+                        let Some(Token::Tags(t)) = self.next() else {
+                            unreachable!()
+                        };
+                        let tags = t
+                            .split('|')
+                            .map(|t| t.trim().to_lowercase())
+                            .filter(|t| !t.is_empty())
+                            .collect();
+                        Ok(Query::Tag { field: f, tags })
+                    }
+                    _ => self.nested(|parser| parser.or(Some(&f))),
+                }
+            }
+            Some(t) => Err(format!("syntax error near {:?}", t)),
+            None => Err("unexpected end of query".to_string()),
+        }
+    }
+}
+
+fn is_stopword(query: &Query) -> bool {
+    matches!(query, Query::Term { term, .. } if STOPWORDS.contains(&term.as_str()))
+}
+
+// [min max] where either bound may be prefixed with '(' for exclusive, or be -inf/+inf
+fn parse_range(field: String, s: &str) -> Result<Query, String> {
+    let parts = s.split_whitespace().collect::<Vec<_>>();
+    let [min, max] = parts.as_slice() else {
+        return Err(format!("invalid numeric range [{}]", s));
+    };
+    Ok(Query::Numeric {
+        field,
+        min: parse_bound(min)?,
+        max: parse_bound(max)?,
+    })
+}
+
+fn parse_bound(s: &str) -> Result<Bound<f64>, String> {
+    let (exclusive, s) = match s.strip_prefix('(') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let v: f64 = match s.to_lowercase().as_str() {
+        "-inf" => return Ok(Bound::Unbounded),
+        "+inf" | "inf" => return Ok(Bound::Unbounded),
+        n => n.parse().map_err(|_| format!("invalid number {}", s))?,
+    };
+    // NaN has no place in the ordering numeric postings are kept in
+    if v.is_nan() {
+        return Err(format!("invalid number {}", s));
+    }
+    Ok(match exclusive {
+        true => Bound::Excluded(v),
+        false => Bound::Included(v),
+    })
+}
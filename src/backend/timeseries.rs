@@ -0,0 +1,361 @@
+use super::Backend;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TimeSeriesError {
+    #[error("TSDB: key already exists")]
+    KeyExists,
+    #[error("TSDB: the key does not exist")]
+    KeyNotFound,
+    #[error("TSDB: Timestamp is older than retention")]
+    TooOld,
+    #[error(
+        "TSDB: Error at upsert, update is not supported when DUPLICATE_POLICY is set to BLOCK mode"
+    )]
+    DuplicateBlocked,
+    #[error("TSDB: Unknown duplicate policy: {0}")]
+    UnknownDuplicatePolicy(String),
+    #[error("TSDB: Unknown aggregation type: {0}")]
+    UnknownAggregation(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    #[default]
+    Block,
+    First,
+    Last,
+    Min,
+    Max,
+    Sum,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregation {
+    Avg,
+    Sum,
+    Min,
+    Max,
+    Count,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TimeSeriesOptions {
+    pub retention: Option<u64>,
+    pub duplicate_policy: Option<DuplicatePolicy>,
+    pub labels: Option<Vec<(String, String)>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LabelFilter {
+    Equal(String, Vec<String>),
+    NotEqual(String, Vec<String>),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TimeSeries {
+    pub(crate) retention: u64,
+    pub(crate) duplicate_policy: DuplicatePolicy,
+    pub(crate) labels: Vec<(String, String)>,
+    pub(crate) samples: BTreeMap<i64, f64>,
+}
+
+impl TimeSeries {
+    pub fn new(opts: TimeSeriesOptions) -> Self {
+        Self {
+            retention: opts.retention.unwrap_or_default(),
+            duplicate_policy: opts.duplicate_policy.unwrap_or_default(),
+            labels: opts.labels.unwrap_or_default(),
+            samples: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        ts: i64,
+        value: f64,
+        policy: Option<DuplicatePolicy>,
+    ) -> Result<i64, TimeSeriesError> {
+        if let Some(last) = self.last_timestamp() {
+            if self.retention > 0 && ts < last.saturating_sub(self.retention as i64) {
+                return Err(TimeSeriesError::TooOld);
+            }
+        }
+
+        let policy = policy.unwrap_or(self.duplicate_policy);
+        match self.samples.get_mut(&ts) {
+            Some(old) => match policy {
+                DuplicatePolicy::Block => return Err(TimeSeriesError::DuplicateBlocked),
+                DuplicatePolicy::First => {}
+                DuplicatePolicy::Last => *old = value,
+                DuplicatePolicy::Min => *old = old.min(value),
+                DuplicatePolicy::Max => *old = old.max(value),
+                DuplicatePolicy::Sum => *old += value,
+            },
+            None => {
+                self.samples.insert(ts, value);
+            }
+        }
+
+        self.trim();
+        Ok(ts)
+    }
+
+    pub fn range(
+        &self,
+        from: i64,
+        to: i64,
+        aggregation: Option<(Aggregation, u64)>,
+        count: Option<usize>,
+    ) -> Vec<(i64, f64)> {
+        if from > to {
+            return vec![];
+        }
+        let samples = self.samples.range(from..=to).map(|(k, v)| (*k, *v));
+        let ret: Vec<(i64, f64)> = match aggregation {
+            Some((agg, bucket)) => downsample(samples, agg, bucket),
+            None => samples.collect(),
+        };
+
+        match count {
+            Some(n) => ret.into_iter().take(n).collect(),
+            None => ret,
+        }
+    }
+
+    pub fn matches(&self, filters: &[LabelFilter]) -> bool {
+        filters.iter().all(|filter| match filter {
+            LabelFilter::Equal(label, values) => match self.label(label) {
+                Some(v) => values.iter().any(|x| x == v),
+                None => values.iter().any(|x| x.is_empty()),
+            },
+            LabelFilter::NotEqual(label, values) => match self.label(label) {
+                Some(v) => !values.iter().any(|x| x == v),
+                None => !values.iter().any(|x| x.is_empty()),
+            },
+        })
+    }
+
+    fn label(&self, name: &str) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn last_timestamp(&self) -> Option<i64> {
+        self.samples.last_key_value().map(|(k, _)| *k)
+    }
+
+    fn trim(&mut self) {
+        if self.retention == 0 {
+            return;
+        }
+        if let Some(last) = self.last_timestamp() {
+            let min = last.saturating_sub(self.retention as i64);
+            self.samples = self.samples.split_off(&min);
+        }
+    }
+}
+
+fn downsample(
+    samples: impl Iterator<Item = (i64, f64)>,
+    agg: Aggregation,
+    bucket: u64,
+) -> Vec<(i64, f64)> {
+    let bucket = bucket.max(1) as i64;
+    let mut ret = Vec::new();
+    let mut current: Option<(i64, Accumulator)> = None;
+
+    for (ts, value) in samples {
+        let start = ts - ts.rem_euclid(bucket);
+        match current {
+            Some((s, ref mut acc)) if s == start => acc.push(value),
+            _ => {
+                if let Some((s, acc)) = current.take() {
+                    ret.push((s, acc.finish(agg)));
+                }
+                current = Some((start, Accumulator::new(value)));
+            }
+        }
+    }
+
+    if let Some((s, acc)) = current {
+        ret.push((s, acc.finish(agg)));
+    }
+    ret
+}
+
+#[derive(Debug)]
+struct Accumulator {
+    sum: f64,
+    min: f64,
+    max: f64,
+    count: u64,
+}
+
+impl Accumulator {
+    fn new(value: f64) -> Self {
+        Self {
+            sum: value,
+            min: value,
+            max: value,
+            count: 1,
+        }
+    }
+
+    fn push(&mut self, value: f64) {
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.count += 1;
+    }
+
+    fn finish(self, agg: Aggregation) -> f64 {
+        match agg {
+            Aggregation::Avg => self.sum / self.count as f64,
+            Aggregation::Sum => self.sum,
+            Aggregation::Min => self.min,
+            Aggregation::Max => self.max,
+            Aggregation::Count => self.count as f64,
+        }
+    }
+}
+
+impl FromStr for DuplicatePolicy {
+    type Err = TimeSeriesError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "block" => Ok(DuplicatePolicy::Block),
+            "first" => Ok(DuplicatePolicy::First),
+            "last" => Ok(DuplicatePolicy::Last),
+            "min" => Ok(DuplicatePolicy::Min),
+            "max" => Ok(DuplicatePolicy::Max),
+            "sum" => Ok(DuplicatePolicy::Sum),
+            _ => Err(TimeSeriesError::UnknownDuplicatePolicy(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for DuplicatePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            DuplicatePolicy::Block => "block",
+            DuplicatePolicy::First => "first",
+            DuplicatePolicy::Last => "last",
+            DuplicatePolicy::Min => "min",
+            DuplicatePolicy::Max => "max",
+            DuplicatePolicy::Sum => "sum",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for Aggregation {
+    type Err = TimeSeriesError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "avg" => Ok(Aggregation::Avg),
+            "sum" => Ok(Aggregation::Sum),
+            "min" => Ok(Aggregation::Min),
+            "max" => Ok(Aggregation::Max),
+            "count" => Ok(Aggregation::Count),
+            _ => Err(TimeSeriesError::UnknownAggregation(s.to_string())),
+        }
+    }
+}
+
+impl LabelFilter {
+    // label=value, label!=value, label=(v1,v2), label!=(v1,v2), label= and label!=
+    pub fn parse(s: &str) -> Option<Self> {
+        let (label, values, negate) = match s.split_once("!=") {
+            Some((l, v)) => (l, v, true),
+            None => {
+                let (l, v) = s.split_once('=')?;
+                (l, v, false)
+            }
+        };
+        if label.is_empty() {
+            return None;
+        }
+
+        let values = match values.strip_prefix('(').and_then(|v| v.strip_suffix(')')) {
+            Some(list) => list.split(',').map(|v| v.trim().to_string()).collect(),
+            None => vec![values.to_string()],
+        };
+
+        Some(match negate {
+            true => LabelFilter::NotEqual(label.to_string(), values),
+            false => LabelFilter::Equal(label.to_string(), values),
+        })
+    }
+}
+
+impl Backend {
+    pub fn ts_create(&self, key: String, opts: TimeSeriesOptions) -> Result<(), TimeSeriesError> {
+        match self.ts.entry(key) {
+            dashmap::mapref::entry::Entry::Occupied(_) => Err(TimeSeriesError::KeyExists),
+            dashmap::mapref::entry::Entry::Vacant(entry) => {
+                entry.insert(TimeSeries::new(opts));
+                Ok(())
+            }
+        }
+    }
+
+    pub fn ts_add(
+        &self,
+        key: String,
+        ts: i64,
+        value: f64,
+        opts: TimeSeriesOptions,
+        on_duplicate: Option<DuplicatePolicy>,
+    ) -> Result<i64, TimeSeriesError> {
+        let mut series = self.ts.entry(key).or_insert_with(|| TimeSeries::new(opts));
+        series.add(ts, value, on_duplicate)
+    }
+
+    pub fn ts_range(
+        &self,
+        key: &str,
+        from: i64,
+        to: i64,
+        aggregation: Option<(Aggregation, u64)>,
+        count: Option<usize>,
+    ) -> Result<Vec<(i64, f64)>, TimeSeriesError> {
+        self.ts
+            .get(key)
+            .map(|series| series.range(from, to, aggregation, count))
+            .ok_or(TimeSeriesError::KeyNotFound)
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn ts_mrange(
+        &self,
+        from: i64,
+        to: i64,
+        aggregation: Option<(Aggregation, u64)>,
+        count: Option<usize>,
+        filters: &[LabelFilter],
+    ) -> Vec<(String, Vec<(String, String)>, Vec<(i64, f64)>)> {
+        let mut ret = self
+            .ts
+            .iter()
+            .filter(|entry| entry.value().matches(filters))
+            .map(|entry| {
+                let series = entry.value();
+                (
+                    entry.key().clone(),
+                    series.labels.clone(),
+                    series.range(from, to, aggregation, count),
+                )
+            })
+            .collect::<Vec<_>>();
+        ret.sort_by(|a, b| a.0.cmp(&b.0));
+        ret
+    }
+}
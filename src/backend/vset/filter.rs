@@ -0,0 +1,352 @@
+use serde_json::Value;
+use std::str::FromStr;
+
+/// A VSIM FILTER expression evaluated against an element's JSON attributes,
+/// e.g. `.year >= 1980 and (.genre == "drama" or .rating > 8)`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterExpr {
+    Literal(Value),
+    Field(String),
+    List(Vec<FilterExpr>),
+    Not(Box<FilterExpr>),
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+    Compare(CompareOp, Box<FilterExpr>, Box<FilterExpr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    In,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Field(String),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+}
+
+impl FilterExpr {
+    pub fn matches(&self, attrs: &Value) -> bool {
+        truthy(&self.eval(attrs))
+    }
+
+    fn eval(&self, attrs: &Value) -> Value {
+        match self {
+            FilterExpr::Literal(v) => v.clone(),
+            FilterExpr::Field(name) => attrs.get(name).cloned().unwrap_or(Value::Null),
+            FilterExpr::List(items) => Value::Array(items.iter().map(|e| e.eval(attrs)).collect()),
+            FilterExpr::Not(e) => Value::Bool(!truthy(&e.eval(attrs))),
+            FilterExpr::And(a, b) => Value::Bool(truthy(&a.eval(attrs)) && truthy(&b.eval(attrs))),
+            FilterExpr::Or(a, b) => Value::Bool(truthy(&a.eval(attrs)) || truthy(&b.eval(attrs))),
+            FilterExpr::Compare(op, a, b) => {
+                Value::Bool(compare(*op, &a.eval(attrs), &b.eval(attrs)))
+            }
+        }
+    }
+}
+
+impl FromStr for FilterExpr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            depth: 0,
+        };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(t) => Err(format!("unexpected token {:?}", t)),
+        }
+    }
+}
+
+fn truthy(v: &Value) -> bool {
+    match v {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().map(|n| n != 0.0).unwrap_or(false),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(o) => !o.is_empty(),
+    }
+}
+
+fn compare(op: CompareOp, a: &Value, b: &Value) -> bool {
+    if op == CompareOp::In {
+        return match b {
+            Value::Array(items) => items.iter().any(|x| equals(a, x)),
+            Value::String(s) => a.as_str().map(|a| s.contains(a)).unwrap_or(false),
+            _ => false,
+        };
+    }
+
+    let ordering = match (a, b) {
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => match (number(a), number(b)) {
+            (Some(a), Some(b)) => a.partial_cmp(&b),
+            _ => None,
+        },
+    };
+
+    match op {
+        CompareOp::Eq => equals(a, b),
+        CompareOp::Ne => !equals(a, b),
+        CompareOp::Gt => ordering.map(|o| o.is_gt()).unwrap_or(false),
+        CompareOp::Ge => ordering.map(|o| o.is_ge()).unwrap_or(false),
+        CompareOp::Lt => ordering.map(|o| o.is_lt()).unwrap_or(false),
+        CompareOp::Le => ordering.map(|o| o.is_le()).unwrap_or(false),
+        CompareOp::In => unreachable!(),
+    }
+}
+
+fn equals(a: &Value, b: &Value) -> bool {
+    match (number(a), number(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn number(v: &Value) -> Option<f64> {
+    match v {
+        Value::Number(n) => n.as_f64(),
+        Value::Bool(b) => Some(*b as u8 as f64),
+        _ => None,
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let chars = s.chars().collect::<Vec<_>>();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            '[' => {
+                tokens.push(Token::LBracket);
+                i += 1;
+            }
+            ']' => {
+                tokens.push(Token::RBracket);
+                i += 1;
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            }
+            '"' | '\'' => {
+                let mut s = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        Some('\\') => {
+                            s.extend(chars.get(i + 1));
+                            i += 2;
+                        }
+                        Some(x) if *x == c => break,
+                        Some(x) => {
+                            s.push(*x);
+                            i += 1;
+                        }
+                        None => return Err("unterminated string".to_string()),
+                    }
+                }
+                tokens.push(Token::Str(s));
+                i += 1;
+            }
+            '.' if chars.get(i + 1).is_some_and(|x| is_ident(*x)) => {
+                let start = i + 1;
+                i += 1;
+                while chars.get(i).is_some_and(|x| is_ident(*x)) {
+                    i += 1;
+                }
+                tokens.push(Token::Field(chars[start..i].iter().collect()));
+            }
+            c if c.is_ascii_digit() || c == '.' || c == '-' => {
+                let start = i;
+                i += 1;
+                while chars
+                    .get(i)
+                    .is_some_and(|x| x.is_ascii_digit() || matches!(x, '.' | 'e' | 'E'))
+                {
+                    i += 1;
+                }
+                let n: String = chars[start..i].iter().collect();
+                let n = n.parse().map_err(|_| format!("invalid number {}", n))?;
+                tokens.push(Token::Number(n));
+            }
+            c if is_ident(c) => {
+                let start = i;
+                while chars.get(i).is_some_and(|x| is_ident(*x)) {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            _ => {
+                let rest: String = chars[i..].iter().take(2).collect();
+                let op = ["==", "!=", ">=", "<=", "&&", "||", ">", "<", "!"]
+                    .into_iter()
+                    .find(|op| rest.starts_with(op))
+                    .ok_or_else(|| format!("unexpected character '{}'", c))?;
+                tokens.push(Token::Op(op));
+                i += op.len();
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// how deep `(`, `[` and `!` may nest, the parser recurses on each
+const MAX_DEPTH: usize = 128;
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn eat_keyword(&mut self, op: &str, word: &str) -> bool {
+        let found = match self.peek() {
+            Some(Token::Op(o)) => *o == op,
+            Some(Token::Ident(w)) => w.eq_ignore_ascii_case(word),
+            _ => false,
+        };
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, String>,
+    ) -> Result<T, String> {
+        if self.depth >= MAX_DEPTH {
+            return Err("expression nested too deeply".to_string());
+        }
+        self.depth += 1;
+        let ret = parse(self);
+        self.depth -= 1;
+        ret
+    }
+
+    fn or(&mut self) -> Result<FilterExpr, String> {
+        let mut left = self.and()?;
+        while self.eat_keyword("||", "or") {
+            left = FilterExpr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<FilterExpr, String> {
+        let mut left = self.not()?;
+        while self.eat_keyword("&&", "and") {
+            left = FilterExpr::And(Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<FilterExpr, String> {
+        if self.eat_keyword("!", "not") {
+            return Ok(FilterExpr::Not(Box::new(self.nested(Self::not)?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<FilterExpr, String> {
+        let left = self.primary()?;
+        let op = match self.peek() {
+            Some(Token::Op("==")) => CompareOp::Eq,
+            Some(Token::Op("!=")) => CompareOp::Ne,
+            Some(Token::Op(">")) => CompareOp::Gt,
+            Some(Token::Op(">=")) => CompareOp::Ge,
+            Some(Token::Op("<")) => CompareOp::Lt,
+            Some(Token::Op("<=")) => CompareOp::Le,
+            Some(Token::Ident(w)) if w.eq_ignore_ascii_case("in") => CompareOp::In,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        let right = self.primary()?;
+        Ok(FilterExpr::Compare(op, Box::new(left), Box::new(right)))
+    }
+
+    fn primary(&mut self) -> Result<FilterExpr, String> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(FilterExpr::Literal(n.into())),
+            Some(Token::Str(s)) => Ok(FilterExpr::Literal(Value::String(s))),
+            Some(Token::Field(f)) => Ok(FilterExpr::Field(f)),
+            Some(Token::Ident(w)) => match w.to_ascii_lowercase().as_str() {
+                "true" => Ok(FilterExpr::Literal(Value::Bool(true))),
+                "false" => Ok(FilterExpr::Literal(Value::Bool(false))),
+                "null" => Ok(FilterExpr::Literal(Value::Null)),
+                _ => Err(format!("unexpected identifier {}", w)),
+            },
+            Some(Token::LParen) => self.nested(|parser| {
+                let expr = parser.or()?;
+                match parser.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => Err("expected ')'".to_string()),
+                }
+            }),
+            Some(Token::LBracket) => self.nested(|parser| {
+                let mut items = vec![];
+                if parser.peek() == Some(&Token::RBracket) {
+                    parser.pos += 1;
+                    return Ok(FilterExpr::List(items));
+                }
+                loop {
+                    items.push(parser.primary()?);
+                    match parser.next() {
+                        Some(Token::Comma) => continue,
+                        Some(Token::RBracket) => return Ok(FilterExpr::List(items)),
+                        _ => return Err("expected ',' or ']'".to_string()),
+                    }
+                }
+            }),
+            Some(t) => Err(format!("unexpected token {:?}", t)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use super::Quantized;
+
+/// A Hierarchical Navigable Small World graph over cosine distance.
+///
+/// Nodes are addressed by an opaque `u32` id handed out by the owning vector set.
+#[derive(Debug, Clone)]
+pub(crate) struct Hnsw {
+    m: usize,
+    ef_construction: usize,
+    nodes: HashMap<u32, Node>,
+    entry: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    vector: Quantized,
+    links: Vec<Vec<u32>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidate {
+    distance: f32,
+    id: u32,
+}
+
+impl Hnsw {
+    pub fn new(m: usize, ef_construction: usize) -> Self {
+        Self {
+            m: m.max(2),
+            ef_construction: ef_construction.max(1),
+            nodes: HashMap::new(),
+            entry: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The links a node keeps per layer above the bottom one.
+    pub fn m(&self) -> usize {
+        self.m
+    }
+
+    pub fn vector(&self, id: u32) -> Option<&Quantized> {
+        self.nodes.get(&id).map(|n| &n.vector)
+    }
+
+    pub fn insert(&mut self, id: u32, vector: Quantized, level: usize) {
+        self.remove(id);
+
+        let query = vector.to_f32();
+        let mut node = Node {
+            vector,
+            links: vec![vec![]; level + 1],
+        };
+
+        let Some(entry) = self.entry else {
+            self.nodes.insert(id, node);
+            self.entry = Some(id);
+            return;
+        };
+
+        let top = self.level_of(entry);
+        let mut eps = vec![self.candidate(entry, &query)];
+        for l in (level + 1..=top).rev() {
+            eps = self.search_layer(&query, eps, 1, l);
+        }
+
+        for l in (0..=level.min(top)).rev() {
+            let found = self.search_layer(&query, eps.clone(), self.ef_construction, l);
+            let neighbours = found
+                .iter()
+                .take(self.max_links(l))
+                .map(|c| c.id)
+                .collect::<Vec<_>>();
+            node.links[l] = neighbours.clone();
+            eps = found;
+
+            for n in neighbours {
+                self.link(n, id, &query, l);
+            }
+        }
+
+        self.nodes.insert(id, node);
+        if level > top {
+            self.entry = Some(id);
+        }
+    }
+
+    pub fn remove(&mut self, id: u32) -> Option<Quantized> {
+        let node = self.nodes.remove(&id)?;
+
+        // unlink the node from its neighbours and let them borrow its links in its place; a
+        // node whose link back was pruned away may still point here, searches step over it
+        // and the next pruning of its list drops it
+        for (l, links) in node.links.iter().enumerate() {
+            for n in links {
+                let Some(neighbour) = self.nodes.get_mut(n) else {
+                    continue;
+                };
+                if let Some(own) = neighbour.links.get_mut(l) {
+                    own.retain(|x| *x != id);
+                }
+            }
+            for n in links {
+                let query = match self.nodes.get(n) {
+                    Some(neighbour) => neighbour.vector.to_f32(),
+                    None => continue,
+                };
+                for candidate in links.iter().filter(|c| *c != n) {
+                    self.link(*n, *candidate, &query, l);
+                }
+            }
+        }
+
+        if self.entry == Some(id) {
+            // the highest of the neighbours on the topmost layer that has any
+            self.entry = node
+                .links
+                .iter()
+                .rev()
+                .find_map(|links| {
+                    links
+                        .iter()
+                        .filter(|n| self.nodes.contains_key(n))
+                        .max_by_key(|n| (self.level_of(**n), Reverse(**n)))
+                })
+                .copied()
+                .or_else(|| self.nodes.keys().next().copied());
+        }
+
+        Some(node.vector)
+    }
+
+    /// Return up to `k` nearest nodes as `(id, distance)` pairs, closest first.
+    pub fn search(
+        &self,
+        query: &[f32],
+        k: usize,
+        ef: usize,
+        mut accept: impl FnMut(u32) -> bool,
+    ) -> Vec<(u32, f32)> {
+        let Some(entry) = self.entry else {
+            return vec![];
+        };
+
+        let mut eps = vec![self.candidate(entry, query)];
+        for l in (1..=self.level_of(entry)).rev() {
+            eps = self.search_layer(query, eps, 1, l);
+        }
+
+        self.search_layer(query, eps, ef.max(k), 0)
+            .into_iter()
+            .filter(|c| accept(c.id))
+            .take(k)
+            .map(|c| (c.id, c.distance))
+            .collect()
+    }
+
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entries: Vec<Candidate>,
+        ef: usize,
+        level: usize,
+    ) -> Vec<Candidate> {
+        let mut visited: HashSet<u32> = entries.iter().map(|c| c.id).collect();
+        let mut candidates: BinaryHeap<Reverse<Candidate>> =
+            entries.iter().copied().map(Reverse).collect();
+        let mut found: BinaryHeap<Candidate> = entries.into_iter().collect();
+
+        while let Some(Reverse(current)) = candidates.pop() {
+            if let Some(worst) = found.peek() {
+                if found.len() >= ef && current.distance > worst.distance {
+                    break;
+                }
+            }
+
+            let Some(links) = self.nodes.get(&current.id).and_then(|n| n.links.get(level)) else {
+                continue;
+            };
+            for n in links {
+                if !visited.insert(*n) || !self.nodes.contains_key(n) {
+                    continue;
+                }
+                let candidate = self.candidate(*n, query);
+                let closer = found
+                    .peek()
+                    .map(|w| candidate.distance < w.distance)
+                    .unwrap_or(true);
+                if found.len() < ef || closer {
+                    candidates.push(Reverse(candidate));
+                    found.push(candidate);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+
+        found.into_sorted_vec()
+    }
+
+    fn link(&mut self, from: u32, to: u32, query: &[f32], level: usize) {
+        let max = self.max_links(level);
+        let Some(node) = self.nodes.get(&from) else {
+            return;
+        };
+        let Some(links) = node.links.get(level) else {
+            return;
+        };
+        if links.contains(&to) {
+            return;
+        }
+
+        let mut links = links.clone();
+        links.retain(|id| self.nodes.contains_key(id));
+        links.push(to);
+        if links.len() > max {
+            let base = node.vector.to_f32();
+            let mut scored = links
+                .into_iter()
+                .map(|id| {
+                    let distance = match id == to {
+                        true => cosine_distance(&base, query),
+                        false => self.distance_between(&base, id),
+                    };
+                    Candidate { distance, id }
+                })
+                .collect::<Vec<_>>();
+            scored.sort();
+            links = scored.into_iter().take(max).map(|c| c.id).collect();
+        }
+
+        if let Some(node) = self.nodes.get_mut(&from) {
+            node.links[level] = links;
+        }
+    }
+
+    fn distance_between(&self, base: &[f32], id: u32) -> f32 {
+        self.nodes
+            .get(&id)
+            .map(|n| n.vector.distance(base))
+            .unwrap_or(f32::MAX)
+    }
+
+    fn candidate(&self, id: u32, query: &[f32]) -> Candidate {
+        Candidate {
+            distance: self.distance_between(query, id),
+            id,
+        }
+    }
+
+    fn level_of(&self, id: u32) -> usize {
+        self.nodes
+            .get(&id)
+            .map(|n| n.links.len().saturating_sub(1))
+            .unwrap_or_default()
+    }
+
+    fn max_links(&self, level: usize) -> usize {
+        match level {
+            0 => self.m * 2,
+            _ => self.m,
+        }
+    }
+}
+
+/// Cosine distance in `[0, 2]` between two already normalized vectors.
+pub(crate) fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    (1.0 - dot).clamp(0.0, 2.0)
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then(self.id.cmp(&other.id))
+    }
+}
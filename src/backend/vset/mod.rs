@@ -0,0 +1,340 @@
+mod filter;
+mod hnsw;
+
+pub use filter::FilterExpr;
+
+use super::Backend;
+use dashmap::mapref::entry::Entry;
+use hnsw::{cosine_distance, Hnsw};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use thiserror::Error;
+
+const DEFAULT_M: usize = 16;
+const DEFAULT_EF_CONSTRUCTION: usize = 200;
+const MAX_LEVEL: usize = 16;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum VectorSetError {
+    #[error("ERR Vector dimension mismatch - got {0} but set has {1}")]
+    DimensionMismatch(usize, usize),
+    #[error("ERR asked quantization mismatch with existing vector set")]
+    QuantizationMismatch,
+    #[error("ERR element not found in set")]
+    ElementNotFound,
+    #[error("ERR invalid JSON attributes: {0}")]
+    InvalidAttributes(String),
+    #[error("ERR zero length vectors are not supported")]
+    ZeroVector,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Quantization {
+    NoQuant,
+    #[default]
+    Q8,
+    Binary,
+}
+
+/// A unit-length vector stored with the set's quantization.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Quantized {
+    F32(Vec<f32>),
+    Q8 { data: Vec<i8>, scale: f32 },
+    Binary { bits: Vec<u64>, dim: usize },
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VAddOptions {
+    pub quantization: Option<Quantization>,
+    pub ef: Option<usize>,
+    pub m: Option<usize>,
+    pub attributes: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VectorSet {
+    dim: usize,
+    quantization: Quantization,
+    index: Hnsw,
+    ids: HashMap<String, u32>,
+    elements: HashMap<u32, Element>,
+    next_id: u32,
+}
+
+#[derive(Debug, Clone)]
+struct Element {
+    name: String,
+    attributes: Option<serde_json::Value>,
+}
+
+impl VectorSet {
+    pub fn new(dim: usize, opts: &VAddOptions) -> Self {
+        Self {
+            dim,
+            quantization: opts.quantization.unwrap_or_default(),
+            index: Hnsw::new(
+                opts.m.unwrap_or(DEFAULT_M),
+                opts.ef.unwrap_or(DEFAULT_EF_CONSTRUCTION),
+            ),
+            ids: HashMap::new(),
+            elements: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Insert or replace an element, returning true if it was newly added.
+    pub fn add(
+        &mut self,
+        name: String,
+        vector: &[f32],
+        attributes: Option<serde_json::Value>,
+    ) -> Result<bool, VectorSetError> {
+        if vector.len() != self.dim {
+            return Err(VectorSetError::DimensionMismatch(vector.len(), self.dim));
+        }
+        let normalized = normalize(vector).ok_or(VectorSetError::ZeroVector)?;
+        let quantized = Quantized::new(&normalized, self.quantization);
+
+        let (id, added) = match self.ids.get(&name) {
+            Some(id) => (*id, false),
+            None => {
+                let id = self.next_id;
+                self.next_id += 1;
+                (id, true)
+            }
+        };
+
+        // levels thin out with the same M the graph is linked with
+        let level = random_level(&name, self.index.m());
+        self.index.insert(id, quantized, level);
+        let attributes = match (attributes, self.elements.remove(&id)) {
+            (Some(attrs), _) => Some(attrs),
+            (None, Some(old)) => old.attributes,
+            (None, None) => None,
+        };
+        self.ids.insert(name.clone(), id);
+        self.elements.insert(id, Element { name, attributes });
+
+        Ok(added)
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        match self.ids.remove(name) {
+            Some(id) => {
+                self.index.remove(id);
+                self.elements.remove(&id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn embedding(&self, name: &str) -> Option<Vec<f32>> {
+        let id = self.ids.get(name)?;
+        self.index.vector(*id).map(|v| v.to_f32())
+    }
+
+    /// Return up to `count` members closest to `query` with their similarity in `[0, 1]`.
+    pub fn similar(
+        &self,
+        query: &[f32],
+        count: usize,
+        ef: usize,
+        filter: Option<&FilterExpr>,
+    ) -> Result<Vec<(String, f64)>, VectorSetError> {
+        if query.len() != self.dim {
+            return Err(VectorSetError::DimensionMismatch(query.len(), self.dim));
+        }
+        let query = normalize(query).ok_or(VectorSetError::ZeroVector)?;
+
+        let accept = |id: u32| match filter {
+            Some(expr) => self
+                .elements
+                .get(&id)
+                .and_then(|e| e.attributes.as_ref())
+                .map(|attrs| expr.matches(attrs))
+                .unwrap_or(false),
+            None => true,
+        };
+
+        // a filter is applied to what the search finds, so when too few of those match
+        // look wider, until there are enough or the whole set has been seen
+        let mut ef = ef;
+        let mut found = self.index.search(&query, count, ef, accept);
+        while filter.is_some() && found.len() < count && ef < self.len() {
+            ef = ef.saturating_mul(2).min(self.len());
+            found = self.index.search(&query, count, ef, accept);
+        }
+
+        let ret = found
+            .into_iter()
+            .filter_map(|(id, distance)| {
+                let element = self.elements.get(&id)?;
+                Some((element.name.clone(), 1.0 - distance as f64 / 2.0))
+            })
+            .collect();
+        Ok(ret)
+    }
+}
+
+impl Quantized {
+    fn new(v: &[f32], quantization: Quantization) -> Self {
+        match quantization {
+            Quantization::NoQuant => Quantized::F32(v.to_vec()),
+            Quantization::Q8 => {
+                let max = v.iter().fold(0f32, |acc, x| acc.max(x.abs()));
+                let scale = if max == 0.0 { 1.0 } else { max / 127.0 };
+                let data = v.iter().map(|x| (x / scale).round() as i8).collect();
+                Quantized::Q8 { data, scale }
+            }
+            Quantization::Binary => {
+                let mut bits = vec![0u64; v.len().div_ceil(64)];
+                for (i, x) in v.iter().enumerate() {
+                    if *x > 0.0 {
+                        bits[i / 64] |= 1 << (i % 64);
+                    }
+                }
+                Quantized::Binary { bits, dim: v.len() }
+            }
+        }
+    }
+
+    pub fn to_f32(&self) -> Vec<f32> {
+        match self {
+            Quantized::F32(v) => v.clone(),
+            Quantized::Q8 { data, scale } => {
+                normalize(&data.iter().map(|x| *x as f32 * scale).collect::<Vec<_>>())
+                    .unwrap_or_else(|| vec![0.0; data.len()])
+            }
+            Quantized::Binary { bits, dim } => {
+                let unit = 1.0 / (*dim as f32).sqrt();
+                (0..*dim)
+                    .map(|i| match bits[i / 64] & (1 << (i % 64)) {
+                        0 => -unit,
+                        _ => unit,
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn distance(&self, query: &[f32]) -> f32 {
+        match self {
+            Quantized::F32(v) => cosine_distance(v, query),
+            _ => cosine_distance(&self.to_f32(), query),
+        }
+    }
+}
+
+fn normalize(v: &[f32]) -> Option<Vec<f32>> {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 || !norm.is_finite() {
+        return None;
+    }
+    Some(v.iter().map(|x| x / norm).collect())
+}
+
+// deterministic per element so that re-inserting a member lands on the same level
+fn random_level(name: &str, m: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    let u = (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64;
+    let u = u.max(f64::MIN_POSITIVE);
+    let level = (-u.ln() / (m as f64).ln()).floor() as usize;
+    level.min(MAX_LEVEL)
+}
+
+impl Backend {
+    pub fn vadd(
+        &self,
+        key: String,
+        element: String,
+        vector: &[f32],
+        opts: VAddOptions,
+    ) -> Result<bool, VectorSetError> {
+        let attributes = match opts.attributes {
+            Some(ref attrs) if attrs.is_empty() => None,
+            Some(ref attrs) => Some(
+                serde_json::from_str(attrs)
+                    .map_err(|e| VectorSetError::InvalidAttributes(e.to_string()))?,
+            ),
+            None => None,
+        };
+
+        match self.vset.entry(key) {
+            Entry::Occupied(mut entry) => {
+                let set = entry.get_mut();
+                if let Some(q) = opts.quantization {
+                    if q != set.quantization {
+                        return Err(VectorSetError::QuantizationMismatch);
+                    }
+                }
+                set.add(element, vector, attributes)
+            }
+            // the set only comes to be with its first element, a rejected one leaves no key
+            Entry::Vacant(entry) => {
+                let mut set = VectorSet::new(vector.len(), &opts);
+                let added = set.add(element, vector, attributes)?;
+                entry.insert(set);
+                Ok(added)
+            }
+        }
+    }
+
+    pub fn vrem(&self, key: &str, element: &str) -> bool {
+        let removed = match self.vset.get_mut(key) {
+            Some(mut set) => set.remove(element),
+            None => false,
+        };
+        self.vset.remove_if(key, |_, set| set.is_empty());
+        removed
+    }
+
+    pub fn vcard(&self, key: &str) -> usize {
+        self.vset.get(key).map(|set| set.len()).unwrap_or_default()
+    }
+
+    pub fn vemb(&self, key: &str, element: &str) -> Option<Vec<f32>> {
+        self.vset.get(key).and_then(|set| set.embedding(element))
+    }
+
+    pub fn vsim(
+        &self,
+        key: &str,
+        query: VSimQuery,
+        count: usize,
+        ef: usize,
+        filter: Option<&FilterExpr>,
+    ) -> Result<Vec<(String, f64)>, VectorSetError> {
+        let Some(set) = self.vset.get(key) else {
+            return Ok(vec![]);
+        };
+        let query = match query {
+            VSimQuery::Vector(v) => v,
+            VSimQuery::Element(name) => set
+                .embedding(&name)
+                .ok_or(VectorSetError::ElementNotFound)?,
+        };
+        set.similar(&query, count, ef, filter)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VSimQuery {
+    Vector(Vec<f32>),
+    Element(String),
+}
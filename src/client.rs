@@ -0,0 +1,407 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use tokio_util::sync::CancellationToken;
+
+use crate::RespVersion;
+
+/// What the server knows about one connection, as shown by CLIENT INFO and CLIENT LIST.
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub id: u64,
+    pub addr: Arc<str>,
+    pub laddr: Arc<str>,
+    pub name: Option<String>,
+    pub user: Arc<str>,
+    pub protocol: RespVersion,
+    pub db: usize,
+    pub flags: ClientFlags,
+    pub created: Instant,
+    pub last_interaction: Instant,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClientFlags {
+    /// In subscribed mode.
+    pub pubsub: bool,
+    /// Hang up once the pending replies are written, set by QUIT.
+    pub close_after_reply: bool,
+    /// Killed by another client.
+    pub close_asap: bool,
+    /// Connected over a Unix socket.
+    pub unix_socket: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientType {
+    Normal,
+    /// Nothing connects as a replica yet, but the class has its own limits like in redis.
+    Replica,
+    PubSub,
+}
+
+/// How much unsent output a client may have queued before it is disconnected: past `hard`
+/// at once, or past `soft` for longer than `soft_seconds`. Zero turns a limit off.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutputBufferLimit {
+    pub hard: usize,
+    pub soft: usize,
+    pub soft_seconds: Duration,
+}
+
+/// The `client-output-buffer-limit` setting, one limit per class of client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputBufferLimits {
+    pub normal: OutputBufferLimit,
+    pub replica: OutputBufferLimit,
+    pub pubsub: OutputBufferLimit,
+}
+
+/// Which clients CLIENT KILL and CLIENT LIST act on; unset fields match everything.
+#[derive(Debug, Default)]
+pub struct ClientFilter {
+    pub ids: Vec<u64>,
+    pub addr: Option<String>,
+    pub laddr: Option<String>,
+    pub user: Option<String>,
+    pub kind: Option<ClientType>,
+}
+
+/// All connected clients, so they can be listed and killed from any connection.
+#[derive(Debug, Default)]
+pub struct ClientRegistry {
+    next_id: AtomicU64,
+    clients: DashMap<u64, ClientHandle>,
+}
+
+/// The registry's view of a connection: the info it last published and a way to close it.
+#[derive(Debug, Clone)]
+pub struct ClientHandle(Arc<ClientShared>);
+
+#[derive(Debug)]
+struct ClientShared {
+    info: Mutex<ClientInfo>,
+    killed: CancellationToken,
+}
+
+impl Default for ClientInfo {
+    fn default() -> Self {
+        let now = Instant::now();
+        Self {
+            id: 0,
+            addr: "".into(),
+            laddr: "".into(),
+            name: None,
+            user: "default".into(),
+            protocol: RespVersion::default(),
+            db: 0,
+            flags: ClientFlags::default(),
+            created: now,
+            last_interaction: now,
+        }
+    }
+}
+
+impl ClientInfo {
+    pub fn kind(&self) -> ClientType {
+        match self.flags.pubsub {
+            true => ClientType::PubSub,
+            false => ClientType::Normal,
+        }
+    }
+}
+
+// one line of CLIENT LIST, "id=3 addr=127.0.0.1:52555 laddr=127.0.0.1:6379 name= ..."
+impl fmt::Display for ClientInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let resp = match self.protocol {
+            RespVersion::Resp2 => 2,
+            RespVersion::Resp3 => 3,
+        };
+        write!(
+            f,
+            "id={} addr={} laddr={} name={} age={} idle={} flags={} db={} user={} resp={}",
+            self.id,
+            self.addr,
+            self.laddr,
+            self.name.as_deref().unwrap_or(""),
+            self.created.elapsed().as_secs(),
+            self.last_interaction.elapsed().as_secs(),
+            self.flags,
+            self.db,
+            self.user,
+            resp
+        )
+    }
+}
+
+// redis' flag letters, "N" when none is set
+impl fmt::Display for ClientFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = [
+            (self.close_asap, 'A'),
+            (self.pubsub, 'P'),
+            (self.close_after_reply, 'c'),
+            (self.unix_socket, 'U'),
+        ];
+        let mut any = false;
+        for (_, letter) in flags.iter().filter(|(set, _)| *set) {
+            write!(f, "{}", letter)?;
+            any = true;
+        }
+        if !any {
+            write!(f, "N")?;
+        }
+        Ok(())
+    }
+}
+
+impl ClientType {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "normal" => Some(ClientType::Normal),
+            "replica" | "slave" => Some(ClientType::Replica),
+            "pubsub" => Some(ClientType::PubSub),
+            _ => None,
+        }
+    }
+
+    // the class names CONFIG GET client-output-buffer-limit shows, "slave" as redis does
+    fn name(&self) -> &'static str {
+        match self {
+            ClientType::Normal => "normal",
+            ClientType::Replica => "slave",
+            ClientType::PubSub => "pubsub",
+        }
+    }
+}
+
+impl OutputBufferLimit {
+    /// Whether `used` bytes of queued output break the limit. `soft_since` remembers when the
+    /// soft limit was first reached and is cleared once the client drops back under it.
+    pub fn exceeded(&self, used: usize, soft_since: &mut Option<Instant>) -> bool {
+        if self.hard != 0 && used >= self.hard {
+            return true;
+        }
+        if self.soft == 0 || used < self.soft {
+            *soft_since = None;
+            return false;
+        }
+        soft_since.get_or_insert_with(Instant::now).elapsed() > self.soft_seconds
+    }
+}
+
+impl Default for OutputBufferLimits {
+    // redis' defaults: normal clients are never cut off, subscribers and replicas are
+    fn default() -> Self {
+        Self {
+            normal: OutputBufferLimit::default(),
+            replica: OutputBufferLimit {
+                hard: 256 * 1024 * 1024,
+                soft: 64 * 1024 * 1024,
+                soft_seconds: Duration::from_secs(60),
+            },
+            pubsub: OutputBufferLimit {
+                hard: 32 * 1024 * 1024,
+                soft: 8 * 1024 * 1024,
+                soft_seconds: Duration::from_secs(60),
+            },
+        }
+    }
+}
+
+impl OutputBufferLimits {
+    pub fn get(&self, kind: ClientType) -> OutputBufferLimit {
+        match kind {
+            ClientType::Normal => self.normal,
+            ClientType::Replica => self.replica,
+            ClientType::PubSub => self.pubsub,
+        }
+    }
+
+    pub fn get_mut(&mut self, kind: ClientType) -> &mut OutputBufferLimit {
+        match kind {
+            ClientType::Normal => &mut self.normal,
+            ClientType::Replica => &mut self.replica,
+            ClientType::PubSub => &mut self.pubsub,
+        }
+    }
+}
+
+// "normal 0 0 0 slave 268435456 67108864 60 pubsub 33554432 8388608 60"
+impl fmt::Display for OutputBufferLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let classes = [ClientType::Normal, ClientType::Replica, ClientType::PubSub];
+        for (i, kind) in classes.into_iter().enumerate() {
+            let limit = self.get(kind);
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(
+                f,
+                "{} {} {} {}",
+                kind.name(),
+                limit.hard,
+                limit.soft,
+                limit.soft_seconds.as_secs()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl ClientFilter {
+    pub fn matches(&self, info: &ClientInfo) -> bool {
+        (self.ids.is_empty() || self.ids.contains(&info.id))
+            && self.addr.as_deref().is_none_or(|a| a == &*info.addr)
+            && self.laddr.as_deref().is_none_or(|a| a == &*info.laddr)
+            && self.user.as_deref().is_none_or(|u| u == &*info.user)
+            && self.kind.is_none_or(|k| k == info.kind())
+    }
+}
+
+impl ClientRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Give a new connection its id and start tracking it.
+    pub fn register(&self, addr: &str, laddr: &str) -> (ClientInfo, ClientHandle) {
+        let info = ClientInfo {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed) + 1,
+            addr: addr.into(),
+            laddr: laddr.into(),
+            ..Default::default()
+        };
+        let handle = ClientHandle(Arc::new(ClientShared {
+            info: Mutex::new(info.clone()),
+            killed: CancellationToken::new(),
+        }));
+        self.clients.insert(info.id, handle.clone());
+        (info, handle)
+    }
+
+    pub fn unregister(&self, id: u64) {
+        self.clients.remove(&id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+
+    /// The last published info of every matching client, oldest first.
+    pub fn list(&self, filter: &ClientFilter) -> Vec<ClientInfo> {
+        let mut clients = self
+            .clients
+            .iter()
+            .map(|c| c.value().info())
+            .filter(|info| filter.matches(info))
+            .collect::<Vec<_>>();
+        clients.sort_by_key(|info| info.id);
+        clients
+    }
+
+    /// Close every matching client except `skip`, returning how many were killed.
+    pub fn kill(&self, filter: &ClientFilter, skip: Option<u64>) -> usize {
+        self.clients
+            .iter()
+            .filter(|c| Some(*c.key()) != skip && filter.matches(&c.value().info()))
+            .map(|c| c.value().kill())
+            .count()
+    }
+}
+
+impl ClientHandle {
+    pub fn info(&self) -> ClientInfo {
+        self.0.info.lock().unwrap().clone()
+    }
+
+    pub fn publish(&self, info: &ClientInfo) {
+        *self.0.info.lock().unwrap() = info.clone();
+    }
+
+    pub fn kill(&self) {
+        self.0.info.lock().unwrap().flags.close_asap = true;
+        self.0.killed.cancel();
+    }
+
+    /// Resolves once another client killed this one.
+    pub async fn killed(&self) {
+        self.0.killed.cancelled().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_registry() {
+        let registry = ClientRegistry::new();
+        let (first, first_handle) = registry.register("127.0.0.1:5000", "127.0.0.1:6379");
+        let (mut second, second_handle) = registry.register("127.0.0.1:5001", "127.0.0.1:6379");
+        assert_eq!((first.id, second.id), (1, 2));
+
+        second.name = Some("worker".to_string());
+        second.flags.pubsub = true;
+        second_handle.publish(&second);
+        let filter = ClientFilter {
+            kind: Some(ClientType::PubSub),
+            ..Default::default()
+        };
+        let listed = registry.list(&filter);
+        assert_eq!(listed.len(), 1);
+        assert!(listed[0]
+            .to_string()
+            .starts_with("id=2 addr=127.0.0.1:5001 laddr=127.0.0.1:6379 name=worker "));
+        assert!(listed[0].to_string().contains(" flags=P db=0 "));
+
+        let filter = ClientFilter {
+            addr: Some("127.0.0.1:5000".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(registry.kill(&filter, Some(first.id)), 0);
+        assert_eq!(registry.kill(&filter, None), 1);
+        assert!(first_handle.info().flags.close_asap);
+
+        registry.unregister(first.id);
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[test]
+    fn test_output_buffer_limit() {
+        let limit = OutputBufferLimit {
+            hard: 100,
+            soft: 10,
+            soft_seconds: Duration::from_millis(50),
+        };
+        let mut since = None;
+        assert!(limit.exceeded(100, &mut since));
+        assert!(!limit.exceeded(9, &mut since));
+        assert!(!limit.exceeded(10, &mut since));
+        assert!(since.is_some());
+        // dropping back under the soft limit starts the clock over
+        assert!(!limit.exceeded(0, &mut since));
+        assert!(since.is_none());
+        assert!(!limit.exceeded(50, &mut since));
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(limit.exceeded(50, &mut since));
+
+        // normal clients aren't limited unless configured to be
+        let limits = OutputBufferLimits::default();
+        assert!(!limits
+            .get(ClientType::Normal)
+            .exceeded(usize::MAX, &mut None));
+        assert_eq!(
+            limits.to_string(),
+            "normal 0 0 0 slave 268435456 67108864 60 pubsub 33554432 8388608 60"
+        );
+    }
+}
@@ -0,0 +1,429 @@
+use crate::cmd::{AclCommand, CommandError, RESP_OK};
+use crate::network::ConnectionState;
+use crate::{
+    Backend, BulkString, ClientFilter, RespArray, RespFrame, RespMap, RespNull, SimpleError,
+    ACL_CATEGORIES, DEFAULT_USER,
+};
+
+use super::{extract_args, extract_string, validator_command, CommandExecutor, COMMAND_TABLE};
+
+impl CommandExecutor for AclCommand {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_for(backend, &mut ConnectionState::default())
+    }
+
+    fn execute_for(self, backend: &Backend, state: &mut ConnectionState) -> RespFrame {
+        let acl = backend.acl();
+        match self {
+            AclCommand::Cat(None) => strings(ACL_CATEGORIES.iter().copied()),
+            AclCommand::Cat(Some(category)) => {
+                let category = category.to_ascii_lowercase();
+                if !ACL_CATEGORIES.contains(&category.as_str()) {
+                    return SimpleError::new(format!("ERR Unknown category '{}'", category)).into();
+                }
+                let names = COMMAND_TABLE
+                    .iter()
+                    .flat_map(|spec| std::iter::once(spec).chain(spec.subcommands))
+                    .filter(|spec| spec.acl_categories.contains(&category.as_str()))
+                    .map(|spec| spec.name);
+                strings(names)
+            }
+            AclCommand::DelUser(names) => {
+                if names.iter().any(|name| name == DEFAULT_USER) {
+                    return SimpleError::new("ERR The 'default' user cannot be removed").into();
+                }
+                let deleted = acl.delete_users(&names);
+                disconnect_users(backend, state, &names);
+                (deleted as i64).into()
+            }
+            AclCommand::GetUser(name) => {
+                let Some(user) = acl.user(&name) else {
+                    return RespFrame::Null(RespNull);
+                };
+                let entries: [(&str, RespFrame); 6] = [
+                    ("flags", strings(user.flags())),
+                    (
+                        "passwords",
+                        strings(user.passwords().iter().map(String::as_str)),
+                    ),
+                    ("commands", BulkString::new(user.command_rules()).into()),
+                    ("keys", BulkString::new(user.key_rules()).into()),
+                    ("channels", BulkString::new(user.channel_rules()).into()),
+                    ("selectors", RespArray::new([]).into()),
+                ];
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (BulkString::from(k).into(), v))
+                    .collect::<RespMap>()
+                    .into()
+            }
+            AclCommand::List => strings(acl.list().iter().map(String::as_str)),
+            AclCommand::Users => strings(acl.usernames().iter().map(String::as_str)),
+            AclCommand::WhoAmI => BulkString::from(&*state.user).into(),
+            AclCommand::Load => {
+                let Some(path) = backend.config().aclfile.clone() else {
+                    return no_acl_file();
+                };
+                match acl.load_file(&path) {
+                    Ok(changed) => {
+                        // the permissions they logged in with are gone
+                        disconnect_users(backend, state, &changed);
+                        RESP_OK.clone()
+                    }
+                    Err(e) => SimpleError::new(format!("ERR {}", e)).into(),
+                }
+            }
+            AclCommand::Save => {
+                let Some(path) = backend.config().aclfile.clone() else {
+                    return no_acl_file();
+                };
+                match acl.save(&path) {
+                    Ok(()) => RESP_OK.clone(),
+                    Err(e) => {
+                        tracing::warn!("Saving ACLs to {} failed: {:?}", path.display(), e);
+                        SimpleError::new(
+                            "ERR There was an error trying to save the ACLs. Please check the \
+                             server logs for more information",
+                        )
+                        .into()
+                    }
+                }
+            }
+            AclCommand::Log(None) => {
+                acl.reset_log();
+                RESP_OK.clone()
+            }
+            AclCommand::Log(Some(count)) => {
+                let entries = acl
+                    .log_entries(count)
+                    .into_iter()
+                    .map(|entry| {
+                        let fields: [(&str, RespFrame); 10] = [
+                            ("count", (entry.count as i64).into()),
+                            ("reason", BulkString::from(entry.reason.as_str()).into()),
+                            ("context", BulkString::from("toplevel").into()),
+                            ("object", BulkString::new(entry.object.clone()).into()),
+                            ("username", BulkString::new(entry.username.clone()).into()),
+                            ("age-seconds", RespFrame::Double(entry.age().as_secs_f64())),
+                            ("client-info", BulkString::new(entry.client_info).into()),
+                            ("entry-id", (entry.entry_id as i64).into()),
+                            ("timestamp-created", entry.created_ms.into()),
+                            ("timestamp-last-updated", entry.updated_ms.into()),
+                        ];
+                        fields
+                            .into_iter()
+                            .map(|(k, v)| (BulkString::from(k).into(), v))
+                            .collect::<RespMap>()
+                            .into()
+                    })
+                    .collect::<Vec<RespFrame>>();
+                RespArray::new(entries).into()
+            }
+            AclCommand::SetUser { username, rules } => match acl.set_user(&username, &rules) {
+                Ok(()) => RESP_OK.clone(),
+                Err((rule, e)) => SimpleError::new(format!(
+                    "ERR Error in ACL SETUSER modifier '{}': {}",
+                    rule, e
+                ))
+                .into(),
+            },
+        }
+    }
+}
+
+// clients logged in as users that were removed or changed; this one hangs up after replying
+fn disconnect_users(backend: &Backend, state: &mut ConnectionState, names: &[String]) {
+    for name in names {
+        let filter = ClientFilter {
+            user: Some(name.clone()),
+            ..Default::default()
+        };
+        backend.clients().kill(&filter, Some(state.id));
+        if *state.user == **name {
+            state.flags.close_after_reply = true;
+        }
+    }
+}
+
+fn no_acl_file() -> RespFrame {
+    SimpleError::new(
+        "ERR This Redis instance is not configured to use an ACL file. You may want to specify \
+         users via the ACL SETUSER command and then issue a CONFIG REWRITE (assuming you have a \
+         Redis configuration file set) in order to store users in the Redis configuration.",
+    )
+    .into()
+}
+
+fn strings<'a>(items: impl IntoIterator<Item = &'a str>) -> RespFrame {
+    RespArray::new(
+        items
+            .into_iter()
+            .map(|s| BulkString::from(s).into())
+            .collect::<Vec<RespFrame>>(),
+    )
+    .into()
+}
+
+impl TryFrom<RespArray> for AclCommand {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["acl"])?;
+        let sub = extract_string(arr.get(1).cloned(), "subcommand")?.to_ascii_lowercase();
+        match sub.as_str() {
+            "cat" => {
+                validator_command(&arr, &["acl", "cat"])?;
+                Ok(AclCommand::Cat(match arr.len() {
+                    2 => None,
+                    3 => Some(extract_string(arr.get(2).cloned(), "category")?),
+                    _ => return Err(CommandError::WrongArity("acl|cat".to_string())),
+                }))
+            }
+            "deluser" => {
+                validator_command(&arr, &["acl", "deluser"])?;
+                let names = extract_args(arr, 2)?
+                    .into_iter()
+                    .map(|arg| extract_string(Some(arg), "username"))
+                    .collect::<Result<_, _>>()?;
+                Ok(AclCommand::DelUser(names))
+            }
+            "getuser" => {
+                validator_command(&arr, &["acl", "getuser"])?;
+                Ok(AclCommand::GetUser(extract_string(
+                    arr.get(2).cloned(),
+                    "username",
+                )?))
+            }
+            "log" => {
+                validator_command(&arr, &["acl", "log"])?;
+                if arr.len() > 3 {
+                    return Err(CommandError::WrongArity("acl|log".to_string()));
+                }
+                let count = match arr.get(2).cloned() {
+                    None => Some(10),
+                    Some(arg) => {
+                        let arg = extract_string(Some(arg), "count")?;
+                        match arg.parse::<i64>() {
+                            _ if arg.eq_ignore_ascii_case("reset") => None,
+                            Ok(n) if n > 0 => Some(n as usize),
+                            _ => {
+                                return Err(CommandError::InvalidArgument(
+                                    "value is out of range, must be positive".to_string(),
+                                ))
+                            }
+                        }
+                    }
+                };
+                Ok(AclCommand::Log(count))
+            }
+            "setuser" => {
+                validator_command(&arr, &["acl", "setuser"])?;
+                let mut args = extract_args(arr, 2)?.into_iter();
+                let username = extract_string(args.next(), "username")?;
+                if username.contains(|c: char| c.is_whitespace() || c == '\0') {
+                    return Err(CommandError::InvalidArgument(
+                        "Usernames can't contain spaces or null characters".to_string(),
+                    ));
+                }
+                let rules = args
+                    .map(|arg| extract_string(Some(arg), "rule"))
+                    .collect::<Result<_, _>>()?;
+                Ok(AclCommand::SetUser { username, rules })
+            }
+            "list" => {
+                validator_command(&arr, &["acl", "list"])?;
+                Ok(AclCommand::List)
+            }
+            "load" => {
+                validator_command(&arr, &["acl", "load"])?;
+                Ok(AclCommand::Load)
+            }
+            "save" => {
+                validator_command(&arr, &["acl", "save"])?;
+                Ok(AclCommand::Save)
+            }
+            "users" => {
+                validator_command(&arr, &["acl", "users"])?;
+                Ok(AclCommand::Users)
+            }
+            "whoami" => {
+                validator_command(&arr, &["acl", "whoami"])?;
+                Ok(AclCommand::WhoAmI)
+            }
+            _ => Err(CommandError::InvalidCommand(format!(
+                "unknown subcommand '{}'. Try ACL HELP.",
+                sub
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::Command;
+    use crate::Config;
+    use anyhow::Result;
+
+    fn run(backend: &Backend, state: &mut ConnectionState, args: &[&str]) -> RespFrame {
+        let arr = RespArray::new(
+            args.iter()
+                .map(|a| BulkString::from(*a).into())
+                .collect::<Vec<RespFrame>>(),
+        );
+        match Command::try_from(arr) {
+            Ok(cmd) => cmd.execute_for(backend, state),
+            Err(e) => e.into(),
+        }
+    }
+
+    fn error(msg: &str) -> RespFrame {
+        SimpleError::new(msg).into()
+    }
+
+    fn bulk(s: &str) -> RespFrame {
+        BulkString::from(s).into()
+    }
+
+    #[test]
+    fn test_acl_setuser_getuser_deluser() {
+        let backend = Backend::new();
+        let state = &mut ConnectionState::default();
+        assert_eq!(
+            run(
+                &backend,
+                state,
+                &["acl", "setuser", "alice", "on", ">pw", "~k*", "+get"]
+            ),
+            RESP_OK.clone()
+        );
+        assert_eq!(
+            run(&backend, state, &["acl", "setuser", "alice", "+nope"]),
+            error("ERR Error in ACL SETUSER modifier '+nope': Unknown command or category name in ACL")
+        );
+        assert_eq!(
+            run(&backend, state, &["acl", "setuser", "a b"]),
+            error("ERR Usernames can't contain spaces or null characters")
+        );
+
+        let RespFrame::Map(user) = run(&backend, state, &["acl", "getuser", "alice"]) else {
+            panic!("ACL GETUSER must reply with a map");
+        };
+        assert_eq!(
+            user.get(BulkString::from("flags")),
+            Some(&RespArray::new([bulk("on")]).into())
+        );
+        assert_eq!(
+            user.get(BulkString::from("commands")),
+            Some(&bulk("-@all +get"))
+        );
+        assert_eq!(user.get(BulkString::from("keys")), Some(&bulk("~k*")));
+        assert_eq!(user.get(BulkString::from("channels")), Some(&bulk("")));
+        assert_eq!(
+            run(&backend, state, &["acl", "getuser", "nobody"]),
+            RespFrame::Null(RespNull)
+        );
+
+        assert_eq!(
+            run(&backend, state, &["acl", "users"]),
+            RespArray::new([bulk("alice"), bulk("default")]).into()
+        );
+        let RespFrame::Array(list) = run(&backend, state, &["acl", "list"]) else {
+            panic!("ACL LIST must reply with an array");
+        };
+        assert_eq!(list.len(), 2);
+        assert_eq!(
+            run(&backend, state, &["acl", "deluser", "alice", "bob"]),
+            RespFrame::Integer(1)
+        );
+        assert_eq!(
+            run(&backend, state, &["acl", "deluser", "default"]),
+            error("ERR The 'default' user cannot be removed")
+        );
+        assert_eq!(run(&backend, state, &["acl", "whoami"]), bulk("default"));
+    }
+
+    #[test]
+    fn test_acl_cat_and_log() {
+        let backend = Backend::new();
+        let state = &mut ConnectionState::default();
+        let RespFrame::Array(categories) = run(&backend, state, &["acl", "cat"]) else {
+            panic!("ACL CAT must reply with an array");
+        };
+        assert_eq!(categories.len(), ACL_CATEGORIES.len());
+        let RespFrame::Array(hash) = run(&backend, state, &["acl", "cat", "HASH"]) else {
+            panic!("ACL CAT must reply with an array");
+        };
+        assert!(hash.contains(&bulk("hgetall")));
+        assert!(!hash.contains(&bulk("get")));
+        assert_eq!(
+            run(&backend, state, &["acl", "cat", "nope"]),
+            error("ERR Unknown category 'nope'")
+        );
+
+        assert_eq!(
+            run(&backend, state, &["auth", "alice", "wrong"]),
+            error("WRONGPASS invalid username-password pair or user is disabled.")
+        );
+        let RespFrame::Array(log) = run(&backend, state, &["acl", "log"]) else {
+            panic!("ACL LOG must reply with an array");
+        };
+        let RespFrame::Map(entry) = &log[0] else {
+            panic!("ACL LOG entries are maps");
+        };
+        assert_eq!(entry.get(BulkString::from("reason")), Some(&bulk("auth")));
+        assert_eq!(entry.get(BulkString::from("object")), Some(&bulk("AUTH")));
+        assert_eq!(
+            entry.get(BulkString::from("username")),
+            Some(&bulk("alice"))
+        );
+        assert_eq!(
+            run(&backend, state, &["acl", "log", "0"]),
+            error("ERR value is out of range, must be positive")
+        );
+        assert_eq!(
+            run(&backend, state, &["acl", "log", "reset"]),
+            RESP_OK.clone()
+        );
+        assert_eq!(
+            run(&backend, state, &["acl", "log"]),
+            RespArray::new([]).into()
+        );
+    }
+
+    #[test]
+    fn test_acl_save_and_load() -> Result<()> {
+        let backend = Backend::new();
+        let state = &mut ConnectionState::default();
+        assert_eq!(run(&backend, state, &["acl", "save"]), no_acl_file());
+
+        let path =
+            std::env::temp_dir().join(format!("simple-redis-aclcmd-{}.acl", std::process::id()));
+        let backend = Backend::with_config(Config {
+            aclfile: Some(path.clone()),
+            ..Default::default()
+        });
+        run(
+            &backend,
+            state,
+            &["acl", "setuser", "alice", "on", "nopass"],
+        );
+        assert_eq!(run(&backend, state, &["acl", "save"]), RESP_OK.clone());
+        run(&backend, state, &["acl", "deluser", "alice"]);
+        assert_eq!(run(&backend, state, &["acl", "load"]), RESP_OK.clone());
+        assert!(backend.acl().user("alice").is_some());
+
+        std::fs::write(&path, "user alice on +nope\n")?;
+        let reply = run(&backend, state, &["acl", "load"]);
+        std::fs::remove_file(&path)?;
+        let RespFrame::Error(e) = reply else {
+            panic!("a bad ACL file must be refused");
+        };
+        assert!(
+            e.contains(": line 1: Error in user declaration '+nope'"),
+            "{}",
+            e.0
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+use crate::cmd::{CommandError, ConfigCommand, RESP_OK};
+use crate::{Backend, BulkString, ConfigError, RespArray, RespFrame, RespMap, SimpleError};
+
+use super::{extract_args, extract_string, validator_command, CommandExecutor};
+
+impl CommandExecutor for ConfigCommand {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match self {
+            ConfigCommand::Get(patterns) => backend
+                .config()
+                .matching(&patterns)
+                .into_iter()
+                .map(|(name, value)| (BulkString::from(name).into(), BulkString::new(value).into()))
+                .collect::<RespMap>()
+                .into(),
+            ConfigCommand::Set(changes) => match backend.update_config(&changes) {
+                Ok(()) => RESP_OK.clone(),
+                Err(ConfigError::Unknown(name)) => SimpleError::new(format!(
+                    "ERR Unknown option or number of arguments for CONFIG SET - '{}'",
+                    name
+                ))
+                .into(),
+                Err(e) => SimpleError::new(format!(
+                    "ERR CONFIG SET failed (possibly related to argument '{}') - {}",
+                    e.name(),
+                    e
+                ))
+                .into(),
+            },
+            ConfigCommand::ResetStat => {
+                backend.stats().reset();
+                RESP_OK.clone()
+            }
+            ConfigCommand::Rewrite => {
+                let config = backend.config();
+                let Some(path) = &config.file else {
+                    return SimpleError::new("ERR The server is running without a config file")
+                        .into();
+                };
+                match config.rewrite(path) {
+                    Ok(()) => RESP_OK.clone(),
+                    Err(e) => SimpleError::new(format!("ERR Rewriting config file: {}", e)).into(),
+                }
+            }
+        }
+    }
+}
+
+impl TryFrom<RespArray> for ConfigCommand {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["config"])?;
+        let sub = extract_string(arr.get(1).cloned(), "subcommand")?.to_ascii_lowercase();
+        match sub.as_str() {
+            "get" => {
+                validator_command(&arr, &["config", "get"])?;
+                let patterns = extract_args(arr, 2)?
+                    .into_iter()
+                    .map(|arg| extract_string(Some(arg), "pattern"))
+                    .collect::<Result<_, _>>()?;
+                Ok(ConfigCommand::Get(patterns))
+            }
+            "set" => {
+                validator_command(&arr, &["config", "set"])?;
+                // parameter and value pairs
+                if !arr.len().is_multiple_of(2) {
+                    return Err(CommandError::WrongArity("config|set".to_string()));
+                }
+                let mut args = extract_args(arr, 2)?.into_iter();
+                let mut changes = vec![];
+                while let Some(name) = args.next() {
+                    let name = extract_string(Some(name), "parameter")?;
+                    changes.push((name, extract_string(args.next(), "value")?));
+                }
+                Ok(ConfigCommand::Set(changes))
+            }
+            "resetstat" => {
+                validator_command(&arr, &["config", "resetstat"])?;
+                Ok(ConfigCommand::ResetStat)
+            }
+            "rewrite" => {
+                validator_command(&arr, &["config", "rewrite"])?;
+                Ok(ConfigCommand::Rewrite)
+            }
+            _ => Err(CommandError::InvalidCommand(format!(
+                "unknown subcommand '{}'. Try CONFIG HELP.",
+                sub
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::Command;
+    use crate::Config;
+    use anyhow::Result;
+    use std::sync::atomic::Ordering;
+
+    fn run(backend: &Backend, args: &[&str]) -> RespFrame {
+        let arr = RespArray::new(
+            args.iter()
+                .map(|a| BulkString::from(*a).into())
+                .collect::<Vec<RespFrame>>(),
+        );
+        match Command::try_from(arr) {
+            Ok(cmd) => cmd.execute(backend),
+            Err(e) => e.into(),
+        }
+    }
+
+    fn error(msg: &str) -> RespFrame {
+        SimpleError::new(msg).into()
+    }
+
+    #[test]
+    fn test_config_get_and_set() {
+        let backend = Backend::new();
+        let mut expected = RespMap::new();
+        expected.insert(BulkString::from("port"), BulkString::from("6379").into());
+        expected.insert(
+            BulkString::from("lazyfree-lazy-user-flush"),
+            BulkString::from("no").into(),
+        );
+        assert_eq!(
+            run(&backend, &["config", "get", "PORT", "lazyfree-*", "port"]),
+            expected.into()
+        );
+
+        assert_eq!(
+            run(
+                &backend,
+                &[
+                    "config",
+                    "set",
+                    "lazyfree-lazy-user-flush",
+                    "yes",
+                    "proto-max-bulk-len",
+                    "1mb"
+                ]
+            ),
+            RESP_OK.clone()
+        );
+        assert!(backend.config().lazyfree_lazy_user_flush);
+        assert_eq!(backend.config().proto_max_bulk_len, 1024 * 1024);
+
+        assert_eq!(
+            run(&backend, &["config", "set", "port", "7000"]),
+            error("ERR CONFIG SET failed (possibly related to argument 'port') - can't set immutable config")
+        );
+        assert_eq!(
+            run(&backend, &["config", "set", "lazyfree-lazy-user-flush", "maybe"]),
+            error("ERR CONFIG SET failed (possibly related to argument 'lazyfree-lazy-user-flush') - argument must be 'yes' or 'no'")
+        );
+        assert_eq!(
+            run(&backend, &["config", "set", "nope", "1"]),
+            error("ERR Unknown option or number of arguments for CONFIG SET - 'nope'")
+        );
+        assert_eq!(
+            run(&backend, &["config", "set", "port", "1", "databases"]),
+            error("ERR wrong number of arguments for 'config|set' command")
+        );
+        assert_eq!(
+            run(&backend, &["config", "nope"]),
+            error("ERR unknown subcommand 'nope'. Try CONFIG HELP.")
+        );
+    }
+
+    #[test]
+    fn test_config_resetstat_and_rewrite() -> Result<()> {
+        let backend = Backend::new();
+        backend
+            .stats()
+            .commands_processed
+            .fetch_add(3, Ordering::Relaxed);
+        assert_eq!(run(&backend, &["config", "resetstat"]), RESP_OK.clone());
+        assert_eq!(
+            backend.stats().commands_processed.load(Ordering::Relaxed),
+            0
+        );
+
+        assert_eq!(
+            run(&backend, &["config", "rewrite"]),
+            error("ERR The server is running without a config file")
+        );
+
+        let path =
+            std::env::temp_dir().join(format!("simple-redis-rewrite-{}.conf", std::process::id()));
+        std::fs::write(&path, "# keep me\nlazyfree-lazy-user-flush no\n")?;
+        let config = Config::from_args([path.display().to_string()])?;
+        let backend = Backend::with_config(config);
+        run(
+            &backend,
+            &["config", "set", "lazyfree-lazy-user-flush", "yes"],
+        );
+        run(
+            &backend,
+            &["config", "set", "client-query-buffer-limit", "2mb"],
+        );
+        assert_eq!(run(&backend, &["config", "rewrite"]), RESP_OK.clone());
+        let text = std::fs::read_to_string(&path)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(
+            text,
+            "# keep me\n\
+             lazyfree-lazy-user-flush yes\n\
+             # Generated by CONFIG REWRITE\n\
+             client-query-buffer-limit 2097152\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,606 @@
+use crate::cmd::{Auth, ClientCommand, CommandError, Echo, Hello, Ping, Quit, Reset, RESP_OK};
+use crate::network::ConnectionState;
+use crate::{
+    AclDenial, Backend, BulkString, ClientFilter, ClientType, RespArray, RespFrame, RespMap,
+    RespNull, RespVersion, SimpleError, SimpleString, VerbatimString, DEFAULT_USER,
+};
+
+use super::{extract_args, extract_string, parse_arg, validator_command, CommandExecutor};
+
+impl CommandExecutor for Hello {
+    // without a connection there is nothing to switch, so answer as a fresh RESP2 client would
+    fn execute(self, _backend: &crate::Backend) -> RespFrame {
+        self.negotiate(&mut ConnectionState::default())
+    }
+
+    fn execute_for(mut self, backend: &crate::Backend, state: &mut ConnectionState) -> RespFrame {
+        // like redis, an unsupported version is refused before logging in
+        if self.protover.is_some_and(|v| v != 2 && v != 3) {
+            return self.negotiate(state);
+        }
+        match self.auth.take() {
+            Some((username, password)) => {
+                if let Err(e) = login(backend, state, &username, &password) {
+                    return e.into();
+                }
+            }
+            None if !state.authenticated => {
+                return SimpleError::new(
+                    "NOAUTH HELLO must be called with the client already authenticated, \
+                     otherwise the HELLO <proto> AUTH <user> <pass> option can be used to \
+                     authenticate the client and select the RESP protocol version at the same \
+                     time",
+                )
+                .into()
+            }
+            None => {}
+        }
+        self.negotiate(state)
+    }
+}
+
+impl CommandExecutor for Auth {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        self.execute_for(backend, &mut ConnectionState::default())
+    }
+
+    fn execute_for(self, backend: &crate::Backend, state: &mut ConnectionState) -> RespFrame {
+        let username = match self.username {
+            Some(username) => username,
+            None if !backend.acl().auth_required() => {
+                return SimpleError::new(
+                    "ERR AUTH <password> called without any password configured for the \
+                     default user. Are you sure your configuration is correct?",
+                )
+                .into()
+            }
+            None => DEFAULT_USER.to_string(),
+        };
+        match login(backend, state, &username, &self.password) {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+// log the connection in as `username`; failures are kept in ACL LOG
+fn login(
+    backend: &Backend,
+    state: &mut ConnectionState,
+    username: &str,
+    password: &str,
+) -> Result<(), SimpleError> {
+    if backend.acl().authenticate(username, password) {
+        state.user = username.into();
+        state.authenticated = true;
+        return Ok(());
+    }
+    backend.acl().log(
+        AclDenial::Auth,
+        "AUTH",
+        username,
+        state.info.to_string(),
+        backend.config().acllog_max_len,
+    );
+    Err(SimpleError::new(
+        "WRONGPASS invalid username-password pair or user is disabled.",
+    ))
+}
+
+impl CommandExecutor for Ping {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        self.execute_for(backend, &mut ConnectionState::default())
+    }
+
+    // a subscribed RESP2 client gets it shaped like a message, so it can tell them apart
+    fn execute_for(self, _backend: &crate::Backend, state: &mut ConnectionState) -> RespFrame {
+        match (
+            self.message,
+            state.flags.pubsub && state.protocol == RespVersion::Resp2,
+        ) {
+            (message, true) => RespArray::new(vec![
+                BulkString::from("pong").into(),
+                message.unwrap_or_else(|| BulkString::from("")).into(),
+            ])
+            .into(),
+            (Some(message), false) => message.into(),
+            (None, false) => SimpleString::new("PONG").into(),
+        }
+    }
+}
+
+impl CommandExecutor for Echo {
+    fn execute(self, _backend: &crate::Backend) -> RespFrame {
+        self.message.into()
+    }
+}
+
+impl CommandExecutor for Quit {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        self.execute_for(backend, &mut ConnectionState::default())
+    }
+
+    fn execute_for(self, _backend: &crate::Backend, state: &mut ConnectionState) -> RespFrame {
+        state.flags.close_after_reply = true;
+        RESP_OK.clone()
+    }
+}
+
+impl CommandExecutor for Reset {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        self.execute_for(backend, &mut ConnectionState::default())
+    }
+
+    // back to the state of a freshly accepted connection, keeping only its id and addresses
+    fn execute_for(self, backend: &crate::Backend, state: &mut ConnectionState) -> RespFrame {
+        state.name = None;
+        state.protocol = RespVersion::Resp2;
+        state.db = 0;
+        state.user = DEFAULT_USER.into();
+        state.authenticated = !backend.acl().auth_required();
+        let id = state.id;
+        backend
+            .pubsub()
+            .unsubscribe_all(id, &mut state.subscriptions);
+        state.flags.pubsub = false;
+        SimpleString::new("RESET").into()
+    }
+}
+
+impl CommandExecutor for ClientCommand {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        self.execute_for(backend, &mut ConnectionState::default())
+    }
+
+    fn execute_for(self, backend: &crate::Backend, state: &mut ConnectionState) -> RespFrame {
+        match self {
+            ClientCommand::SetName(name) => {
+                state.name = name;
+                RESP_OK.clone()
+            }
+            ClientCommand::GetName => match &state.name {
+                Some(name) => BulkString::from(name.as_str()).into(),
+                None => RespFrame::Null(RespNull),
+            },
+            ClientCommand::Id => (state.id as i64).into(),
+            ClientCommand::Info => VerbatimString::text(format!("{}\n", state.info)).into(),
+            ClientCommand::List(filter) => {
+                // the registry only has what this connection published after its last command
+                let list = backend
+                    .clients()
+                    .list(&filter)
+                    .into_iter()
+                    .map(|info| match info.id == state.id {
+                        true => format!("{}\n", state.info),
+                        false => format!("{}\n", info),
+                    })
+                    .collect::<String>();
+                VerbatimString::text(list).into()
+            }
+            ClientCommand::Kill {
+                filter,
+                skip_me,
+                legacy,
+            } => {
+                let mut killed = backend.clients().kill(&filter, Some(state.id));
+                // closing ourselves right away would lose the reply
+                if !skip_me && filter.matches(&state.info) {
+                    state.flags.close_after_reply = true;
+                    killed += 1;
+                }
+                match (legacy, killed) {
+                    (true, 0) => SimpleError::new("ERR No such client").into(),
+                    (true, _) => RESP_OK.clone(),
+                    (false, n) => (n as i64).into(),
+                }
+            }
+        }
+    }
+}
+
+impl Hello {
+    /// Validate the handshake and switch the connection's protocol and name on success.
+    pub fn negotiate(self, state: &mut ConnectionState) -> RespFrame {
+        let version = match self.protover {
+            None => state.protocol,
+            Some(2) => RespVersion::Resp2,
+            Some(3) => RespVersion::Resp3,
+            Some(_) => return SimpleError::new("NOPROTO unsupported protocol version").into(),
+        };
+
+        state.protocol = version;
+        if let Some(name) = self.setname {
+            state.name = (!name.is_empty()).then_some(name);
+        }
+
+        let mut info = RespMap::new();
+        info.insert(BulkString::from("server"), BulkString::from("redis").into());
+        info.insert(
+            BulkString::from("version"),
+            BulkString::from(env!("CARGO_PKG_VERSION")).into(),
+        );
+        let proto = match version {
+            RespVersion::Resp2 => 2,
+            RespVersion::Resp3 => 3,
+        };
+        info.insert(BulkString::from("proto"), RespFrame::Integer(proto));
+        info.insert(BulkString::from("id"), RespFrame::Integer(state.id as i64));
+        info.insert(
+            BulkString::from("mode"),
+            BulkString::from("standalone").into(),
+        );
+        info.insert(BulkString::from("role"), BulkString::from("master").into());
+        info.insert(BulkString::from("modules"), RespArray::new([]).into());
+        info.into()
+    }
+}
+
+impl TryFrom<RespArray> for Auth {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["auth"])?;
+        let mut args = extract_args(arr, 1)?.into_iter();
+        let first = extract_string(args.next(), "password")?;
+        match (args.next(), args.next()) {
+            (None, _) => Ok(Auth {
+                username: None,
+                password: first,
+            }),
+            (Some(password), None) => Ok(Auth {
+                username: Some(first),
+                password: extract_string(Some(password), "password")?,
+            }),
+            _ => Err(syntax_error()),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Hello {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["hello"])?;
+
+        let mut args = extract_args(arr, 1)?.into_iter();
+        let mut hello = Hello {
+            protover: None,
+            auth: None,
+            setname: None,
+        };
+
+        let Some(protover) = args.next() else {
+            return Ok(hello);
+        };
+        hello.protover = Some(parse_arg(Some(protover), "protocol version")?);
+
+        while let Some(arg) = args.next() {
+            match extract_string(Some(arg), "option")?
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "auth" => {
+                    let user = extract_string(args.next(), "username")?;
+                    let pass = extract_string(args.next(), "password")?;
+                    hello.auth = Some((user, pass));
+                }
+                "setname" => hello.setname = Some(extract_client_name(args.next())?),
+                opt => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Syntax error in HELLO option '{}'",
+                        opt
+                    )))
+                }
+            }
+        }
+
+        Ok(hello)
+    }
+}
+
+impl TryFrom<RespArray> for Ping {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["ping"])?;
+        if arr.len() > 2 {
+            return Err(CommandError::WrongArity("ping".to_string()));
+        }
+
+        let message = match extract_args(arr, 1)?.into_iter().next() {
+            Some(RespFrame::BulkString(message)) => Some(message),
+            Some(_) => return Err(CommandError::InvalidArgument("Invalid message".to_string())),
+            None => None,
+        };
+        Ok(Ping { message })
+    }
+}
+
+impl TryFrom<RespArray> for Echo {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["echo"])?;
+
+        match extract_args(arr, 1)?.into_iter().next() {
+            Some(RespFrame::BulkString(message)) => Ok(Echo { message }),
+            _ => Err(CommandError::InvalidArgument("Invalid message".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Quit {
+    type Error = CommandError;
+
+    // like redis, any arguments are ignored
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["quit"])?;
+        Ok(Quit)
+    }
+}
+
+impl TryFrom<RespArray> for Reset {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["reset"])?;
+        Ok(Reset)
+    }
+}
+
+impl TryFrom<RespArray> for ClientCommand {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["client"])?;
+
+        let sub = extract_string(arr.get(1).cloned(), "subcommand")?.to_ascii_lowercase();
+        match sub.as_str() {
+            "setname" => {
+                validator_command(&arr, &["client", "setname"])?;
+                let name = extract_client_name(extract_args(arr, 2)?.into_iter().next())?;
+                Ok(ClientCommand::SetName((!name.is_empty()).then_some(name)))
+            }
+            "getname" => {
+                validator_command(&arr, &["client", "getname"])?;
+                Ok(ClientCommand::GetName)
+            }
+            "id" => {
+                validator_command(&arr, &["client", "id"])?;
+                Ok(ClientCommand::Id)
+            }
+            "info" => {
+                validator_command(&arr, &["client", "info"])?;
+                Ok(ClientCommand::Info)
+            }
+            "list" => {
+                validator_command(&arr, &["client", "list"])?;
+                let mut args = extract_args(arr, 2)?.into_iter();
+                let mut filter = ClientFilter::default();
+                while let Some(arg) = args.next() {
+                    match extract_string(Some(arg), "option")?
+                        .to_ascii_lowercase()
+                        .as_str()
+                    {
+                        "type" => filter.kind = Some(parse_client_type(args.next())?),
+                        // every remaining argument is an id
+                        "id" => {
+                            for id in args.by_ref() {
+                                filter.ids.push(parse_client_id(Some(id))?);
+                            }
+                            if filter.ids.is_empty() {
+                                return Err(syntax_error());
+                            }
+                        }
+                        _ => return Err(syntax_error()),
+                    }
+                }
+                Ok(ClientCommand::List(filter))
+            }
+            "kill" => {
+                validator_command(&arr, &["client", "kill"])?;
+                let mut args = extract_args(arr, 2)?.into_iter();
+                if args.len() == 1 {
+                    let filter = ClientFilter {
+                        addr: Some(extract_string(args.next(), "address")?),
+                        ..Default::default()
+                    };
+                    return Ok(ClientCommand::Kill {
+                        filter,
+                        skip_me: false,
+                        legacy: true,
+                    });
+                }
+
+                let mut filter = ClientFilter::default();
+                let mut skip_me = true;
+                while let Some(arg) = args.next() {
+                    let option = extract_string(Some(arg), "option")?.to_ascii_lowercase();
+                    if args.len() == 0 {
+                        return Err(syntax_error());
+                    }
+                    match option.as_str() {
+                        "id" => filter.ids.push(parse_client_id(args.next())?),
+                        "addr" => filter.addr = Some(extract_string(args.next(), "address")?),
+                        "laddr" => filter.laddr = Some(extract_string(args.next(), "address")?),
+                        "user" => filter.user = Some(extract_string(args.next(), "username")?),
+                        "type" => filter.kind = Some(parse_client_type(args.next())?),
+                        "skipme" => {
+                            skip_me = match extract_string(args.next(), "skipme")?
+                                .to_ascii_lowercase()
+                                .as_str()
+                            {
+                                "yes" => true,
+                                "no" => false,
+                                _ => return Err(syntax_error()),
+                            }
+                        }
+                        _ => return Err(syntax_error()),
+                    }
+                }
+                Ok(ClientCommand::Kill {
+                    filter,
+                    skip_me,
+                    legacy: false,
+                })
+            }
+            _ => Err(CommandError::InvalidCommand(format!(
+                "unknown subcommand '{}'. Try CLIENT HELP.",
+                sub
+            ))),
+        }
+    }
+}
+
+fn parse_client_id(arg: Option<RespFrame>) -> Result<u64, CommandError> {
+    match parse_arg::<u64>(arg, "client-id") {
+        Ok(id) if id > 0 => Ok(id),
+        _ => Err(CommandError::InvalidArgument(
+            "client-id should be greater than 0".to_string(),
+        )),
+    }
+}
+
+fn parse_client_type(arg: Option<RespFrame>) -> Result<ClientType, CommandError> {
+    let kind = extract_string(arg, "client type")?;
+    ClientType::parse(&kind)
+        .ok_or_else(|| CommandError::InvalidArgument(format!("Unknown client type '{}'", kind)))
+}
+
+fn syntax_error() -> CommandError {
+    CommandError::InvalidArgument("syntax error".to_string())
+}
+
+// CLIENT SETNAME and HELLO SETNAME alike; names show up in CLIENT LIST, which is split on
+// spaces and newlines
+fn extract_client_name(arg: Option<RespFrame>) -> Result<String, CommandError> {
+    let name = extract_string(arg, "clientname")?;
+    if name.bytes().any(|c| !(b'!'..=b'~').contains(&c)) {
+        return Err(CommandError::InvalidArgument(
+            "Client names cannot contain spaces, newlines or special characters.".to_string(),
+        ));
+    }
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_hello_try_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*7\r\n$5\r\nhello\r\n$1\r\n3\r\n$4\r\nAUTH\r\n$7\r\ndefault\r\n$3\r\npwd\r\n$7\r\nSETNAME\r\n$3\r\ncli\r\n");
+
+        let arr = RespArray::decode(&mut buf)?;
+        let hello = Hello::try_from(arr)?;
+        assert_eq!(hello.protover, Some(3));
+        assert_eq!(hello.auth, Some(("default".to_string(), "pwd".to_string())));
+        assert_eq!(hello.setname, Some("cli".to_string()));
+
+        let arr = RespArray::new([
+            b"hello".into(),
+            b"3".into(),
+            b"SETNAME".into(),
+            b"a\nb".into(),
+        ]);
+        assert_eq!(
+            RespFrame::from(Hello::try_from(arr).unwrap_err()),
+            SimpleError::new(
+                "ERR Client names cannot contain spaces, newlines or special characters."
+            )
+            .into()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hello_negotiate() {
+        let mut state = ConnectionState::default();
+        let hello = Hello {
+            protover: Some(3),
+            auth: None,
+            setname: Some("cli".to_string()),
+        };
+        let RespFrame::Map(info) = hello.negotiate(&mut state) else {
+            panic!("HELLO must reply with a map");
+        };
+        assert_eq!(
+            info.get(BulkString::from("proto")),
+            Some(&RespFrame::Integer(3))
+        );
+        assert_eq!(state.protocol, RespVersion::Resp3);
+        assert_eq!(state.name.as_deref(), Some("cli"));
+
+        let hello = Hello {
+            protover: Some(4),
+            auth: None,
+            setname: None,
+        };
+        assert_eq!(
+            hello.negotiate(&mut state),
+            SimpleError::new("NOPROTO unsupported protocol version").into()
+        );
+        assert_eq!(state.protocol, RespVersion::Resp3);
+    }
+
+    fn run(args: &[&str], state: &mut ConnectionState) -> RespFrame {
+        let arr = RespArray::new(
+            args.iter()
+                .map(|a| BulkString::from(*a).into())
+                .collect::<Vec<RespFrame>>(),
+        );
+        match crate::cmd::Command::try_from(arr) {
+            Ok(cmd) => cmd.execute_for(&crate::Backend::new(), state),
+            Err(e) => e.into(),
+        }
+    }
+
+    #[test]
+    fn test_ping_echo_and_client_name() {
+        let mut state = ConnectionState::default();
+        assert_eq!(run(&["PING"], &mut state), SimpleString::new("PONG").into());
+        assert_eq!(run(&["ping", "hi"], &mut state), b"hi".into());
+        assert_eq!(
+            run(&["ping", "a", "b"], &mut state),
+            SimpleError::new("ERR wrong number of arguments for 'ping' command").into()
+        );
+        assert_eq!(run(&["echo", "hey"], &mut state), b"hey".into());
+
+        assert_eq!(
+            run(&["client", "getname"], &mut state),
+            RespFrame::Null(RespNull)
+        );
+        assert_eq!(
+            run(&["client", "setname", "cli"], &mut state),
+            RESP_OK.clone()
+        );
+        assert_eq!(run(&["CLIENT", "GETNAME"], &mut state), b"cli".into());
+        assert_eq!(
+            run(&["client", "setname", "a b"], &mut state),
+            SimpleError::new(
+                "ERR Client names cannot contain spaces, newlines or special characters."
+            )
+            .into()
+        );
+        assert_eq!(
+            run(&["client", "nope"], &mut state),
+            SimpleError::new("ERR unknown subcommand 'nope'. Try CLIENT HELP.").into()
+        );
+
+        state.protocol = RespVersion::Resp3;
+        assert_eq!(
+            run(&["reset"], &mut state),
+            SimpleString::new("RESET").into()
+        );
+        assert_eq!(state.name, None);
+        assert_eq!(state.protocol, RespVersion::Resp2);
+
+        assert_eq!(run(&["quit"], &mut state), RESP_OK.clone());
+        assert!(state.flags.close_after_reply);
+    }
+}
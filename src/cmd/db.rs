@@ -0,0 +1,322 @@
+use crate::cmd::{CommandError, DbSize, FlushAll, FlushDb, Move, Select, SwapDb, RESP_OK};
+use crate::network::ConnectionState;
+use crate::{RespArray, RespFrame, SimpleError};
+
+use super::{extract_args, extract_string, validator_command, CommandExecutor};
+
+impl CommandExecutor for Select {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        self.execute_for(backend, &mut ConnectionState::default())
+    }
+
+    fn execute_for(self, backend: &crate::Backend, state: &mut ConnectionState) -> RespFrame {
+        if self.index >= backend.databases() {
+            return out_of_range();
+        }
+        state.db = self.index;
+        RESP_OK.clone()
+    }
+}
+
+impl CommandExecutor for Move {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        if self.db == backend.index() {
+            return SimpleError::new("ERR source and destination objects are the same").into();
+        }
+        match backend.select(self.db) {
+            Some(dst) => (backend.move_key(&self.key, &dst) as i64).into(),
+            None => out_of_range(),
+        }
+    }
+}
+
+impl CommandExecutor for SwapDb {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        let databases = backend.databases();
+        if self.first >= databases {
+            return SimpleError::new("ERR invalid first DB index").into();
+        }
+        if self.second >= databases {
+            return SimpleError::new("ERR invalid second DB index").into();
+        }
+        backend.swap_db(self.first, self.second);
+        RESP_OK.clone()
+    }
+}
+
+impl CommandExecutor for FlushDb {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        backend.flush_db(
+            self.lazy
+                .unwrap_or_else(|| backend.config().lazyfree_lazy_user_flush),
+        );
+        RESP_OK.clone()
+    }
+}
+
+impl CommandExecutor for FlushAll {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        backend.flush_all(
+            self.lazy
+                .unwrap_or_else(|| backend.config().lazyfree_lazy_user_flush),
+        );
+        RESP_OK.clone()
+    }
+}
+
+impl CommandExecutor for DbSize {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        (backend.dbsize() as i64).into()
+    }
+}
+
+fn out_of_range() -> RespFrame {
+    SimpleError::new("ERR DB index is out of range").into()
+}
+
+impl TryFrom<RespArray> for Select {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["select"])?;
+
+        let mut args = extract_args(arr, 1)?.into_iter();
+        Ok(Select {
+            index: parse_db_index(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Move {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["move"])?;
+
+        let mut args = extract_args(arr, 1)?.into_iter();
+        Ok(Move {
+            key: extract_string(args.next(), "key")?,
+            db: parse_db_index(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for SwapDb {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["swapdb"])?;
+
+        let mut args = extract_args(arr, 1)?.into_iter();
+        Ok(SwapDb {
+            first: parse_db_index(args.next())?,
+            second: parse_db_index(args.next())?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for FlushDb {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["flushdb"])?;
+        Ok(FlushDb {
+            lazy: parse_flush_mode(extract_args(arr, 1)?)?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for FlushAll {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["flushall"])?;
+        Ok(FlushAll {
+            lazy: parse_flush_mode(extract_args(arr, 1)?)?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for DbSize {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["dbsize"])?;
+        Ok(DbSize)
+    }
+}
+
+// negative indexes are out of range rather than not integers
+fn parse_db_index(arg: Option<RespFrame>) -> Result<usize, CommandError> {
+    let index = extract_string(arg, "DB index")?
+        .parse::<i64>()
+        .map_err(|_| {
+            CommandError::InvalidArgument("value is not an integer or out of range".to_string())
+        })?;
+    usize::try_from(index)
+        .map_err(|_| CommandError::InvalidArgument("DB index is out of range".to_string()))
+}
+
+// FLUSHDB and FLUSHALL take an optional ASYNC or SYNC
+// no mode leaves it to lazyfree-lazy-user-flush
+fn parse_flush_mode(args: Vec<RespFrame>) -> Result<Option<bool>, CommandError> {
+    let mut args = args.into_iter();
+    let lazy = match args.next() {
+        None => None,
+        Some(arg) => match extract_string(Some(arg), "flush mode")?
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "async" => Some(true),
+            "sync" => Some(false),
+            _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+        },
+    };
+    match args.next() {
+        Some(_) => Err(CommandError::InvalidArgument("syntax error".to_string())),
+        None => Ok(lazy),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::Command;
+    use crate::{Backend, BulkString, KeyType};
+
+    fn run(backend: &Backend, state: &mut ConnectionState, args: &[&str]) -> RespFrame {
+        let arr = RespArray::new(
+            args.iter()
+                .map(|a| BulkString::from(*a).into())
+                .collect::<Vec<RespFrame>>(),
+        );
+        let db = backend.select(state.db).unwrap();
+        match Command::try_from(arr) {
+            Ok(cmd) => cmd.execute_for(&db, state),
+            Err(e) => e.into(),
+        }
+    }
+
+    #[test]
+    fn test_select_move_and_dbsize() {
+        let backend = Backend::new();
+        let mut state = ConnectionState::default();
+        run(&backend, &mut state, &["set", "a", "1"]);
+        run(&backend, &mut state, &["hset", "h", "f", "v"]);
+        assert_eq!(run(&backend, &mut state, &["dbsize"]), 2.into());
+
+        assert_eq!(
+            run(&backend, &mut state, &["select", "16"]),
+            SimpleError::new("ERR DB index is out of range").into()
+        );
+        assert_eq!(
+            run(&backend, &mut state, &["select", "x"]),
+            SimpleError::new("ERR value is not an integer or out of range").into()
+        );
+        assert_eq!(run(&backend, &mut state, &["move", "h", "1"]), 1.into());
+        assert_eq!(run(&backend, &mut state, &["move", "nope", "1"]), 0.into());
+        assert_eq!(
+            run(&backend, &mut state, &["move", "a", "0"]),
+            SimpleError::new("ERR source and destination objects are the same").into()
+        );
+
+        assert_eq!(run(&backend, &mut state, &["select", "1"]), RESP_OK.clone());
+        assert_eq!(state.db, 1);
+        assert_eq!(
+            run(&backend, &mut state, &["get", "a"]),
+            RespFrame::Null(crate::RespNull)
+        );
+        assert_eq!(run(&backend, &mut state, &["hget", "h", "f"]), b"v".into());
+        assert_eq!(run(&backend, &mut state, &["dbsize"]), 1.into());
+
+        // a key of the same name already in the destination is left alone
+        run(&backend, &mut state, &["hset", "h", "f", "mine"]);
+        run(&backend, &mut state, &["select", "0"]);
+        run(&backend, &mut state, &["hset", "h", "f", "theirs"]);
+        assert_eq!(run(&backend, &mut state, &["move", "h", "1"]), 0.into());
+        assert_eq!(
+            run(&backend, &mut state, &["hget", "h", "f"]),
+            b"theirs".into()
+        );
+        run(&backend, &mut state, &["select", "1"]);
+        assert_eq!(
+            run(&backend, &mut state, &["hget", "h", "f"]),
+            b"mine".into()
+        );
+    }
+
+    #[test]
+    fn test_swapdb_and_flush() {
+        let backend = Backend::new();
+        let mut state = ConnectionState::default();
+        run(&backend, &mut state, &["set", "a", "1"]);
+
+        // SWAPDB switches what every client sees, including ones that already selected a db
+        assert_eq!(
+            run(&backend, &mut state, &["swapdb", "0", "2"]),
+            RESP_OK.clone()
+        );
+        assert_eq!(run(&backend, &mut state, &["dbsize"]), 0.into());
+        assert_eq!(
+            backend.select(2).unwrap().key_type("a"),
+            Some(KeyType::String)
+        );
+        assert_eq!(
+            run(&backend, &mut state, &["swapdb", "0", "16"]),
+            SimpleError::new("ERR invalid second DB index").into()
+        );
+
+        run(&backend, &mut state, &["set", "b", "1"]);
+        assert_eq!(run(&backend, &mut state, &["flushdb"]), RESP_OK.clone());
+        assert_eq!(run(&backend, &mut state, &["dbsize"]), 0.into());
+        assert_eq!(backend.select(2).unwrap().dbsize(), 1);
+
+        assert_eq!(
+            run(&backend, &mut state, &["flushall", "async"]),
+            RESP_OK.clone()
+        );
+        assert_eq!(backend.select(2).unwrap().dbsize(), 0);
+
+        // FT indexes aren't keys, a flush empties them but keeps their definitions
+        run(
+            &backend,
+            &mut state,
+            &["ft.create", "idx", "SCHEMA", "title", "TEXT"],
+        );
+        run(&backend, &mut state, &["hset", "doc", "title", "old"]);
+        assert_eq!(run(&backend, &mut state, &["flushdb"]), RESP_OK.clone());
+        run(&backend, &mut state, &["hset", "doc", "title", "new"]);
+        let found = RespArray::new([1.into(), b"doc".into()]);
+        assert_eq!(
+            run(
+                &backend,
+                &mut state,
+                &["ft.search", "idx", "new", "NOCONTENT"]
+            ),
+            found.clone().into()
+        );
+        assert_eq!(
+            run(
+                &backend,
+                &mut state,
+                &["ft.search", "idx", "old", "NOCONTENT"]
+            ),
+            RespArray::new([0.into()]).into()
+        );
+        assert_eq!(run(&backend, &mut state, &["flushall"]), RESP_OK.clone());
+        run(&backend, &mut state, &["hset", "doc", "title", "new"]);
+        assert_eq!(
+            run(
+                &backend,
+                &mut state,
+                &["ft.search", "idx", "new", "NOCONTENT"]
+            ),
+            found.into()
+        );
+
+        assert_eq!(
+            run(&backend, &mut state, &["flushall", "later"]),
+            SimpleError::new("ERR syntax error").into()
+        );
+    }
+}
@@ -1,12 +1,15 @@
 use crate::{
     cmd::{CommandError, HGet, HGetAll, HSet},
-    BulkString, RespArray, RespFrame,
+    BulkString, KeyType, RespArray, RespFrame, RespMap,
 };
 
-use super::{extract_args, validator_command, CommandExecutor};
+use super::{expect_type, extract_args, validator_command, CommandExecutor};
 
 impl CommandExecutor for HGet {
     fn execute(self, backend: &crate::Backend) -> RespFrame {
+        if let Err(e) = expect_type(backend, &self.key, KeyType::Hash) {
+            return e.into();
+        }
         match backend.hget(&self.key, &self.field) {
             Some(value) => value,
             None => RespFrame::Null(crate::RespNull),
@@ -16,6 +19,9 @@ impl CommandExecutor for HGet {
 
 impl CommandExecutor for HSet {
     fn execute(self, backend: &crate::Backend) -> RespFrame {
+        if let Err(e) = expect_type(backend, &self.key, KeyType::Hash) {
+            return e.into();
+        }
         backend.hset(self.key, self.field, self.value);
         crate::cmd::RESP_OK.clone()
     }
@@ -23,30 +29,25 @@ impl CommandExecutor for HSet {
 
 impl CommandExecutor for HGetAll {
     fn execute(self, backend: &crate::Backend) -> RespFrame {
+        if let Err(e) = expect_type(backend, &self.key, KeyType::Hash) {
+            return e.into();
+        }
         let hmap = backend.hmap.get(&self.key);
 
-        match hmap {
-            Some(hmap) => {
-                let mut data = Vec::with_capacity(hmap.len());
-
-                for v in hmap.iter() {
-                    let key = v.key().to_owned();
-                    data.push((key, v.value().clone()));
-                }
-
-                if self.sort {
-                    data.sort_by(|a, b| a.0.cmp(&b.0));
-                }
-
-                let ret = data
-                    .into_iter()
-                    .flat_map(|(k, v)| vec![BulkString::new(k).into(), v])
-                    .collect::<Vec<RespFrame>>();
-
-                RespArray::new(ret).into()
-            }
-            None => RespArray::new([]).into(),
-        }
+        let mut fields = hmap
+            .map(|hmap| {
+                hmap.iter()
+                    .map(|v| (v.key().to_owned(), v.value().clone()))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        fields.sort_by(|a, b| a.0.cmp(&b.0));
+
+        fields
+            .into_iter()
+            .map(|(k, v)| (BulkString::new(k).into(), v))
+            .collect::<RespMap>()
+            .into()
     }
 }
 
@@ -54,17 +55,17 @@ impl TryFrom<RespArray> for HGet {
     type Error = CommandError;
 
     fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
-        validator_command(&arr, &["hget"], 2)?;
+        validator_command(&arr, &["hget"])?;
 
         let mut args = extract_args(arr, 1)?.into_iter();
 
         let key = match args.next() {
-            Some(RespFrame::BulkString(key)) => String::from_utf8(key.0)?,
+            Some(RespFrame::BulkString(key)) => String::from_utf8(key.to_vec())?,
             _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
         };
 
         let field = match args.next() {
-            Some(RespFrame::BulkString(field)) => String::from_utf8(field.0)?,
+            Some(RespFrame::BulkString(field)) => String::from_utf8(field.to_vec())?,
             _ => return Err(CommandError::InvalidArgument("Invalid field".to_string())),
         };
 
@@ -76,17 +77,17 @@ impl TryFrom<RespArray> for HSet {
     type Error = CommandError;
 
     fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
-        validator_command(&arr, &["hset"], 3)?;
+        validator_command(&arr, &["hset"])?;
 
         let mut args = extract_args(arr, 1)?.into_iter();
 
         let key = match args.next() {
-            Some(RespFrame::BulkString(key)) => String::from_utf8(key.0)?,
+            Some(RespFrame::BulkString(key)) => String::from_utf8(key.to_vec())?,
             _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
         };
 
         let field = match args.next() {
-            Some(RespFrame::BulkString(field)) => String::from_utf8(field.0)?,
+            Some(RespFrame::BulkString(field)) => String::from_utf8(field.to_vec())?,
             _ => return Err(CommandError::InvalidArgument("Invalid field".to_string())),
         };
 
@@ -103,28 +104,27 @@ impl TryFrom<RespArray> for HGetAll {
     type Error = CommandError;
 
     fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
-        validator_command(&arr, &["hgetall"], 1)?;
+        validator_command(&arr, &["hgetall"])?;
 
         let mut args = extract_args(arr, 1)?.into_iter();
 
         let key = match args.next() {
-            Some(RespFrame::BulkString(key)) => String::from_utf8(key.0)?,
+            Some(RespFrame::BulkString(key)) => String::from_utf8(key.to_vec())?,
             _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
         };
 
-        Ok(HGetAll { key, sort: false })
+        Ok(HGetAll { key })
     }
 }
 
 #[cfg(test)]
-
 mod tests {
     use crate::cmd::RESP_OK;
     use anyhow::Result;
     use bytes::BytesMut;
 
     use super::*;
-    use crate::{RespArray, RespDecode};
+    use crate::{BulkString, RespArray, RespDecode};
 
     #[test]
     fn test_hget_try_from_resp_array() -> Result<()> {
@@ -198,17 +198,24 @@ mod tests {
 
         let cmd = HGetAll {
             key: "map".to_string(),
-            sort: true,
         };
         let result = cmd.execute(&backend);
 
+        let mut expected = RespMap::new();
+        expected.insert(BulkString::from("hello"), BulkString::from("world").into());
+        expected.insert(
+            BulkString::from("hello1"),
+            BulkString::from("world1").into(),
+        );
+        assert_eq!(result, expected.clone().into());
+
         let expected = RespArray::new([
             BulkString::from("hello").into(),
             BulkString::from("world").into(),
             BulkString::from("hello1").into(),
             BulkString::from("world1").into(),
         ]);
-        assert_eq!(result, expected.into());
+        assert_eq!(result.into_resp2(), expected.into());
         Ok(())
     }
 }
@@ -1,13 +1,16 @@
 use crate::cmd::RESP_OK;
 use crate::{
     cmd::{CommandError, Get, Set},
-    RespArray, RespFrame,
+    KeyType, RespArray, RespFrame,
 };
 
-use super::{extract_args, validator_command, CommandExecutor};
+use super::{expect_type, extract_args, validator_command, CommandExecutor};
 
 impl CommandExecutor for Get {
     fn execute(self, backend: &crate::Backend) -> RespFrame {
+        if let Err(e) = expect_type(backend, &self.key, KeyType::String) {
+            return e.into();
+        }
         match backend.get(&self.key) {
             Some(value) => value,
             None => RespFrame::Null(crate::RespNull),
@@ -26,13 +29,13 @@ impl TryFrom<RespArray> for Get {
     type Error = CommandError;
 
     fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
-        validator_command(&arr, &["get"], 1)?;
+        validator_command(&arr, &["get"])?;
 
         let mut args = extract_args(arr, 1)?.into_iter();
 
         match args.next() {
             Some(RespFrame::BulkString(key)) => Ok(Get {
-                key: String::from_utf8(key.0)?,
+                key: String::from_utf8(key.to_vec())?,
             }),
             _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
         }
@@ -43,12 +46,12 @@ impl TryFrom<RespArray> for Set {
     type Error = CommandError;
 
     fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
-        validator_command(&arr, &["set"], 2)?;
+        validator_command(&arr, &["set"])?;
 
         let mut args = extract_args(arr, 1)?.into_iter();
 
         let key = match args.next() {
-            Some(RespFrame::BulkString(key)) => String::from_utf8(key.0)?,
+            Some(RespFrame::BulkString(key)) => String::from_utf8(key.to_vec())?,
             _ => return Err(CommandError::InvalidArgument("Invalid key".to_string())),
         };
 
@@ -119,4 +122,41 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_wrong_arity_and_wrong_type() -> Result<()> {
+        let mut buf = BytesMut::from(&b"*1\r\n$3\r\nget\r\n"[..]);
+        let err = Get::try_from(RespArray::decode(&mut buf)?).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ERR wrong number of arguments for 'get' command"
+        );
+
+        let backend = crate::Backend::new();
+        backend.hset("key".to_string(), "field".to_string(), 1.into());
+        let get = Get {
+            key: "key".to_string(),
+        };
+        assert_eq!(
+            get.execute(&backend),
+            crate::SimpleError::new(
+                "WRONGTYPE Operation against a key holding the wrong kind of value"
+            )
+            .into()
+        );
+
+        // SET takes the key over whatever it held
+        let set = Set {
+            key: "key".to_string(),
+            value: 2.into(),
+        };
+        set.execute(&backend);
+        assert_eq!(backend.hget("key", "field"), None);
+        let get = Get {
+            key: "key".to_string(),
+        };
+        assert_eq!(get.execute(&backend), 2.into());
+
+        Ok(())
+    }
 }
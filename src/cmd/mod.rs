@@ -1,8 +1,26 @@
+mod acl;
+mod config;
+mod connection;
+mod db;
 mod hmap;
 mod map;
+mod pubsub;
+mod search;
+mod server;
+mod table;
+mod ts;
+mod vset;
 
-use crate::Backend;
-use crate::{RespArray, RespError, RespFrame, SimpleString};
+pub use table::{
+    lookup_command, resolve_command, CommandFlag, CommandSpec, KeySpec, COMMAND_TABLE,
+};
+
+use crate::network::ConnectionState;
+use crate::{
+    AggregateRequest, Aggregation, Backend, DuplicatePolicy, FilterExpr, IndexDefinition, KeyType,
+    LabelFilter, Query, SearchOptions, ShutdownOptions, TimeSeriesOptions, VAddOptions, VSimQuery,
+};
+use crate::{BulkString, ClientFilter, RespArray, RespError, RespFrame, SimpleError, SimpleString};
 use enum_dispatch::enum_dispatch;
 use lazy_static::lazy_static;
 use thiserror::Error;
@@ -11,22 +29,53 @@ lazy_static! {
     static ref RESP_OK: RespFrame = SimpleString::new("OK").into();
 }
 
+// the messages are what the client gets back, so they carry redis' error prefixes
 #[derive(Error, Debug)]
 pub enum CommandError {
-    #[error("Invalid command: {0}")]
+    #[error("ERR {0}")]
     InvalidCommand(String),
-    #[error("Invalid argument: {0}")]
+    #[error("ERR {0}")]
     InvalidArgument(String),
-    #[error("{0}")]
+    #[error("ERR wrong number of arguments for '{0}' command")]
+    WrongArity(String),
+    #[error("ERR unknown command '{name}', with args beginning with: {args}")]
+    UnknownCommand { name: String, args: String },
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+    #[error("CROSSSLOT Keys in request don't hash to the same slot")]
+    CrossSlot,
+    #[error("ERR {0}")]
     RespError(#[from] RespError),
 
-    #[error("from ParseIntError: {0}")]
+    #[error("ERR invalid UTF-8 argument: {0}")]
     Utf8Error(#[from] std::string::FromUtf8Error),
 }
 
+impl From<CommandError> for SimpleError {
+    fn from(e: CommandError) -> Self {
+        SimpleError::new(e.to_string())
+    }
+}
+
+impl From<CommandError> for RespFrame {
+    fn from(e: CommandError) -> Self {
+        SimpleError::from(e).into()
+    }
+}
+
 #[enum_dispatch]
 pub trait CommandExecutor {
     fn execute(self, backend: &Backend) -> RespFrame;
+
+    /// Run on behalf of a connected client. Only the connection commands look at `state`,
+    /// everything else just executes against the backend.
+    fn execute_for(self, backend: &Backend, state: &mut ConnectionState) -> RespFrame
+    where
+        Self: Sized,
+    {
+        let _ = state;
+        self.execute(backend)
+    }
 }
 
 #[enum_dispatch(CommandExecutor)]
@@ -38,7 +87,44 @@ pub enum Command {
     HSet(HSet),
     HGet(HGet),
     HGetAll(HGetAll),
-    Unrecognized(Unrecognized),
+    Hello(Hello),
+    Ping(Ping),
+    Echo(Echo),
+    Quit(Quit),
+    Reset(Reset),
+    Client(ClientCommand),
+    Select(Select),
+    Move(Move),
+    SwapDb(SwapDb),
+    FlushDb(FlushDb),
+    FlushAll(FlushAll),
+    DbSize(DbSize),
+    TsCreate(TsCreate),
+    TsAdd(TsAdd),
+    TsRange(TsRange),
+    TsMRange(TsMRange),
+    VAdd(VAdd),
+    VSim(VSim),
+    VRem(VRem),
+    VCard(VCard),
+    VEmb(VEmb),
+    FtCreate(FtCreate),
+    FtSearch(FtSearch),
+    FtAggregate(FtAggregate),
+    CommandQuery(CommandQuery),
+    Config(ConfigCommand),
+    Shutdown(Shutdown),
+    Auth(Auth),
+    Acl(AclCommand),
+    Subscribe(Subscribe),
+    Unsubscribe(Unsubscribe),
+    PSubscribe(PSubscribe),
+    PUnsubscribe(PUnsubscribe),
+    Publish(Publish),
+    SSubscribe(SSubscribe),
+    SUnsubscribe(SUnsubscribe),
+    SPublish(SPublish),
+    PubSub(PubSubCommand),
 }
 
 #[derive(Debug)]
@@ -68,11 +154,272 @@ pub struct HGet {
 #[derive(Debug)]
 pub struct HGetAll {
     key: String,
-    sort: bool,
 }
 
 #[derive(Debug)]
-pub struct Unrecognized;
+pub struct Hello {
+    protover: Option<i64>,
+    auth: Option<(String, String)>,
+    setname: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Auth {
+    username: Option<String>,
+    password: String,
+}
+
+#[derive(Debug)]
+pub struct Ping {
+    message: Option<BulkString>,
+}
+
+#[derive(Debug)]
+pub struct Echo {
+    message: BulkString,
+}
+
+#[derive(Debug)]
+pub struct Quit;
+
+#[derive(Debug)]
+pub struct Reset;
+
+/// CLIENT and its subcommands, answered from the connection and the client registry.
+#[derive(Debug)]
+pub enum ClientCommand {
+    SetName(Option<String>),
+    GetName,
+    Id,
+    Info,
+    List(ClientFilter),
+    /// The old `CLIENT KILL addr:port` form replies +OK instead of a count.
+    Kill {
+        filter: ClientFilter,
+        skip_me: bool,
+        legacy: bool,
+    },
+}
+
+#[derive(Debug)]
+pub struct Select {
+    index: usize,
+}
+
+#[derive(Debug)]
+pub struct Move {
+    key: String,
+    db: usize,
+}
+
+#[derive(Debug)]
+pub struct SwapDb {
+    first: usize,
+    second: usize,
+}
+
+#[derive(Debug)]
+pub struct FlushDb {
+    lazy: Option<bool>,
+}
+
+#[derive(Debug)]
+pub struct FlushAll {
+    lazy: Option<bool>,
+}
+
+#[derive(Debug)]
+pub struct DbSize;
+
+#[derive(Debug)]
+pub struct TsCreate {
+    key: String,
+    opts: TimeSeriesOptions,
+}
+
+#[derive(Debug)]
+pub struct TsAdd {
+    key: String,
+    timestamp: Option<i64>,
+    value: f64,
+    opts: TimeSeriesOptions,
+    /// ON_DUPLICATE, for this sample only; the key's own policy is left as it is.
+    on_duplicate: Option<DuplicatePolicy>,
+}
+
+#[derive(Debug)]
+pub struct TsRange {
+    key: String,
+    from: i64,
+    to: i64,
+    aggregation: Option<(Aggregation, u64)>,
+    count: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct TsMRange {
+    from: i64,
+    to: i64,
+    aggregation: Option<(Aggregation, u64)>,
+    count: Option<usize>,
+    with_labels: bool,
+    filters: Vec<LabelFilter>,
+}
+
+#[derive(Debug)]
+pub struct VAdd {
+    key: String,
+    element: String,
+    vector: Vec<f32>,
+    opts: VAddOptions,
+}
+
+#[derive(Debug)]
+pub struct VSim {
+    key: String,
+    query: VSimQuery,
+    with_scores: bool,
+    count: usize,
+    ef: Option<usize>,
+    filter: Option<FilterExpr>,
+    filter_ef: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct VRem {
+    key: String,
+    element: String,
+}
+
+#[derive(Debug)]
+pub struct VCard {
+    key: String,
+}
+
+#[derive(Debug)]
+pub struct VEmb {
+    key: String,
+    element: String,
+}
+
+#[derive(Debug)]
+pub struct FtCreate {
+    index: String,
+    def: IndexDefinition,
+}
+
+#[derive(Debug)]
+pub struct FtSearch {
+    index: String,
+    query: Query,
+    opts: SearchOptions,
+}
+
+#[derive(Debug)]
+pub struct FtAggregate {
+    index: String,
+    req: AggregateRequest,
+}
+
+/// COMMAND and its subcommands, answered from the command table.
+#[derive(Debug)]
+pub enum CommandQuery {
+    List,
+    Count,
+    Info(Vec<String>),
+    Docs(Vec<String>),
+    GetKeys(Vec<RespFrame>),
+}
+
+#[derive(Debug)]
+pub struct Shutdown {
+    abort: bool,
+    options: ShutdownOptions,
+}
+
+/// CONFIG and its subcommands, answered from the server's configuration.
+#[derive(Debug)]
+pub enum ConfigCommand {
+    Get(Vec<String>),
+    Set(Vec<(String, String)>),
+    ResetStat,
+    Rewrite,
+}
+
+/// ACL and its subcommands, answered from the server's users.
+#[derive(Debug)]
+pub enum AclCommand {
+    Cat(Option<String>),
+    DelUser(Vec<String>),
+    GetUser(String),
+    List,
+    Load,
+    /// The latest entries, or RESET with `None`.
+    Log(Option<usize>),
+    Save,
+    SetUser {
+        username: String,
+        rules: Vec<String>,
+    },
+    Users,
+    WhoAmI,
+}
+
+#[derive(Debug)]
+pub struct Subscribe {
+    channels: Vec<BulkString>,
+}
+
+/// Leaves every channel when none are given.
+#[derive(Debug)]
+pub struct Unsubscribe {
+    channels: Vec<BulkString>,
+}
+
+#[derive(Debug)]
+pub struct PSubscribe {
+    patterns: Vec<BulkString>,
+}
+
+/// Leaves every pattern when none are given.
+#[derive(Debug)]
+pub struct PUnsubscribe {
+    patterns: Vec<BulkString>,
+}
+
+#[derive(Debug)]
+pub struct Publish {
+    channel: BulkString,
+    message: BulkString,
+}
+
+/// The shard channels all hash to the same slot.
+#[derive(Debug)]
+pub struct SSubscribe {
+    channels: Vec<BulkString>,
+}
+
+/// Leaves every shard channel when none are given.
+#[derive(Debug)]
+pub struct SUnsubscribe {
+    channels: Vec<BulkString>,
+}
+
+#[derive(Debug)]
+pub struct SPublish {
+    channel: BulkString,
+    message: BulkString,
+}
+
+/// PUBSUB and its subcommands, answered from the broker.
+#[derive(Debug)]
+pub enum PubSubCommand {
+    Channels(Option<BulkString>),
+    NumPat,
+    NumSub(Vec<BulkString>),
+    ShardChannels(Option<BulkString>),
+    ShardNumSub(Vec<BulkString>),
+}
 
 impl TryFrom<RespFrame> for Command {
     type Error = CommandError;
@@ -89,42 +436,60 @@ impl TryFrom<RespFrame> for Command {
 impl TryFrom<RespArray> for Command {
     type Error = CommandError;
     fn try_from(v: RespArray) -> Result<Self, Self::Error> {
-        match v.first() {
-            Some(RespFrame::BulkString(ref cmd)) => match cmd.as_ref() {
-                b"get" => Ok(Get::try_from(v)?.into()),
-                b"set" => Ok(Set::try_from(v)?.into()),
-                b"hget" => Ok(HGet::try_from(v)?.into()),
-                b"hset" => Ok(HSet::try_from(v)?.into()),
-                b"hgetall" => Ok(HGetAll::try_from(v)?.into()),
-                _ => Ok(Unrecognized.into()),
-            },
-            _ => Err(CommandError::InvalidCommand(
-                "Command must have a BulkString as the first argument".to_string(),
-            )),
+        let spec = match v.first() {
+            Some(RespFrame::BulkString(ref cmd)) => lookup_command(cmd),
+            _ => {
+                return Err(CommandError::InvalidCommand(
+                    "Command must have a BulkString as the first argument".to_string(),
+                ))
+            }
+        };
+
+        match spec {
+            Some(spec) => (spec.parse)(v),
+            None => Err(unknown_command(&v)),
         }
     }
 }
 
-impl CommandExecutor for Unrecognized {
-    fn execute(self, _: &Backend) -> RespFrame {
-        RESP_OK.clone()
+fn unknown_command(arr: &RespArray) -> CommandError {
+    let name = |frame: &RespFrame| match frame {
+        RespFrame::BulkString(s) => String::from_utf8_lossy(s).into_owned(),
+        _ => String::new(),
+    };
+    // like redis, echo back only the start of each argument
+    let args = arr
+        .iter()
+        .skip(1)
+        .map(|arg| format!("'{}' ", name(arg).chars().take(128).collect::<String>()))
+        .collect();
+    CommandError::UnknownCommand {
+        name: name(&arr[0]),
+        args,
+    }
+}
+
+// a missing key is fine, typed commands create or skip it
+fn expect_type(backend: &Backend, key: &str, expected: KeyType) -> Result<(), CommandError> {
+    match backend.key_type(key) {
+        Some(found) if found != expected => Err(CommandError::WrongType),
+        _ => Ok(()),
     }
 }
 
-fn validator_command(
-    arr: &RespArray,
-    names: &[&'static str],
-    n_args: usize,
-) -> Result<(), CommandError> {
-    //test argument must have 2 elements
-    if arr.len() != n_args + names.len() {
-        return Err(CommandError::InvalidArgument(format!(
-            "{} command must have {} arguments",
-            names.join(" "),
-            n_args
-        )));
+// arity comes from the command table, `names` holds the command and any subcommand
+fn validator_command(arr: &RespArray, names: &[&'static str]) -> Result<(), CommandError> {
+    let spec = table::find_spec(names).ok_or_else(|| {
+        CommandError::InvalidCommand(format!("{} is not registered", names.join("|")))
+    })?;
+    if !spec.check_arity(arr.len()) {
+        return Err(CommandError::WrongArity(names.join("|")));
     }
 
+    validator_names(arr, names)
+}
+
+fn validator_names(arr: &RespArray, names: &[&'static str]) -> Result<(), CommandError> {
     for (i, name) in names.iter().enumerate() {
         //test if first element is a BulkString
         match arr[i] {
@@ -150,3 +515,16 @@ fn validator_command(
 fn extract_args(arr: RespArray, start: usize) -> Result<Vec<RespFrame>, CommandError> {
     Ok(arr.0.into_iter().skip(start).collect::<Vec<RespFrame>>())
 }
+
+fn extract_string(arg: Option<RespFrame>, name: &str) -> Result<String, CommandError> {
+    match arg {
+        Some(RespFrame::BulkString(s)) => Ok(String::from_utf8(s.to_vec())?),
+        _ => Err(CommandError::InvalidArgument(format!("Invalid {}", name))),
+    }
+}
+
+fn parse_arg<T: std::str::FromStr>(arg: Option<RespFrame>, name: &str) -> Result<T, CommandError> {
+    extract_string(arg, name)?
+        .parse()
+        .map_err(|_| CommandError::InvalidArgument(format!("Invalid {}", name)))
+}
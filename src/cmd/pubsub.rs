@@ -0,0 +1,601 @@
+use std::collections::BTreeSet;
+
+use crate::cmd::{
+    CommandError, PSubscribe, PUnsubscribe, PubSubCommand, Publish, SPublish, SSubscribe,
+    SUnsubscribe, Subscribe, Unsubscribe,
+};
+use crate::network::ConnectionState;
+use crate::{
+    key_hash_slot, Backend, BulkString, MessageSender, PubSub, RespArray, RespFrame, RespNull,
+    RespPush, Subscriptions,
+};
+
+use super::{extract_args, extract_string, validator_command, CommandExecutor};
+
+// the (un)subscribe commands only differ in where the subscription is kept
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    Channel,
+    Pattern,
+    ShardChannel,
+}
+
+impl Target {
+    fn subscribed(self, subscriptions: &mut Subscriptions) -> &mut BTreeSet<Vec<u8>> {
+        match self {
+            Target::Channel => &mut subscriptions.channels,
+            Target::Pattern => &mut subscriptions.patterns,
+            Target::ShardChannel => &mut subscriptions.shard_channels,
+        }
+    }
+
+    // what the confirmations count, shard channels are counted apart from the rest
+    fn count(self, subscriptions: &Subscriptions) -> usize {
+        match self {
+            Target::ShardChannel => subscriptions.shard_channels.len(),
+            _ => subscriptions.count(),
+        }
+    }
+
+    fn join(self, pubsub: &PubSub, name: &[u8], id: u64, sender: MessageSender) {
+        match self {
+            Target::Channel => pubsub.subscribe(name, id, sender),
+            Target::Pattern => pubsub.psubscribe(name, id, sender),
+            Target::ShardChannel => pubsub.ssubscribe(name, id, sender),
+        }
+    }
+
+    fn leave(self, pubsub: &PubSub, name: &[u8], id: u64) {
+        match self {
+            Target::Channel => pubsub.unsubscribe(name, id),
+            Target::Pattern => pubsub.punsubscribe(name, id),
+            Target::ShardChannel => pubsub.sunsubscribe(name, id),
+        }
+    }
+}
+
+impl CommandExecutor for Subscribe {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_for(backend, &mut ConnectionState::default())
+    }
+
+    fn execute_for(self, backend: &Backend, state: &mut ConnectionState) -> RespFrame {
+        subscribe(backend, state, Target::Channel, "subscribe", self.channels)
+    }
+}
+
+impl CommandExecutor for PSubscribe {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_for(backend, &mut ConnectionState::default())
+    }
+
+    fn execute_for(self, backend: &Backend, state: &mut ConnectionState) -> RespFrame {
+        subscribe(backend, state, Target::Pattern, "psubscribe", self.patterns)
+    }
+}
+
+impl CommandExecutor for Unsubscribe {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_for(backend, &mut ConnectionState::default())
+    }
+
+    fn execute_for(self, backend: &Backend, state: &mut ConnectionState) -> RespFrame {
+        unsubscribe(
+            backend,
+            state,
+            Target::Channel,
+            "unsubscribe",
+            self.channels,
+        )
+    }
+}
+
+impl CommandExecutor for PUnsubscribe {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_for(backend, &mut ConnectionState::default())
+    }
+
+    fn execute_for(self, backend: &Backend, state: &mut ConnectionState) -> RespFrame {
+        unsubscribe(
+            backend,
+            state,
+            Target::Pattern,
+            "punsubscribe",
+            self.patterns,
+        )
+    }
+}
+
+impl CommandExecutor for SSubscribe {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_for(backend, &mut ConnectionState::default())
+    }
+
+    fn execute_for(self, backend: &Backend, state: &mut ConnectionState) -> RespFrame {
+        subscribe(
+            backend,
+            state,
+            Target::ShardChannel,
+            "ssubscribe",
+            self.channels,
+        )
+    }
+}
+
+impl CommandExecutor for SUnsubscribe {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_for(backend, &mut ConnectionState::default())
+    }
+
+    fn execute_for(self, backend: &Backend, state: &mut ConnectionState) -> RespFrame {
+        unsubscribe(
+            backend,
+            state,
+            Target::ShardChannel,
+            "sunsubscribe",
+            self.channels,
+        )
+    }
+}
+
+impl CommandExecutor for SPublish {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let receivers = backend.pubsub().spublish(&self.channel, &self.message);
+        RespFrame::Integer(receivers as i64)
+    }
+}
+
+impl CommandExecutor for Publish {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let receivers = backend.pubsub().publish(&self.channel, &self.message);
+        RespFrame::Integer(receivers as i64)
+    }
+}
+
+impl CommandExecutor for PubSubCommand {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let pubsub = backend.pubsub();
+        match self {
+            PubSubCommand::Channels(pattern) => {
+                names(pubsub.channels(pattern.as_ref().map(|p| p.as_ref())))
+            }
+            PubSubCommand::NumPat => RespFrame::Integer(pubsub.numpat() as i64),
+            PubSubCommand::NumSub(channels) => numsub(channels, |c| pubsub.numsub(c)),
+            PubSubCommand::ShardChannels(pattern) => {
+                names(pubsub.shard_channels(pattern.as_ref().map(|p| p.as_ref())))
+            }
+            PubSubCommand::ShardNumSub(channels) => numsub(channels, |c| pubsub.shard_numsub(c)),
+        }
+    }
+}
+
+fn names(channels: Vec<Vec<u8>>) -> RespFrame {
+    RespArray::new(
+        channels
+            .into_iter()
+            .map(|channel| BulkString::new(channel).into())
+            .collect::<Vec<RespFrame>>(),
+    )
+    .into()
+}
+
+// each channel followed by its subscriber count, flat as in redis
+fn numsub(channels: Vec<BulkString>, count: impl Fn(&[u8]) -> usize) -> RespFrame {
+    RespArray::new(
+        channels
+            .into_iter()
+            .flat_map(|channel| {
+                let count = count(&channel) as i64;
+                [channel.into(), RespFrame::Integer(count)]
+            })
+            .collect::<Vec<RespFrame>>(),
+    )
+    .into()
+}
+
+// one confirmation per channel, each with how many subscriptions the client now holds; they
+// go out as the connection's replies, so what is returned here is never sent
+fn subscribe(
+    backend: &Backend,
+    state: &mut ConnectionState,
+    target: Target,
+    kind: &'static str,
+    names: Vec<BulkString>,
+) -> RespFrame {
+    let id = state.id;
+    for name in names {
+        let subscriptions = &mut state.subscriptions;
+        if target.subscribed(subscriptions).insert(name.to_vec()) {
+            if let Some(sender) = subscriptions.sender.clone() {
+                target.join(backend.pubsub(), &name, id, sender);
+            }
+        }
+        let count = target.count(&state.subscriptions);
+        state.replies.push(confirmation(kind, name.into(), count));
+    }
+    state.flags.pubsub = !state.subscriptions.is_empty();
+    RespFrame::Null(RespNull)
+}
+
+// without names everything is left, and a client with nothing to leave still gets an answer
+fn unsubscribe(
+    backend: &Backend,
+    state: &mut ConnectionState,
+    target: Target,
+    kind: &'static str,
+    names: Vec<BulkString>,
+) -> RespFrame {
+    let id = state.id;
+    let names = match names.is_empty() {
+        true => target
+            .subscribed(&mut state.subscriptions)
+            .iter()
+            .cloned()
+            .collect(),
+        false => names.iter().map(|name| name.to_vec()).collect::<Vec<_>>(),
+    };
+    if names.is_empty() {
+        let count = target.count(&state.subscriptions);
+        state
+            .replies
+            .push(confirmation(kind, RespNull.into(), count));
+    }
+    for name in names {
+        if target.subscribed(&mut state.subscriptions).remove(&name) {
+            target.leave(backend.pubsub(), &name, id);
+        }
+        let count = target.count(&state.subscriptions);
+        state
+            .replies
+            .push(confirmation(kind, BulkString::new(name).into(), count));
+    }
+    state.flags.pubsub = !state.subscriptions.is_empty();
+    RespFrame::Null(RespNull)
+}
+
+fn confirmation(kind: &'static str, subject: RespFrame, count: usize) -> RespFrame {
+    RespPush::new(vec![
+        BulkString::from(kind).into(),
+        subject,
+        RespFrame::Integer(count as i64),
+    ])
+    .into()
+}
+
+fn bulk_args(arr: RespArray, start: usize, name: &str) -> Result<Vec<BulkString>, CommandError> {
+    extract_args(arr, start)?
+        .into_iter()
+        .map(|arg| match arg {
+            RespFrame::BulkString(arg) => Ok(arg),
+            _ => Err(CommandError::InvalidArgument(format!("Invalid {}", name))),
+        })
+        .collect()
+}
+
+// shard channels are bound to slots like keys, so one command can only name a single slot
+fn same_slot(channels: &[BulkString]) -> Result<(), CommandError> {
+    let mut slots = channels.iter().map(|channel| key_hash_slot(channel));
+    match slots.next() {
+        Some(first) if slots.any(|slot| slot != first) => Err(CommandError::CrossSlot),
+        _ => Ok(()),
+    }
+}
+
+impl TryFrom<RespArray> for Subscribe {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["subscribe"])?;
+        Ok(Subscribe {
+            channels: bulk_args(arr, 1, "channel")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Unsubscribe {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["unsubscribe"])?;
+        Ok(Unsubscribe {
+            channels: bulk_args(arr, 1, "channel")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for PSubscribe {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["psubscribe"])?;
+        Ok(PSubscribe {
+            patterns: bulk_args(arr, 1, "pattern")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for PUnsubscribe {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["punsubscribe"])?;
+        Ok(PUnsubscribe {
+            patterns: bulk_args(arr, 1, "pattern")?,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for Publish {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["publish"])?;
+        let mut args = bulk_args(arr, 1, "argument")?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(channel), Some(message)) => Ok(Publish { channel, message }),
+            _ => Err(CommandError::WrongArity("publish".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for SSubscribe {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["ssubscribe"])?;
+        let channels = bulk_args(arr, 1, "channel")?;
+        same_slot(&channels)?;
+        Ok(SSubscribe { channels })
+    }
+}
+
+impl TryFrom<RespArray> for SUnsubscribe {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["sunsubscribe"])?;
+        let channels = bulk_args(arr, 1, "channel")?;
+        same_slot(&channels)?;
+        Ok(SUnsubscribe { channels })
+    }
+}
+
+impl TryFrom<RespArray> for SPublish {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["spublish"])?;
+        let mut args = bulk_args(arr, 1, "argument")?.into_iter();
+        match (args.next(), args.next()) {
+            (Some(channel), Some(message)) => Ok(SPublish { channel, message }),
+            _ => Err(CommandError::WrongArity("spublish".to_string())),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for PubSubCommand {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["pubsub"])?;
+        let sub = extract_string(arr.get(1).cloned(), "subcommand")?.to_ascii_lowercase();
+        match sub.as_str() {
+            "channels" => {
+                validator_command(&arr, &["pubsub", "channels"])?;
+                if arr.len() > 3 {
+                    return Err(CommandError::WrongArity("pubsub|channels".to_string()));
+                }
+                let pattern = bulk_args(arr, 2, "pattern")?.into_iter().next();
+                Ok(PubSubCommand::Channels(pattern))
+            }
+            "numpat" => {
+                validator_command(&arr, &["pubsub", "numpat"])?;
+                Ok(PubSubCommand::NumPat)
+            }
+            "numsub" => {
+                validator_command(&arr, &["pubsub", "numsub"])?;
+                Ok(PubSubCommand::NumSub(bulk_args(arr, 2, "channel")?))
+            }
+            "shardchannels" => {
+                validator_command(&arr, &["pubsub", "shardchannels"])?;
+                if arr.len() > 3 {
+                    return Err(CommandError::WrongArity("pubsub|shardchannels".to_string()));
+                }
+                let pattern = bulk_args(arr, 2, "pattern")?.into_iter().next();
+                Ok(PubSubCommand::ShardChannels(pattern))
+            }
+            "shardnumsub" => {
+                validator_command(&arr, &["pubsub", "shardnumsub"])?;
+                Ok(PubSubCommand::ShardNumSub(bulk_args(arr, 2, "channel")?))
+            }
+            _ => Err(CommandError::InvalidCommand(format!(
+                "unknown subcommand '{}'. Try PUBSUB HELP.",
+                sub
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::Command;
+    use crate::{RespVersion, SimpleError};
+    use tokio::sync::mpsc;
+
+    fn run(backend: &Backend, state: &mut ConnectionState, args: &[&str]) -> RespFrame {
+        let arr = RespArray::new(
+            args.iter()
+                .map(|a| BulkString::from(*a).into())
+                .collect::<Vec<RespFrame>>(),
+        );
+        match Command::try_from(arr) {
+            Ok(cmd) => cmd.execute_for(backend, state),
+            Err(e) => e.into(),
+        }
+    }
+
+    fn push(parts: Vec<RespFrame>) -> RespFrame {
+        RespPush::new(parts).into()
+    }
+
+    fn bulk(s: &str) -> RespFrame {
+        BulkString::from(s).into()
+    }
+
+    #[test]
+    fn test_subscribe_and_unsubscribe() {
+        let backend = Backend::new();
+        let (sender, mut messages) = mpsc::unbounded_channel();
+        let state = &mut ConnectionState::default();
+        state.id = 9;
+        state.subscriptions.sender = Some(sender);
+
+        run(&backend, state, &["subscribe", "a", "b", "a"]);
+        assert_eq!(
+            std::mem::take(&mut state.replies),
+            vec![
+                push(vec![bulk("subscribe"), bulk("a"), 1.into()]),
+                push(vec![bulk("subscribe"), bulk("b"), 2.into()]),
+                push(vec![bulk("subscribe"), bulk("a"), 2.into()]),
+            ]
+        );
+        run(&backend, state, &["psubscribe", "b*"]);
+        state.replies.clear();
+        assert!(state.flags.pubsub);
+
+        assert_eq!(
+            run(&backend, state, &["publish", "b", "hi"]),
+            RespFrame::Integer(2)
+        );
+        assert_eq!(
+            messages.try_recv().unwrap(),
+            push(vec![bulk("message"), bulk("b"), bulk("hi")])
+        );
+        assert_eq!(
+            messages.try_recv().unwrap(),
+            push(vec![bulk("pmessage"), bulk("b*"), bulk("b"), bulk("hi")])
+        );
+        assert_eq!(
+            run(&backend, state, &["pubsub", "channels"]),
+            RespArray::new(vec![bulk("a"), bulk("b")]).into()
+        );
+        assert_eq!(
+            run(&backend, state, &["pubsub", "numsub", "a", "nope"]),
+            RespArray::new(vec![bulk("a"), 1.into(), bulk("nope"), 0.into()]).into()
+        );
+        assert_eq!(
+            run(&backend, state, &["pubsub", "numpat"]),
+            RespFrame::Integer(1)
+        );
+
+        run(&backend, state, &["unsubscribe"]);
+        assert_eq!(
+            std::mem::take(&mut state.replies),
+            vec![
+                push(vec![bulk("unsubscribe"), bulk("a"), 2.into()]),
+                push(vec![bulk("unsubscribe"), bulk("b"), 1.into()]),
+            ]
+        );
+        run(&backend, state, &["punsubscribe", "b*"]);
+        run(&backend, state, &["punsubscribe"]);
+        assert_eq!(
+            state.replies.pop(),
+            Some(push(vec![bulk("punsubscribe"), RespNull.into(), 0.into()]))
+        );
+        assert!(!state.flags.pubsub);
+        assert_eq!(
+            run(&backend, state, &["publish", "b", "hi"]),
+            RespFrame::Integer(0)
+        );
+    }
+
+    #[test]
+    fn test_shard_subscriptions() {
+        let backend = Backend::new();
+        let (sender, mut messages) = mpsc::unbounded_channel();
+        let state = &mut ConnectionState::default();
+        state.subscriptions.sender = Some(sender);
+
+        run(&backend, state, &["subscribe", "a"]);
+        run(&backend, state, &["ssubscribe", "{user1}.a", "{user1}.b"]);
+        assert_eq!(
+            std::mem::take(&mut state.replies),
+            vec![
+                push(vec![bulk("subscribe"), bulk("a"), 1.into()]),
+                push(vec![bulk("ssubscribe"), bulk("{user1}.a"), 1.into()]),
+                push(vec![bulk("ssubscribe"), bulk("{user1}.b"), 2.into()]),
+            ]
+        );
+        assert_eq!(
+            run(&backend, state, &["ssubscribe", "a", "b"]),
+            SimpleError::new("CROSSSLOT Keys in request don't hash to the same slot").into()
+        );
+
+        assert_eq!(
+            run(&backend, state, &["spublish", "{user1}.a", "hi"]),
+            RespFrame::Integer(1)
+        );
+        assert_eq!(
+            messages.try_recv().unwrap(),
+            push(vec![bulk("smessage"), bulk("{user1}.a"), bulk("hi")])
+        );
+        // the global and shard namespaces don't mix
+        assert_eq!(
+            run(&backend, state, &["spublish", "a", "hi"]),
+            RespFrame::Integer(0)
+        );
+        assert_eq!(
+            run(&backend, state, &["pubsub", "shardchannels", "*.a"]),
+            RespArray::new(vec![bulk("{user1}.a")]).into()
+        );
+        assert_eq!(
+            run(
+                &backend,
+                state,
+                &["pubsub", "shardnumsub", "{user1}.b", "a"]
+            ),
+            RespArray::new(vec![bulk("{user1}.b"), 1.into(), bulk("a"), 0.into()]).into()
+        );
+
+        run(&backend, state, &["sunsubscribe"]);
+        assert_eq!(
+            std::mem::take(&mut state.replies),
+            vec![
+                push(vec![bulk("sunsubscribe"), bulk("{user1}.a"), 1.into()]),
+                push(vec![bulk("sunsubscribe"), bulk("{user1}.b"), 0.into()]),
+            ]
+        );
+        assert!(state.flags.pubsub);
+        run(&backend, state, &["unsubscribe"]);
+        assert!(!state.flags.pubsub);
+    }
+
+    #[test]
+    fn test_ping_and_reset_while_subscribed() {
+        let backend = Backend::new();
+        let (sender, _messages) = mpsc::unbounded_channel();
+        let state = &mut ConnectionState::default();
+        state.subscriptions.sender = Some(sender);
+        run(&backend, state, &["subscribe", "a"]);
+
+        assert_eq!(
+            run(&backend, state, &["ping"]),
+            RespArray::new(vec![bulk("pong"), bulk("")]).into()
+        );
+        state.protocol = RespVersion::Resp3;
+        assert_eq!(
+            run(&backend, state, &["ping"]),
+            crate::SimpleString::new("PONG").into()
+        );
+
+        run(&backend, state, &["reset"]);
+        assert!(!state.flags.pubsub);
+        assert_eq!(state.subscriptions.count(), 0);
+        assert!(backend.pubsub().channels(None).is_empty());
+        assert_eq!(
+            run(&backend, state, &["pubsub", "nope"]),
+            SimpleError::new("ERR unknown subcommand 'nope'. Try PUBSUB HELP.").into()
+        );
+    }
+}
@@ -0,0 +1,609 @@
+use crate::cmd::{CommandError, FtAggregate, FtCreate, FtSearch, RESP_OK};
+use crate::{
+    AggregateRequest, BulkString, Document, FieldType, IndexDefinition, Query, Reducer,
+    ReducerKind, RespArray, RespFrame, SchemaField, SearchOptions, SimpleError,
+};
+
+use super::{extract_args, extract_string, parse_arg, validator_command, CommandExecutor};
+
+const DEFAULT_LIMIT: usize = 10;
+
+impl CommandExecutor for FtCreate {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        match backend.ft_create(self.index, self.def) {
+            Ok(_) => RESP_OK.clone(),
+            Err(e) => SimpleError::new(e.to_string()).into(),
+        }
+    }
+}
+
+impl CommandExecutor for FtSearch {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        match backend.ft_search(&self.index, &self.query, &self.opts) {
+            Ok((total, docs)) => {
+                let mut ret = vec![(total as i64).into()];
+                for (key, doc) in docs {
+                    ret.push(BulkString::new(key).into());
+                    if let Some(doc) = doc {
+                        ret.push(document_to_frame(doc));
+                    }
+                }
+                RespArray::new(ret).into()
+            }
+            Err(e) => SimpleError::new(e.to_string()).into(),
+        }
+    }
+}
+
+impl CommandExecutor for FtAggregate {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        match backend.ft_aggregate(&self.index, &self.req) {
+            Ok(rows) => {
+                let mut ret = vec![(rows.len() as i64).into()];
+                ret.extend(rows.into_iter().map(document_to_frame));
+                RespArray::new(ret).into()
+            }
+            Err(e) => SimpleError::new(e.to_string()).into(),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for FtCreate {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["ft.create"])?;
+
+        let mut args = extract_args(arr, 1)?.into_iter();
+        let index = extract_string(args.next(), "index")?;
+        let mut def = IndexDefinition::default();
+
+        loop {
+            match extract_string(args.next(), "SCHEMA")?
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "on" => {
+                    let on = extract_string(args.next(), "ON")?;
+                    if !on.eq_ignore_ascii_case("hash") {
+                        return Err(CommandError::InvalidArgument(format!(
+                            "Unsupported index type: {}",
+                            on
+                        )));
+                    }
+                }
+                "prefix" => {
+                    let n: usize = parse_arg(args.next(), "PREFIX count")?;
+                    for _ in 0..n {
+                        def.prefixes.push(extract_string(args.next(), "prefix")?);
+                    }
+                }
+                "schema" => break,
+                opt => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Unknown argument: {}",
+                        opt
+                    )))
+                }
+            }
+        }
+
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            let field = extract_string(Some(arg), "field")?;
+            let mut attribute = field.clone();
+            let mut kind = extract_string(args.next(), "field type")?;
+            if kind.eq_ignore_ascii_case("as") {
+                attribute = extract_string(args.next(), "alias")?;
+                kind = extract_string(args.next(), "field type")?;
+            }
+
+            let mut kind = match kind.to_ascii_lowercase().as_str() {
+                "text" => FieldType::Text,
+                "numeric" => FieldType::Numeric,
+                "tag" => FieldType::Tag { separator: ',' },
+                other => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Unsupported field type: {}",
+                        other
+                    )))
+                }
+            };
+
+            let mut sortable = false;
+            while let Some(RespFrame::BulkString(opt)) = args.peek() {
+                if opt.eq_ignore_ascii_case(b"sortable") {
+                    sortable = true;
+                } else if opt.eq_ignore_ascii_case(b"separator") {
+                    args.next();
+                    let sep = extract_string(args.peek().cloned(), "SEPARATOR")?;
+                    match (&mut kind, sep.chars().next()) {
+                        (FieldType::Tag { separator }, Some(c)) if sep.len() == 1 => *separator = c,
+                        _ => {
+                            return Err(CommandError::InvalidArgument(
+                                "Invalid SEPARATOR".to_string(),
+                            ))
+                        }
+                    }
+                } else {
+                    break;
+                }
+                args.next();
+            }
+
+            def.schema.push(SchemaField {
+                field,
+                attribute,
+                kind,
+                sortable,
+            });
+        }
+
+        if def.schema.is_empty() {
+            return Err(CommandError::InvalidArgument(
+                "SCHEMA must contain at least one field".to_string(),
+            ));
+        }
+
+        Ok(FtCreate { index, def })
+    }
+}
+
+impl TryFrom<RespArray> for FtSearch {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["ft.search"])?;
+
+        let mut args = extract_args(arr, 1)?.into_iter().peekable();
+        let index = extract_string(args.next(), "index")?;
+        let query = parse_query(args.next())?;
+
+        let mut opts = SearchOptions {
+            no_content: false,
+            return_fields: None,
+            sort_by: None,
+            offset: 0,
+            num: DEFAULT_LIMIT,
+        };
+
+        while let Some(arg) = args.next() {
+            match extract_string(Some(arg), "option")?
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "nocontent" => opts.no_content = true,
+                "return" => {
+                    let n: usize = parse_arg(args.next(), "RETURN count")?;
+                    let fields = (0..n)
+                        .map(|_| extract_string(args.next(), "RETURN field"))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    opts.return_fields = Some(fields);
+                }
+                "sortby" => {
+                    let field = extract_string(args.next(), "SORTBY field")?;
+                    let asc = match args.peek() {
+                        Some(RespFrame::BulkString(s)) if s.eq_ignore_ascii_case(b"desc") => {
+                            args.next();
+                            false
+                        }
+                        Some(RespFrame::BulkString(s)) if s.eq_ignore_ascii_case(b"asc") => {
+                            args.next();
+                            true
+                        }
+                        _ => true,
+                    };
+                    opts.sort_by = Some((field, asc));
+                }
+                "limit" => {
+                    opts.offset = parse_arg(args.next(), "LIMIT offset")?;
+                    opts.num = parse_arg(args.next(), "LIMIT num")?;
+                }
+                opt => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Unknown argument: {}",
+                        opt
+                    )))
+                }
+            }
+        }
+
+        Ok(FtSearch { index, query, opts })
+    }
+}
+
+impl TryFrom<RespArray> for FtAggregate {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["ft.aggregate"])?;
+
+        let mut args = extract_args(arr, 1)?.into_iter().peekable();
+        let index = extract_string(args.next(), "index")?;
+        let query = parse_query(args.next())?;
+
+        let mut req = AggregateRequest {
+            query,
+            load: vec![],
+            group_by: None,
+            sort_by: vec![],
+            limit: None,
+        };
+
+        while let Some(arg) = args.next() {
+            match extract_string(Some(arg), "option")?
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "load" => {
+                    let n: usize = parse_arg(args.next(), "LOAD count")?;
+                    for _ in 0..n {
+                        req.load.push(parse_property(args.next())?);
+                    }
+                }
+                "groupby" => {
+                    if req.group_by.is_some() {
+                        return Err(CommandError::InvalidArgument(
+                            "Only a single GROUPBY step is supported".to_string(),
+                        ));
+                    }
+                    let n: usize = parse_arg(args.next(), "GROUPBY count")?;
+                    let fields = (0..n)
+                        .map(|_| parse_property(args.next()))
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    let mut reducers = vec![];
+                    while matches!(args.peek(), Some(RespFrame::BulkString(s)) if s.eq_ignore_ascii_case(b"reduce"))
+                    {
+                        args.next();
+                        reducers.push(parse_reducer(&mut args)?);
+                    }
+                    req.group_by = Some((fields, reducers));
+                }
+                "sortby" => {
+                    let n: usize = parse_arg(args.next(), "SORTBY count")?;
+                    let mut consumed = 0;
+                    while consumed < n {
+                        let field = parse_property(args.next())?;
+                        consumed += 1;
+                        let mut asc = true;
+                        if consumed < n {
+                            if let Some(RespFrame::BulkString(s)) = args.peek() {
+                                if s.eq_ignore_ascii_case(b"asc") || s.eq_ignore_ascii_case(b"desc")
+                                {
+                                    asc = s.eq_ignore_ascii_case(b"asc");
+                                    args.next();
+                                    consumed += 1;
+                                }
+                            }
+                        }
+                        req.sort_by.push((field, asc));
+                    }
+                }
+                "limit" => {
+                    let offset = parse_arg(args.next(), "LIMIT offset")?;
+                    let num = parse_arg(args.next(), "LIMIT num")?;
+                    req.limit = Some((offset, num));
+                }
+                opt => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Unknown argument: {}",
+                        opt
+                    )))
+                }
+            }
+        }
+
+        Ok(FtAggregate { index, req })
+    }
+}
+
+// REDUCE <function> <nargs> <arg>... [AS <name>]
+fn parse_reducer(
+    args: &mut std::iter::Peekable<impl Iterator<Item = RespFrame>>,
+) -> Result<Reducer, CommandError> {
+    let name = extract_string(args.next(), "reducer")?.to_ascii_lowercase();
+    let n: usize = parse_arg(args.next(), "reducer nargs")?;
+    let mut params = (0..n)
+        .map(|_| parse_property(args.next()))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter();
+
+    let mut field = || {
+        params
+            .next()
+            .ok_or_else(|| CommandError::InvalidArgument(format!("{} requires a property", name)))
+    };
+    let kind = match name.as_str() {
+        "count" => ReducerKind::Count,
+        "count_distinct" => ReducerKind::CountDistinct(field()?),
+        "sum" => ReducerKind::Sum(field()?),
+        "avg" => ReducerKind::Avg(field()?),
+        "min" => ReducerKind::Min(field()?),
+        "max" => ReducerKind::Max(field()?),
+        _ => {
+            return Err(CommandError::InvalidArgument(format!(
+                "Unknown reducer: {}",
+                name
+            )))
+        }
+    };
+
+    let alias = match args.peek() {
+        Some(RespFrame::BulkString(s)) if s.eq_ignore_ascii_case(b"as") => {
+            args.next();
+            Some(extract_string(args.next(), "alias")?)
+        }
+        _ => None,
+    };
+
+    Ok(Reducer { kind, alias })
+}
+
+fn parse_query(arg: Option<RespFrame>) -> Result<Query, CommandError> {
+    let query = extract_string(arg, "query")?;
+    Query::parse(&query).map_err(|e| CommandError::InvalidArgument(format!("Syntax error: {}", e)))
+}
+
+fn parse_property(arg: Option<RespFrame>) -> Result<String, CommandError> {
+    let prop = extract_string(arg, "property")?;
+    Ok(prop.strip_prefix('@').unwrap_or(&prop).to_string())
+}
+
+fn document_to_frame(doc: Document) -> RespFrame {
+    let ret = doc
+        .into_iter()
+        .flat_map(|(k, v)| [BulkString::new(k).into(), BulkString::new(v).into()])
+        .collect::<Vec<RespFrame>>();
+    RespArray::new(ret).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn frames(args: &[&str]) -> RespArray {
+        RespArray::new(
+            args.iter()
+                .map(|s| BulkString::from(*s).into())
+                .collect::<Vec<RespFrame>>(),
+        )
+    }
+
+    fn hset(backend: &crate::Backend, key: &str, fields: &[(&str, &str)]) {
+        for (f, v) in fields {
+            backend.hset(key.to_string(), f.to_string(), BulkString::from(*v).into());
+        }
+    }
+
+    fn search(backend: &crate::Backend, args: &[&str]) -> Result<RespFrame> {
+        let cmd = FtSearch::try_from(frames(args))?;
+        Ok(cmd.execute(backend))
+    }
+
+    #[test]
+    fn test_ft_create_try_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*13\r\n$9\r\nft.create\r\n$3\r\nidx\r\n$2\r\nON\r\n$4\r\nHASH\r\n$6\r\nPREFIX\r\n$1\r\n1\r\n$5\r\nbook:\r\n$6\r\nSCHEMA\r\n$5\r\ntitle\r\n$4\r\nTEXT\r\n$4\r\nyear\r\n$7\r\nNUMERIC\r\n$8\r\nSORTABLE\r\n");
+
+        let arr = RespArray::decode(&mut buf)?;
+        let cmd = FtCreate::try_from(arr)?;
+        assert_eq!(cmd.index, "idx");
+        assert_eq!(cmd.def.prefixes, vec!["book:".to_string()]);
+        assert_eq!(cmd.def.schema.len(), 2);
+        assert_eq!(cmd.def.schema[1].kind, FieldType::Numeric);
+        assert!(cmd.def.schema[1].sortable);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ft_search_commands() -> Result<()> {
+        let backend = crate::Backend::new();
+        hset(
+            &backend,
+            "book:1",
+            &[
+                ("title", "The Rust Book"),
+                ("year", "2018"),
+                ("tags", "rust,programming"),
+            ],
+        );
+        hset(
+            &backend,
+            "other:1",
+            &[("title", "Rust in Action"), ("year", "2021")],
+        );
+
+        let cmd = FtCreate::try_from(frames(&[
+            "ft.create",
+            "idx",
+            "ON",
+            "HASH",
+            "PREFIX",
+            "1",
+            "book:",
+            "SCHEMA",
+            "title",
+            "TEXT",
+            "year",
+            "NUMERIC",
+            "SORTABLE",
+            "tags",
+            "TAG",
+        ]))?;
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+        let dup = FtCreate::try_from(frames(&["ft.create", "idx", "SCHEMA", "title", "TEXT"]))?;
+        assert_eq!(
+            dup.execute(&backend),
+            SimpleError::new("Index already exists").into()
+        );
+
+        hset(
+            &backend,
+            "book:2",
+            &[
+                ("title", "Programming Rust"),
+                ("year", "2021"),
+                ("tags", "rust"),
+            ],
+        );
+        hset(
+            &backend,
+            "book:3",
+            &[("title", "Dune"), ("year", "1965"), ("tags", "scifi")],
+        );
+
+        let ret = search(&backend, &["ft.search", "idx", "rust", "NOCONTENT"])?;
+        let expected = RespArray::new([2.into(), b"book:1".into(), b"book:2".into()]);
+        assert_eq!(ret, expected.into());
+
+        let ret = search(
+            &backend,
+            &[
+                "ft.search",
+                "idx",
+                "@year:[2000 +inf] -@tags:{programming}",
+                "RETURN",
+                "1",
+                "title",
+            ],
+        )?;
+        let expected = RespArray::new([
+            1.into(),
+            b"book:2".into(),
+            RespArray::new([b"title".into(), b"Programming Rust".into()]).into(),
+        ]);
+        assert_eq!(ret, expected.into());
+
+        let ret = search(
+            &backend,
+            &[
+                "ft.search",
+                "idx",
+                "dune | prog*",
+                "NOCONTENT",
+                "SORTBY",
+                "year",
+                "DESC",
+                "LIMIT",
+                "0",
+                "2",
+            ],
+        )?;
+        let expected = RespArray::new([2.into(), b"book:2".into(), b"book:3".into()]);
+        assert_eq!(ret, expected.into());
+
+        // updates replace the previous postings
+        hset(&backend, "book:3", &[("title", "Dune Messiah")]);
+        let ret = search(
+            &backend,
+            &["ft.search", "idx", "\"dune messiah\"", "NOCONTENT"],
+        )?;
+        assert_eq!(ret, RespArray::new([1.into(), b"book:3".into()]).into());
+
+        // NaN is refused, and bounds are ordered the same way the postings are
+        let err = search(&backend, &["ft.search", "idx", "@year:[nan 5]"]).unwrap_err();
+        assert!(err.to_string().starts_with("ERR Syntax error"), "{}", err);
+        let ret = search(&backend, &["ft.search", "idx", "@year:[0 -0]", "NOCONTENT"])?;
+        assert_eq!(ret, RespArray::new([0.into()]).into());
+
+        // stopwords aren't indexed, so a query leaves them out too
+        let ret = search(&backend, &["ft.search", "idx", "the dune", "NOCONTENT"])?;
+        assert_eq!(ret, RespArray::new([1.into(), b"book:3".into()]).into());
+        let ret = search(&backend, &["ft.search", "idx", "dune | the", "NOCONTENT"])?;
+        assert_eq!(ret, RespArray::new([1.into(), b"book:3".into()]).into());
+        let ret = search(&backend, &["ft.search", "idx", "the", "NOCONTENT"])?;
+        assert_eq!(ret, RespArray::new([0.into()]).into());
+
+        // too deep a query is refused instead of overflowing the stack
+        for open in ["(", "-", "@title:"] {
+            let query = format!("{}rust", open.repeat(100_000));
+            let err = search(&backend, &["ft.search", "idx", &query]).unwrap_err();
+            assert!(err.to_string().starts_with("ERR Syntax error"), "{}", err);
+        }
+        let query = format!("{}rust{}", "(".repeat(100), ")".repeat(100));
+        let ret = search(&backend, &["ft.search", "idx", &query, "NOCONTENT"])?;
+        assert_eq!(
+            ret,
+            RespArray::new([2.into(), b"book:1".into(), b"book:2".into()]).into()
+        );
+
+        let ret = search(&backend, &["ft.search", "missing", "*"])?;
+        assert_eq!(ret, SimpleError::new("missing: no such index").into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ft_aggregate_command() -> Result<()> {
+        let backend = crate::Backend::new();
+        hset(&backend, "p:1", &[("kind", "fruit"), ("price", "3")]);
+        hset(&backend, "p:2", &[("kind", "fruit"), ("price", "5")]);
+        hset(&backend, "p:3", &[("kind", "veg"), ("price", "2")]);
+        let cmd = FtCreate::try_from(frames(&[
+            "ft.create",
+            "prod",
+            "PREFIX",
+            "1",
+            "p:",
+            "SCHEMA",
+            "kind",
+            "TAG",
+            "price",
+            "NUMERIC",
+        ]))?;
+        cmd.execute(&backend);
+
+        let cmd = FtAggregate::try_from(frames(&[
+            "ft.aggregate",
+            "prod",
+            "*",
+            "GROUPBY",
+            "1",
+            "@kind",
+            "REDUCE",
+            "COUNT",
+            "0",
+            "AS",
+            "n",
+            "REDUCE",
+            "SUM",
+            "1",
+            "@price",
+            "AS",
+            "total",
+            "SORTBY",
+            "2",
+            "@total",
+            "DESC",
+        ]))?;
+        let expected = RespArray::new([
+            2.into(),
+            RespArray::new([
+                b"kind".into(),
+                b"fruit".into(),
+                b"n".into(),
+                b"2".into(),
+                b"total".into(),
+                b"8".into(),
+            ])
+            .into(),
+            RespArray::new([
+                b"kind".into(),
+                b"veg".into(),
+                b"n".into(),
+                b"1".into(),
+                b"total".into(),
+                b"2".into(),
+            ])
+            .into(),
+        ]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        Ok(())
+    }
+}
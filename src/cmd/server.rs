@@ -0,0 +1,383 @@
+use crate::cmd::{CommandError, CommandQuery, Shutdown, RESP_OK};
+use crate::network::ConnectionState;
+use crate::{BulkString, RespArray, RespFrame, RespMap, RespNull, RespSet, SimpleError};
+use tokio::sync::oneshot;
+
+use super::table::{find_spec, lookup_command, CommandSpec, KeySpec, COMMAND_TABLE};
+use super::{extract_args, extract_string, validator_command, CommandExecutor};
+
+impl CommandExecutor for CommandQuery {
+    fn execute(self, _backend: &crate::Backend) -> RespFrame {
+        match self {
+            CommandQuery::List => all_commands(),
+            CommandQuery::Count => (COMMAND_TABLE.len() as i64).into(),
+            CommandQuery::Info(names) if names.is_empty() => all_commands(),
+            CommandQuery::Info(names) => RespArray::new(
+                names
+                    .iter()
+                    .map(|name| match lookup_name(name) {
+                        Some(spec) => command_entry(spec),
+                        None => RespFrame::Null(RespNull),
+                    })
+                    .collect::<Vec<RespFrame>>(),
+            )
+            .into(),
+            CommandQuery::Docs(names) => {
+                let specs = match names.is_empty() {
+                    true => COMMAND_TABLE.iter().collect(),
+                    // like redis, unknown names are left out
+                    false => names
+                        .iter()
+                        .filter_map(|n| lookup_name(n))
+                        .collect::<Vec<_>>(),
+                };
+                specs
+                    .into_iter()
+                    .map(|spec| (BulkString::from(spec.name).into(), command_docs(spec)))
+                    .collect::<RespMap>()
+                    .into()
+            }
+            CommandQuery::GetKeys(args) => match get_keys(args) {
+                Ok(keys) => RespArray::new(keys).into(),
+                Err(e) => e.into(),
+            },
+        }
+    }
+}
+
+impl TryFrom<RespArray> for CommandQuery {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["command"])?;
+
+        let sub = match arr.get(1) {
+            Some(_) => extract_string(arr.get(1).cloned(), "subcommand")?.to_ascii_lowercase(),
+            None => return Ok(CommandQuery::List),
+        };
+
+        match sub.as_str() {
+            "count" => {
+                validator_command(&arr, &["command", "count"])?;
+                Ok(CommandQuery::Count)
+            }
+            "info" => {
+                validator_command(&arr, &["command", "info"])?;
+                Ok(CommandQuery::Info(command_names(arr)?))
+            }
+            "docs" => {
+                validator_command(&arr, &["command", "docs"])?;
+                Ok(CommandQuery::Docs(command_names(arr)?))
+            }
+            "getkeys" => {
+                validator_command(&arr, &["command", "getkeys"])?;
+                Ok(CommandQuery::GetKeys(extract_args(arr, 2)?))
+            }
+            _ => Err(CommandError::InvalidCommand(format!(
+                "unknown subcommand '{}'. Try COMMAND HELP.",
+                sub
+            ))),
+        }
+    }
+}
+
+impl CommandExecutor for Shutdown {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        self.execute_for(backend, &mut ConnectionState::default())
+    }
+
+    fn execute_for(self, backend: &crate::Backend, state: &mut ConnectionState) -> RespFrame {
+        if self.abort {
+            return match backend.shutdown().abort() {
+                true => RESP_OK.clone(),
+                false => SimpleError::new("ERR No shutdown in progress.").into(),
+            };
+        }
+        // the client hears back only if the shutdown fails
+        let (tx, rx) = oneshot::channel();
+        backend.shutdown().request(self.options, Some(tx));
+        state.blocked = Some(rx);
+        RespFrame::Null(RespNull)
+    }
+}
+
+impl TryFrom<RespArray> for Shutdown {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["shutdown"])?;
+        let syntax_error = || CommandError::InvalidArgument("syntax error".to_string());
+        let mut shutdown = Shutdown {
+            abort: false,
+            options: Default::default(),
+        };
+        for arg in extract_args(arr, 1)? {
+            let options = &mut shutdown.options;
+            match extract_string(Some(arg), "option")?
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "nosave" if options.save.is_none() => options.save = Some(false),
+                "save" if options.save.is_none() => options.save = Some(true),
+                "now" => options.now = true,
+                "force" => options.force = true,
+                "abort" => shutdown.abort = true,
+                _ => return Err(syntax_error()),
+            }
+        }
+        // ABORT goes alone
+        if shutdown.abort && shutdown.options != Default::default() {
+            return Err(syntax_error());
+        }
+        Ok(shutdown)
+    }
+}
+
+fn all_commands() -> RespFrame {
+    RespArray::new(
+        COMMAND_TABLE
+            .iter()
+            .map(command_entry)
+            .collect::<Vec<RespFrame>>(),
+    )
+    .into()
+}
+
+fn command_names(arr: RespArray) -> Result<Vec<String>, CommandError> {
+    extract_args(arr, 2)?
+        .into_iter()
+        .map(|name| extract_string(Some(name), "command name"))
+        .collect()
+}
+
+// "config|get" style names reach subcommands
+fn lookup_name(name: &str) -> Option<&'static CommandSpec> {
+    find_spec(&name.split('|').collect::<Vec<_>>())
+}
+
+fn get_keys(args: Vec<RespFrame>) -> Result<Vec<RespFrame>, CommandError> {
+    let spec = match args.first() {
+        Some(RespFrame::BulkString(name)) => lookup_command(name),
+        _ => None,
+    }
+    .ok_or_else(|| CommandError::InvalidCommand("Invalid command specified".to_string()))?;
+
+    if !spec.check_arity(args.len()) {
+        return Err(CommandError::InvalidCommand(
+            "Invalid number of arguments specified for command".to_string(),
+        ));
+    }
+
+    match spec.keys(&args) {
+        keys if keys.is_empty() => Err(CommandError::InvalidCommand(
+            "The command has no key arguments".to_string(),
+        )),
+        keys => Ok(keys.into_iter().cloned().collect()),
+    }
+}
+
+fn map(entries: impl IntoIterator<Item = (&'static str, RespFrame)>) -> RespFrame {
+    entries
+        .into_iter()
+        .map(|(k, v)| (BulkString::from(k).into(), v))
+        .collect::<RespMap>()
+        .into()
+}
+
+fn status_set(items: impl IntoIterator<Item = String>) -> RespFrame {
+    RespSet::new(
+        items
+            .into_iter()
+            .map(|s| RespFrame::from(s.as_str()))
+            .collect::<Vec<RespFrame>>(),
+    )
+    .into()
+}
+
+// the ten element reply of redis 7: name, arity, flags, first key, last key, step,
+// acl categories, tips, key specs and subcommands
+fn command_entry(spec: &CommandSpec) -> RespFrame {
+    let (first, last, step) = spec.legacy_key_range();
+    RespArray::new([
+        BulkString::from(spec.name).into(),
+        spec.arity.into(),
+        status_set(spec.flags.iter().map(|f| f.as_str().to_string())),
+        first.into(),
+        last.into(),
+        step.into(),
+        status_set(spec.acl_categories.iter().map(|c| format!("@{}", c))),
+        RespSet::new([]).into(),
+        RespArray::new(
+            spec.key_specs
+                .iter()
+                .map(key_spec)
+                .collect::<Vec<RespFrame>>(),
+        )
+        .into(),
+        RespArray::new(
+            spec.subcommands
+                .iter()
+                .map(command_entry)
+                .collect::<Vec<RespFrame>>(),
+        )
+        .into(),
+    ])
+    .into()
+}
+
+fn key_spec(spec: &KeySpec) -> RespFrame {
+    map([
+        (
+            "flags",
+            status_set(spec.flags.iter().map(|f| f.to_string())),
+        ),
+        (
+            "begin_search",
+            map([
+                ("type", BulkString::from("index").into()),
+                ("spec", map([("index", (spec.begin as i64).into())])),
+            ]),
+        ),
+        (
+            "find_keys",
+            map([
+                ("type", BulkString::from("range").into()),
+                (
+                    "spec",
+                    map([
+                        ("lastkey", spec.last_key.into()),
+                        ("keystep", (spec.step as i64).into()),
+                        ("limit", 0.into()),
+                    ]),
+                ),
+            ]),
+        ),
+    ])
+}
+
+fn command_docs(spec: &CommandSpec) -> RespFrame {
+    let mut docs = vec![
+        ("summary", BulkString::from(spec.summary).into()),
+        ("since", BulkString::from(spec.since).into()),
+        ("group", BulkString::from(spec.group).into()),
+    ];
+    if !spec.subcommands.is_empty() {
+        let subcommands = spec
+            .subcommands
+            .iter()
+            .map(|sub| (BulkString::from(sub.name).into(), command_docs(sub)))
+            .collect::<RespMap>();
+        docs.push(("subcommands", subcommands.into()));
+    }
+    map(docs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::Command;
+    use crate::SimpleError;
+    use anyhow::Result;
+
+    fn command(args: &[&str]) -> Result<Command, CommandError> {
+        RespArray::new(
+            args.iter()
+                .map(|a| BulkString::from(*a).into())
+                .collect::<Vec<RespFrame>>(),
+        )
+        .try_into()
+    }
+
+    fn run(args: &[&str]) -> Result<RespFrame> {
+        Ok(command(args)?.execute(&crate::Backend::new()))
+    }
+
+    #[test]
+    fn test_dispatch_is_case_insensitive() -> Result<()> {
+        assert!(matches!(command(&["GeT", "k"])?, Command::Get(_)));
+        assert!(matches!(
+            command(&["COMMAND", "Count"])?,
+            Command::CommandQuery(CommandQuery::Count)
+        ));
+        assert_eq!(
+            command(&["get"]).unwrap_err().to_string(),
+            "ERR wrong number of arguments for 'get' command"
+        );
+        assert_eq!(
+            command(&["command", "count", "x"]).unwrap_err().to_string(),
+            "ERR wrong number of arguments for 'command|count' command"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_info_and_count() -> Result<()> {
+        assert_eq!(
+            run(&["command", "count"])?,
+            (COMMAND_TABLE.len() as i64).into()
+        );
+
+        let RespFrame::Array(info) = run(&["command", "info", "GET", "nope"])? else {
+            panic!("COMMAND INFO must reply with an array");
+        };
+        assert_eq!(info[1], RespFrame::Null(RespNull));
+        let RespFrame::Array(get) = &info[0] else {
+            panic!("a command entry must be an array");
+        };
+        assert_eq!(get.len(), 10);
+        assert_eq!(get[0], BulkString::from("get").into());
+        assert_eq!(get[1], 2.into());
+        assert_eq!(
+            get[2],
+            RespSet::new(["readonly".into(), "fast".into()]).into()
+        );
+        assert_eq!(&get[3..6], &[1.into(), 1.into(), 1.into()]);
+
+        let RespFrame::Array(all) = run(&["command"])? else {
+            panic!("COMMAND must reply with an array");
+        };
+        assert_eq!(all.len(), COMMAND_TABLE.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_docs() -> Result<()> {
+        let RespFrame::Map(docs) = run(&["command", "docs", "hget", "command"])? else {
+            panic!("COMMAND DOCS must reply with a map");
+        };
+        assert_eq!(docs.len(), 2);
+        let Some(RespFrame::Map(hget)) = docs.get(BulkString::from("hget")) else {
+            panic!("missing docs for hget");
+        };
+        assert_eq!(
+            hget.get(BulkString::from("group")),
+            Some(&BulkString::from("hash").into())
+        );
+        let Some(RespFrame::Map(command)) = docs.get(BulkString::from("command")) else {
+            panic!("missing docs for command");
+        };
+        assert!(command.get(BulkString::from("subcommands")).is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_getkeys() -> Result<()> {
+        assert_eq!(
+            run(&["command", "getkeys", "set", "foo", "bar"])?,
+            RespArray::new([BulkString::from("foo").into()]).into()
+        );
+        assert_eq!(
+            run(&["command", "getkeys", "ts.mrange", "-", "+", "filter", "a=b"])?,
+            SimpleError::new("ERR The command has no key arguments").into()
+        );
+        assert_eq!(
+            run(&["command", "getkeys", "get"])?,
+            SimpleError::new("ERR Invalid number of arguments specified for command").into()
+        );
+        assert_eq!(
+            run(&["command", "getkeys", "nope", "x"])?,
+            SimpleError::new("ERR Invalid command specified").into()
+        );
+        Ok(())
+    }
+}
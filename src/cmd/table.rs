@@ -0,0 +1,1319 @@
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+
+use crate::{RespArray, RespFrame};
+
+use super::{
+    AclCommand, Auth, ClientCommand, Command, CommandError, CommandQuery, ConfigCommand, DbSize,
+    Echo, FlushAll, FlushDb, FtAggregate, FtCreate, FtSearch, Get, HGet, HGetAll, HSet, Hello,
+    Move, PSubscribe, PUnsubscribe, Ping, PubSubCommand, Publish, Quit, Reset, SPublish,
+    SSubscribe, SUnsubscribe, Select, Set, Shutdown, Subscribe, SwapDb, TsAdd, TsCreate, TsMRange,
+    TsRange, Unsubscribe, VAdd, VCard, VEmb, VRem, VSim,
+};
+
+/// Everything the server knows about a command: how to parse it, and what COMMAND reports.
+#[derive(Debug)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    /// Like redis, the count includes the command name and a negative arity is a minimum.
+    pub arity: i64,
+    pub flags: &'static [CommandFlag],
+    /// Without the leading `@`.
+    pub acl_categories: &'static [&'static str],
+    pub key_specs: &'static [KeySpec],
+    pub group: &'static str,
+    pub since: &'static str,
+    pub summary: &'static str,
+    pub subcommands: &'static [CommandSpec],
+    pub(crate) parse: fn(RespArray) -> Result<Command, CommandError>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandFlag {
+    Write,
+    ReadOnly,
+    Admin,
+    DenyOom,
+    Fast,
+    Blocking,
+    NoScript,
+    Loading,
+    Stale,
+    NoAuth,
+    PubSub,
+}
+
+/// Where the keys are in the arguments: starting at `begin`, up to `last_key` (negative
+/// counts from the end) every `step` arguments.
+#[derive(Debug)]
+pub struct KeySpec {
+    pub begin: usize,
+    pub last_key: i64,
+    pub step: usize,
+    pub flags: &'static [&'static str],
+}
+
+impl CommandFlag {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CommandFlag::Write => "write",
+            CommandFlag::ReadOnly => "readonly",
+            CommandFlag::Admin => "admin",
+            CommandFlag::DenyOom => "denyoom",
+            CommandFlag::Fast => "fast",
+            CommandFlag::Blocking => "blocking",
+            CommandFlag::NoScript => "noscript",
+            CommandFlag::Loading => "loading",
+            CommandFlag::Stale => "stale",
+            CommandFlag::NoAuth => "no_auth",
+            CommandFlag::PubSub => "pubsub",
+        }
+    }
+}
+
+impl CommandSpec {
+    pub fn check_arity(&self, args: usize) -> bool {
+        match self.arity {
+            n if n >= 0 => args as i64 == n,
+            n => args as i64 >= -n,
+        }
+    }
+
+    pub fn subcommand(&self, name: &str) -> Option<&'static CommandSpec> {
+        self.subcommands
+            .iter()
+            .find(|sub| sub.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The first, last and step of the pre-key-spec COMMAND reply.
+    pub fn legacy_key_range(&self) -> (i64, i64, i64) {
+        match self.key_specs.first() {
+            Some(spec) => {
+                let last = match spec.last_key {
+                    n if n >= 0 => spec.begin as i64 + n,
+                    n => n,
+                };
+                (spec.begin as i64, last, spec.step as i64)
+            }
+            None => (0, 0, 0),
+        }
+    }
+
+    /// Whether the command reads keys it finds itself, by index or label, rather than ones
+    /// named in its arguments; only a user who may read every key can run it.
+    pub fn reads_unnamed_keys(&self) -> bool {
+        matches!(self.name, "ts.mrange" | "ft.search" | "ft.aggregate")
+    }
+
+    /// Pick the keys out of a full command line, name included.
+    pub fn keys<'a, T>(&self, args: &'a [T]) -> Vec<&'a T> {
+        self.keys_with_specs(args)
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    /// The keys along with the spec that found them, whose flags say how each is used.
+    pub fn keys_with_specs<'a, T>(&self, args: &'a [T]) -> Vec<(&'a T, &'static KeySpec)> {
+        let mut keys = vec![];
+        for spec in self.key_specs {
+            let last = match spec.last_key {
+                n if n >= 0 => spec.begin + n as usize,
+                n => args.len().saturating_sub(n.unsigned_abs() as usize),
+            };
+            let last = last.min(args.len().saturating_sub(1));
+            keys.extend(
+                (spec.begin..=last)
+                    .step_by(spec.step.max(1))
+                    .map(|i| (&args[i], spec)),
+            );
+        }
+        keys
+    }
+}
+
+fn parse<T>(arr: RespArray) -> Result<Command, CommandError>
+where
+    T: TryFrom<RespArray, Error = CommandError> + Into<Command>,
+{
+    Ok(T::try_from(arr)?.into())
+}
+
+const READ_KEY: &[KeySpec] = &[KeySpec {
+    begin: 1,
+    last_key: 0,
+    step: 1,
+    flags: &["RO", "access"],
+}];
+
+// shard channels aren't keys, but they are declared like them so clients route them by slot
+const SHARD_CHANNEL: &[KeySpec] = &[KeySpec {
+    begin: 1,
+    last_key: 0,
+    step: 1,
+    flags: &["not_key"],
+}];
+
+const SHARD_CHANNELS: &[KeySpec] = &[KeySpec {
+    begin: 1,
+    last_key: -1,
+    step: 1,
+    flags: &["not_key"],
+}];
+
+const UPDATE_KEY: &[KeySpec] = &[KeySpec {
+    begin: 1,
+    last_key: 0,
+    step: 1,
+    flags: &["RW", "update"],
+}];
+
+const OVERWRITE_KEY: &[KeySpec] = &[KeySpec {
+    begin: 1,
+    last_key: 0,
+    step: 1,
+    flags: &["OW", "update"],
+}];
+
+const DELETE_FROM_KEY: &[KeySpec] = &[KeySpec {
+    begin: 1,
+    last_key: 0,
+    step: 1,
+    flags: &["RW", "delete"],
+}];
+
+pub static COMMAND_TABLE: &[CommandSpec] = &[
+    CommandSpec {
+        name: "get",
+        arity: 2,
+        flags: &[CommandFlag::ReadOnly, CommandFlag::Fast],
+        acl_categories: &["read", "string", "fast"],
+        key_specs: READ_KEY,
+        group: "string",
+        since: "1.0.0",
+        summary: "Returns the string value of a key.",
+        subcommands: &[],
+        parse: parse::<Get>,
+    },
+    CommandSpec {
+        name: "set",
+        arity: 3,
+        flags: &[CommandFlag::Write, CommandFlag::DenyOom],
+        acl_categories: &["write", "string", "slow"],
+        key_specs: OVERWRITE_KEY,
+        group: "string",
+        since: "1.0.0",
+        summary: "Sets the string value of a key, ignoring its type.",
+        subcommands: &[],
+        parse: parse::<Set>,
+    },
+    CommandSpec {
+        name: "hget",
+        arity: 3,
+        flags: &[CommandFlag::ReadOnly, CommandFlag::Fast],
+        acl_categories: &["read", "hash", "fast"],
+        key_specs: READ_KEY,
+        group: "hash",
+        since: "2.0.0",
+        summary: "Returns the value of a field in a hash.",
+        subcommands: &[],
+        parse: parse::<HGet>,
+    },
+    CommandSpec {
+        name: "hset",
+        arity: 4,
+        flags: &[CommandFlag::Write, CommandFlag::DenyOom, CommandFlag::Fast],
+        acl_categories: &["write", "hash", "fast"],
+        key_specs: UPDATE_KEY,
+        group: "hash",
+        since: "2.0.0",
+        summary: "Creates or modifies the value of a field in a hash.",
+        subcommands: &[],
+        parse: parse::<HSet>,
+    },
+    CommandSpec {
+        name: "hgetall",
+        arity: 2,
+        flags: &[CommandFlag::ReadOnly],
+        acl_categories: &["read", "hash", "slow"],
+        key_specs: READ_KEY,
+        group: "hash",
+        since: "2.0.0",
+        summary: "Returns all fields and values in a hash.",
+        subcommands: &[],
+        parse: parse::<HGetAll>,
+    },
+    CommandSpec {
+        name: "hello",
+        arity: -1,
+        flags: &[
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+            CommandFlag::Fast,
+            CommandFlag::NoAuth,
+        ],
+        acl_categories: &["fast", "connection"],
+        key_specs: &[],
+        group: "connection",
+        since: "6.0.0",
+        summary: "Handshakes with the Redis server.",
+        subcommands: &[],
+        parse: parse::<Hello>,
+    },
+    CommandSpec {
+        name: "ping",
+        arity: -1,
+        flags: &[CommandFlag::Fast],
+        acl_categories: &["fast", "connection"],
+        key_specs: &[],
+        group: "connection",
+        since: "1.0.0",
+        summary: "Returns the server's liveliness response.",
+        subcommands: &[],
+        parse: parse::<Ping>,
+    },
+    CommandSpec {
+        name: "echo",
+        arity: 2,
+        flags: &[CommandFlag::Fast],
+        acl_categories: &["fast", "connection"],
+        key_specs: &[],
+        group: "connection",
+        since: "1.0.0",
+        summary: "Returns the given string.",
+        subcommands: &[],
+        parse: parse::<Echo>,
+    },
+    CommandSpec {
+        name: "quit",
+        arity: -1,
+        flags: &[
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+            CommandFlag::Fast,
+            CommandFlag::NoAuth,
+        ],
+        acl_categories: &["fast", "connection"],
+        key_specs: &[],
+        group: "connection",
+        since: "1.0.0",
+        summary: "Closes the connection.",
+        subcommands: &[],
+        parse: parse::<Quit>,
+    },
+    CommandSpec {
+        name: "reset",
+        arity: 1,
+        flags: &[
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+            CommandFlag::Fast,
+            CommandFlag::NoAuth,
+        ],
+        acl_categories: &["fast", "connection"],
+        key_specs: &[],
+        group: "connection",
+        since: "6.2.0",
+        summary: "Resets the connection.",
+        subcommands: &[],
+        parse: parse::<Reset>,
+    },
+    CommandSpec {
+        name: "client",
+        arity: -2,
+        flags: &[],
+        acl_categories: &["slow"],
+        key_specs: &[],
+        group: "connection",
+        since: "2.4.0",
+        summary: "A container for client connection commands.",
+        subcommands: &[
+            CommandSpec {
+                name: "client|getname",
+                arity: 2,
+                flags: &[
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["slow", "connection"],
+                key_specs: &[],
+                group: "connection",
+                since: "2.6.9",
+                summary: "Returns the name of the connection.",
+                subcommands: &[],
+                parse: parse::<ClientCommand>,
+            },
+            CommandSpec {
+                name: "client|id",
+                arity: 2,
+                flags: &[
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["slow", "connection"],
+                key_specs: &[],
+                group: "connection",
+                since: "5.0.0",
+                summary: "Returns the unique client ID of the connection.",
+                subcommands: &[],
+                parse: parse::<ClientCommand>,
+            },
+            CommandSpec {
+                name: "client|info",
+                arity: 2,
+                flags: &[
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["slow", "connection"],
+                key_specs: &[],
+                group: "connection",
+                since: "6.2.0",
+                summary: "Returns information about the connection.",
+                subcommands: &[],
+                parse: parse::<ClientCommand>,
+            },
+            CommandSpec {
+                name: "client|kill",
+                arity: -3,
+                flags: &[
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["admin", "slow", "dangerous", "connection"],
+                key_specs: &[],
+                group: "connection",
+                since: "2.4.0",
+                summary: "Terminates open connections.",
+                subcommands: &[],
+                parse: parse::<ClientCommand>,
+            },
+            CommandSpec {
+                name: "client|list",
+                arity: -2,
+                flags: &[
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["admin", "slow", "dangerous", "connection"],
+                key_specs: &[],
+                group: "connection",
+                since: "2.4.0",
+                summary: "Lists open connections.",
+                subcommands: &[],
+                parse: parse::<ClientCommand>,
+            },
+            CommandSpec {
+                name: "client|setname",
+                arity: 3,
+                flags: &[
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["slow", "connection"],
+                key_specs: &[],
+                group: "connection",
+                since: "2.6.9",
+                summary: "Sets the connection name.",
+                subcommands: &[],
+                parse: parse::<ClientCommand>,
+            },
+        ],
+        parse: parse::<ClientCommand>,
+    },
+    CommandSpec {
+        name: "select",
+        arity: 2,
+        flags: &[CommandFlag::Loading, CommandFlag::Stale, CommandFlag::Fast],
+        acl_categories: &["fast", "connection"],
+        key_specs: &[],
+        group: "connection",
+        since: "1.0.0",
+        summary: "Changes the selected database.",
+        subcommands: &[],
+        parse: parse::<Select>,
+    },
+    CommandSpec {
+        name: "move",
+        arity: 3,
+        flags: &[CommandFlag::Write, CommandFlag::Fast],
+        acl_categories: &["keyspace", "write", "fast"],
+        key_specs: UPDATE_KEY,
+        group: "generic",
+        since: "1.0.0",
+        summary: "Moves a key to another database.",
+        subcommands: &[],
+        parse: parse::<Move>,
+    },
+    CommandSpec {
+        name: "swapdb",
+        arity: 3,
+        flags: &[CommandFlag::Write, CommandFlag::Fast],
+        acl_categories: &["keyspace", "write", "fast", "dangerous"],
+        key_specs: &[],
+        group: "server",
+        since: "4.0.0",
+        summary: "Swaps two Redis databases.",
+        subcommands: &[],
+        parse: parse::<SwapDb>,
+    },
+    CommandSpec {
+        name: "flushdb",
+        arity: -1,
+        flags: &[CommandFlag::Write],
+        acl_categories: &["keyspace", "write", "slow", "dangerous"],
+        key_specs: &[],
+        group: "server",
+        since: "1.0.0",
+        summary: "Removes all keys from the current database.",
+        subcommands: &[],
+        parse: parse::<FlushDb>,
+    },
+    CommandSpec {
+        name: "flushall",
+        arity: -1,
+        flags: &[CommandFlag::Write],
+        acl_categories: &["keyspace", "write", "slow", "dangerous"],
+        key_specs: &[],
+        group: "server",
+        since: "1.0.0",
+        summary: "Removes all keys from all databases.",
+        subcommands: &[],
+        parse: parse::<FlushAll>,
+    },
+    CommandSpec {
+        name: "dbsize",
+        arity: 1,
+        flags: &[CommandFlag::ReadOnly, CommandFlag::Fast],
+        acl_categories: &["keyspace", "read", "fast"],
+        key_specs: &[],
+        group: "server",
+        since: "1.0.0",
+        summary: "Returns the number of keys in the database.",
+        subcommands: &[],
+        parse: parse::<DbSize>,
+    },
+    CommandSpec {
+        name: "command",
+        arity: -1,
+        flags: &[CommandFlag::Loading, CommandFlag::Stale],
+        acl_categories: &["slow", "connection"],
+        key_specs: &[],
+        group: "server",
+        since: "2.8.13",
+        summary: "Returns detailed information about all commands.",
+        subcommands: &[
+            CommandSpec {
+                name: "command|count",
+                arity: 2,
+                flags: &[CommandFlag::Loading, CommandFlag::Stale],
+                acl_categories: &["slow", "connection"],
+                key_specs: &[],
+                group: "server",
+                since: "2.8.13",
+                summary: "Returns a count of commands.",
+                subcommands: &[],
+                parse: parse::<CommandQuery>,
+            },
+            CommandSpec {
+                name: "command|docs",
+                arity: -2,
+                flags: &[CommandFlag::Loading, CommandFlag::Stale],
+                acl_categories: &["slow", "connection"],
+                key_specs: &[],
+                group: "server",
+                since: "7.0.0",
+                summary: "Returns documentary information about one, multiple or all commands.",
+                subcommands: &[],
+                parse: parse::<CommandQuery>,
+            },
+            CommandSpec {
+                name: "command|getkeys",
+                arity: -3,
+                flags: &[CommandFlag::Loading, CommandFlag::Stale],
+                acl_categories: &["slow", "connection"],
+                key_specs: &[],
+                group: "server",
+                since: "2.8.13",
+                summary: "Extracts the key names from an arbitrary command.",
+                subcommands: &[],
+                parse: parse::<CommandQuery>,
+            },
+            CommandSpec {
+                name: "command|info",
+                arity: -2,
+                flags: &[CommandFlag::Loading, CommandFlag::Stale],
+                acl_categories: &["slow", "connection"],
+                key_specs: &[],
+                group: "server",
+                since: "2.8.13",
+                summary: "Returns information about one, multiple or all commands.",
+                subcommands: &[],
+                parse: parse::<CommandQuery>,
+            },
+        ],
+        parse: parse::<CommandQuery>,
+    },
+    CommandSpec {
+        name: "config",
+        arity: -2,
+        flags: &[],
+        acl_categories: &["slow"],
+        key_specs: &[],
+        group: "server",
+        since: "2.0.0",
+        summary: "A container for server configuration commands.",
+        subcommands: &[
+            CommandSpec {
+                name: "config|get",
+                arity: -3,
+                flags: &[
+                    CommandFlag::Admin,
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["admin", "slow", "dangerous"],
+                key_specs: &[],
+                group: "server",
+                since: "2.0.0",
+                summary: "Returns the effective values of configuration parameters.",
+                subcommands: &[],
+                parse: parse::<ConfigCommand>,
+            },
+            CommandSpec {
+                name: "config|resetstat",
+                arity: 2,
+                flags: &[
+                    CommandFlag::Admin,
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["admin", "slow", "dangerous"],
+                key_specs: &[],
+                group: "server",
+                since: "2.0.0",
+                summary: "Resets the server's statistics.",
+                subcommands: &[],
+                parse: parse::<ConfigCommand>,
+            },
+            CommandSpec {
+                name: "config|rewrite",
+                arity: 2,
+                flags: &[
+                    CommandFlag::Admin,
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["admin", "slow", "dangerous"],
+                key_specs: &[],
+                group: "server",
+                since: "2.8.0",
+                summary: "Persists the effective configuration to file.",
+                subcommands: &[],
+                parse: parse::<ConfigCommand>,
+            },
+            CommandSpec {
+                name: "config|set",
+                arity: -4,
+                flags: &[
+                    CommandFlag::Admin,
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["admin", "slow", "dangerous"],
+                key_specs: &[],
+                group: "server",
+                since: "2.0.0",
+                summary: "Sets configuration parameters in-flight.",
+                subcommands: &[],
+                parse: parse::<ConfigCommand>,
+            },
+        ],
+        parse: parse::<ConfigCommand>,
+    },
+    CommandSpec {
+        name: "shutdown",
+        arity: -1,
+        flags: &[
+            CommandFlag::Admin,
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+        ],
+        acl_categories: &["admin", "slow", "dangerous"],
+        key_specs: &[],
+        group: "server",
+        since: "1.0.0",
+        summary: "Synchronously saves the database(s) to disk and shuts down the Redis server.",
+        subcommands: &[],
+        parse: parse::<Shutdown>,
+    },
+    CommandSpec {
+        name: "auth",
+        arity: -2,
+        flags: &[
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+            CommandFlag::Fast,
+            CommandFlag::NoAuth,
+        ],
+        acl_categories: &["fast", "connection"],
+        key_specs: &[],
+        group: "connection",
+        since: "1.0.0",
+        summary: "Authenticates the connection.",
+        subcommands: &[],
+        parse: parse::<Auth>,
+    },
+    CommandSpec {
+        name: "acl",
+        arity: -2,
+        flags: &[],
+        acl_categories: &["slow"],
+        key_specs: &[],
+        group: "server",
+        since: "6.0.0",
+        summary: "A container for Access List Control commands.",
+        subcommands: &[
+            CommandSpec {
+                name: "acl|cat",
+                arity: -2,
+                flags: &[
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["slow"],
+                key_specs: &[],
+                group: "server",
+                since: "6.0.0",
+                summary: "Lists the ACL categories, or the commands inside a category.",
+                subcommands: &[],
+                parse: parse::<AclCommand>,
+            },
+            CommandSpec {
+                name: "acl|deluser",
+                arity: -3,
+                flags: &[
+                    CommandFlag::Admin,
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["admin", "slow", "dangerous"],
+                key_specs: &[],
+                group: "server",
+                since: "6.0.0",
+                summary: "Deletes ACL users, and terminates their connections.",
+                subcommands: &[],
+                parse: parse::<AclCommand>,
+            },
+            CommandSpec {
+                name: "acl|getuser",
+                arity: 3,
+                flags: &[
+                    CommandFlag::Admin,
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["admin", "slow", "dangerous"],
+                key_specs: &[],
+                group: "server",
+                since: "6.0.0",
+                summary: "Lists the ACL rules of a user.",
+                subcommands: &[],
+                parse: parse::<AclCommand>,
+            },
+            CommandSpec {
+                name: "acl|list",
+                arity: 2,
+                flags: &[
+                    CommandFlag::Admin,
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["admin", "slow", "dangerous"],
+                key_specs: &[],
+                group: "server",
+                since: "6.0.0",
+                summary: "Dumps the effective rules in ACL file format.",
+                subcommands: &[],
+                parse: parse::<AclCommand>,
+            },
+            CommandSpec {
+                name: "acl|load",
+                arity: 2,
+                flags: &[
+                    CommandFlag::Admin,
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["admin", "slow", "dangerous"],
+                key_specs: &[],
+                group: "server",
+                since: "6.0.0",
+                summary: "Reloads the rules from the configured ACL file.",
+                subcommands: &[],
+                parse: parse::<AclCommand>,
+            },
+            CommandSpec {
+                name: "acl|log",
+                arity: -2,
+                flags: &[
+                    CommandFlag::Admin,
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["admin", "slow", "dangerous"],
+                key_specs: &[],
+                group: "server",
+                since: "6.0.0",
+                summary: "Lists recent security events generated due to ACL rules.",
+                subcommands: &[],
+                parse: parse::<AclCommand>,
+            },
+            CommandSpec {
+                name: "acl|save",
+                arity: 2,
+                flags: &[
+                    CommandFlag::Admin,
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["admin", "slow", "dangerous"],
+                key_specs: &[],
+                group: "server",
+                since: "6.0.0",
+                summary: "Saves the effective ACL rules in the configured ACL file.",
+                subcommands: &[],
+                parse: parse::<AclCommand>,
+            },
+            CommandSpec {
+                name: "acl|setuser",
+                arity: -3,
+                flags: &[
+                    CommandFlag::Admin,
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["admin", "slow", "dangerous"],
+                key_specs: &[],
+                group: "server",
+                since: "6.0.0",
+                summary: "Creates and modifies an ACL user and its rules.",
+                subcommands: &[],
+                parse: parse::<AclCommand>,
+            },
+            CommandSpec {
+                name: "acl|users",
+                arity: 2,
+                flags: &[
+                    CommandFlag::Admin,
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["admin", "slow", "dangerous"],
+                key_specs: &[],
+                group: "server",
+                since: "6.0.0",
+                summary: "Lists all ACL users.",
+                subcommands: &[],
+                parse: parse::<AclCommand>,
+            },
+            CommandSpec {
+                name: "acl|whoami",
+                arity: 2,
+                flags: &[
+                    CommandFlag::NoScript,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["slow"],
+                key_specs: &[],
+                group: "server",
+                since: "6.0.0",
+                summary: "Returns the authenticated username of the current connection.",
+                subcommands: &[],
+                parse: parse::<AclCommand>,
+            },
+        ],
+        parse: parse::<AclCommand>,
+    },
+    CommandSpec {
+        name: "subscribe",
+        arity: -2,
+        flags: &[
+            CommandFlag::PubSub,
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+        ],
+        acl_categories: &["pubsub", "slow"],
+        key_specs: &[],
+        group: "pubsub",
+        since: "2.0.0",
+        summary: "Listens for messages published to channels.",
+        subcommands: &[],
+        parse: parse::<Subscribe>,
+    },
+    CommandSpec {
+        name: "unsubscribe",
+        arity: -1,
+        flags: &[
+            CommandFlag::PubSub,
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+        ],
+        acl_categories: &["pubsub", "slow"],
+        key_specs: &[],
+        group: "pubsub",
+        since: "2.0.0",
+        summary: "Stops listening to messages posted to channels.",
+        subcommands: &[],
+        parse: parse::<Unsubscribe>,
+    },
+    CommandSpec {
+        name: "psubscribe",
+        arity: -2,
+        flags: &[
+            CommandFlag::PubSub,
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+        ],
+        acl_categories: &["pubsub", "slow"],
+        key_specs: &[],
+        group: "pubsub",
+        since: "2.0.0",
+        summary: "Listens for messages published to channels that match one or more patterns.",
+        subcommands: &[],
+        parse: parse::<PSubscribe>,
+    },
+    CommandSpec {
+        name: "punsubscribe",
+        arity: -1,
+        flags: &[
+            CommandFlag::PubSub,
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+        ],
+        acl_categories: &["pubsub", "slow"],
+        key_specs: &[],
+        group: "pubsub",
+        since: "2.0.0",
+        summary:
+            "Stops listening to messages published to channels that match one or more patterns.",
+        subcommands: &[],
+        parse: parse::<PUnsubscribe>,
+    },
+    CommandSpec {
+        name: "publish",
+        arity: 3,
+        flags: &[
+            CommandFlag::PubSub,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+            CommandFlag::Fast,
+        ],
+        acl_categories: &["pubsub", "fast"],
+        key_specs: &[],
+        group: "pubsub",
+        since: "2.0.0",
+        summary: "Posts a message to a channel.",
+        subcommands: &[],
+        parse: parse::<Publish>,
+    },
+    CommandSpec {
+        name: "ssubscribe",
+        arity: -2,
+        flags: &[
+            CommandFlag::PubSub,
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+        ],
+        acl_categories: &["pubsub", "slow"],
+        key_specs: SHARD_CHANNELS,
+        group: "pubsub",
+        since: "7.0.0",
+        summary: "Listens for messages published to shard channels.",
+        subcommands: &[],
+        parse: parse::<SSubscribe>,
+    },
+    CommandSpec {
+        name: "sunsubscribe",
+        arity: -1,
+        flags: &[
+            CommandFlag::PubSub,
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+        ],
+        acl_categories: &["pubsub", "slow"],
+        key_specs: SHARD_CHANNELS,
+        group: "pubsub",
+        since: "7.0.0",
+        summary: "Stops listening to messages posted to shard channels.",
+        subcommands: &[],
+        parse: parse::<SUnsubscribe>,
+    },
+    CommandSpec {
+        name: "spublish",
+        arity: 3,
+        flags: &[
+            CommandFlag::PubSub,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+            CommandFlag::Fast,
+        ],
+        acl_categories: &["pubsub", "fast"],
+        key_specs: SHARD_CHANNEL,
+        group: "pubsub",
+        since: "7.0.0",
+        summary: "Post a message to a shard channel",
+        subcommands: &[],
+        parse: parse::<SPublish>,
+    },
+    CommandSpec {
+        name: "pubsub",
+        arity: -2,
+        flags: &[],
+        acl_categories: &["slow"],
+        key_specs: &[],
+        group: "pubsub",
+        since: "2.8.0",
+        summary: "A container for Pub/Sub commands.",
+        subcommands: &[
+            CommandSpec {
+                name: "pubsub|channels",
+                arity: -2,
+                flags: &[
+                    CommandFlag::PubSub,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["pubsub", "slow"],
+                key_specs: &[],
+                group: "pubsub",
+                since: "2.8.0",
+                summary: "Returns the active channels.",
+                subcommands: &[],
+                parse: parse::<PubSubCommand>,
+            },
+            CommandSpec {
+                name: "pubsub|numpat",
+                arity: 2,
+                flags: &[
+                    CommandFlag::PubSub,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["pubsub", "slow"],
+                key_specs: &[],
+                group: "pubsub",
+                since: "2.8.0",
+                summary: "Returns a count of unique pattern subscriptions.",
+                subcommands: &[],
+                parse: parse::<PubSubCommand>,
+            },
+            CommandSpec {
+                name: "pubsub|numsub",
+                arity: -2,
+                flags: &[
+                    CommandFlag::PubSub,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["pubsub", "slow"],
+                key_specs: &[],
+                group: "pubsub",
+                since: "2.8.0",
+                summary: "Returns a count of subscribers to channels.",
+                subcommands: &[],
+                parse: parse::<PubSubCommand>,
+            },
+            CommandSpec {
+                name: "pubsub|shardchannels",
+                arity: -2,
+                flags: &[
+                    CommandFlag::PubSub,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["pubsub", "slow"],
+                key_specs: &[],
+                group: "pubsub",
+                since: "7.0.0",
+                summary: "Returns the active shard channels.",
+                subcommands: &[],
+                parse: parse::<PubSubCommand>,
+            },
+            CommandSpec {
+                name: "pubsub|shardnumsub",
+                arity: -2,
+                flags: &[
+                    CommandFlag::PubSub,
+                    CommandFlag::Loading,
+                    CommandFlag::Stale,
+                ],
+                acl_categories: &["pubsub", "slow"],
+                key_specs: &[],
+                group: "pubsub",
+                since: "7.0.0",
+                summary: "Returns the count of subscribers of shard channels.",
+                subcommands: &[],
+                parse: parse::<PubSubCommand>,
+            },
+        ],
+        parse: parse::<PubSubCommand>,
+    },
+    CommandSpec {
+        name: "ts.create",
+        arity: -2,
+        flags: &[CommandFlag::Write, CommandFlag::DenyOom],
+        acl_categories: &["write", "timeseries", "fast"],
+        key_specs: UPDATE_KEY,
+        group: "timeseries",
+        since: "1.0.0",
+        summary: "Creates a new time series.",
+        subcommands: &[],
+        parse: parse::<TsCreate>,
+    },
+    CommandSpec {
+        name: "ts.add",
+        arity: -4,
+        flags: &[CommandFlag::Write, CommandFlag::DenyOom],
+        acl_categories: &["write", "timeseries", "fast"],
+        key_specs: UPDATE_KEY,
+        group: "timeseries",
+        since: "1.0.0",
+        summary: "Appends a sample to a time series.",
+        subcommands: &[],
+        parse: parse::<TsAdd>,
+    },
+    CommandSpec {
+        name: "ts.range",
+        arity: -4,
+        flags: &[CommandFlag::ReadOnly],
+        acl_categories: &["read", "timeseries", "slow"],
+        key_specs: READ_KEY,
+        group: "timeseries",
+        since: "1.0.0",
+        summary: "Queries a range of samples in a time series.",
+        subcommands: &[],
+        parse: parse::<TsRange>,
+    },
+    CommandSpec {
+        name: "ts.mrange",
+        arity: -5,
+        flags: &[CommandFlag::ReadOnly],
+        acl_categories: &["read", "timeseries", "slow"],
+        key_specs: &[],
+        group: "timeseries",
+        since: "1.0.0",
+        summary: "Queries a range of samples across time series selected by labels.",
+        subcommands: &[],
+        parse: parse::<TsMRange>,
+    },
+    CommandSpec {
+        name: "vadd",
+        arity: -4,
+        flags: &[CommandFlag::Write, CommandFlag::DenyOom],
+        acl_categories: &["write", "vectorset", "slow"],
+        key_specs: UPDATE_KEY,
+        group: "vectorset",
+        since: "8.0.0",
+        summary: "Adds an element and its vector to a vector set.",
+        subcommands: &[],
+        parse: parse::<VAdd>,
+    },
+    CommandSpec {
+        name: "vsim",
+        arity: -4,
+        flags: &[CommandFlag::ReadOnly],
+        acl_categories: &["read", "vectorset", "slow"],
+        key_specs: READ_KEY,
+        group: "vectorset",
+        since: "8.0.0",
+        summary: "Returns the elements most similar to a vector or an element.",
+        subcommands: &[],
+        parse: parse::<VSim>,
+    },
+    CommandSpec {
+        name: "vrem",
+        arity: 3,
+        flags: &[CommandFlag::Write],
+        acl_categories: &["write", "vectorset", "slow"],
+        key_specs: DELETE_FROM_KEY,
+        group: "vectorset",
+        since: "8.0.0",
+        summary: "Removes an element from a vector set.",
+        subcommands: &[],
+        parse: parse::<VRem>,
+    },
+    CommandSpec {
+        name: "vcard",
+        arity: 2,
+        flags: &[CommandFlag::ReadOnly, CommandFlag::Fast],
+        acl_categories: &["read", "vectorset", "fast"],
+        key_specs: READ_KEY,
+        group: "vectorset",
+        since: "8.0.0",
+        summary: "Returns the number of elements in a vector set.",
+        subcommands: &[],
+        parse: parse::<VCard>,
+    },
+    CommandSpec {
+        name: "vemb",
+        arity: 3,
+        flags: &[CommandFlag::ReadOnly, CommandFlag::Fast],
+        acl_categories: &["read", "vectorset", "fast"],
+        key_specs: READ_KEY,
+        group: "vectorset",
+        since: "8.0.0",
+        summary: "Returns the vector of an element in a vector set.",
+        subcommands: &[],
+        parse: parse::<VEmb>,
+    },
+    CommandSpec {
+        name: "ft.create",
+        arity: -5,
+        flags: &[CommandFlag::Write, CommandFlag::DenyOom],
+        acl_categories: &["write", "search", "slow"],
+        key_specs: &[],
+        group: "search",
+        since: "1.0.0",
+        summary: "Creates an index over hashes with the given prefixes.",
+        subcommands: &[],
+        parse: parse::<FtCreate>,
+    },
+    CommandSpec {
+        name: "ft.search",
+        arity: -3,
+        flags: &[CommandFlag::ReadOnly],
+        acl_categories: &["read", "search", "slow"],
+        key_specs: &[],
+        group: "search",
+        since: "1.0.0",
+        summary: "Searches an index with a query.",
+        subcommands: &[],
+        parse: parse::<FtSearch>,
+    },
+    CommandSpec {
+        name: "ft.aggregate",
+        arity: -3,
+        flags: &[CommandFlag::ReadOnly],
+        acl_categories: &["read", "search", "slow"],
+        key_specs: &[],
+        group: "search",
+        since: "1.1.0",
+        summary: "Runs a grouping and reducing pipeline over an index.",
+        subcommands: &[],
+        parse: parse::<FtAggregate>,
+    },
+];
+
+lazy_static! {
+    static ref COMMANDS: HashMap<&'static str, &'static CommandSpec> =
+        COMMAND_TABLE.iter().map(|spec| (spec.name, spec)).collect();
+}
+
+/// Look a command up by name, ignoring case.
+pub fn lookup_command(name: &[u8]) -> Option<&'static CommandSpec> {
+    let name = String::from_utf8_lossy(name).to_ascii_lowercase();
+    COMMANDS.get(name.as_str()).copied()
+}
+
+/// The command a command line runs, down to the subcommand of a container, as long as it
+/// exists and has the right number of arguments.
+pub fn resolve_command(args: &[RespFrame]) -> Option<&'static CommandSpec> {
+    let name = |i: usize| match args.get(i) {
+        Some(RespFrame::BulkString(name)) => Some(String::from_utf8_lossy(name).into_owned()),
+        _ => None,
+    };
+    let spec = lookup_command(name(0)?.as_bytes())?;
+    if !spec.check_arity(args.len()) {
+        return None;
+    }
+    if spec.subcommands.is_empty() {
+        return Some(spec);
+    }
+    let sub = spec.subcommand(&format!("{}|{}", spec.name, name(1)?))?;
+    sub.check_arity(args.len()).then_some(sub)
+}
+
+/// Look up a command, or one of its subcommands with `["command", "info"]`.
+pub(crate) fn find_spec(names: &[&str]) -> Option<&'static CommandSpec> {
+    let (name, subs) = names.split_first()?;
+    let mut spec = lookup_command(name.as_bytes())?;
+    for sub in subs {
+        spec = spec.subcommand(&format!("{}|{}", spec.name, sub))?;
+    }
+    Some(spec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_command_table() {
+        let mut names = HashSet::new();
+        for spec in COMMAND_TABLE {
+            assert_eq!(spec.name, spec.name.to_ascii_lowercase());
+            assert!(names.insert(spec.name), "{} registered twice", spec.name);
+            assert_ne!(spec.arity, 0);
+            for sub in spec.subcommands {
+                assert!(sub.name.starts_with(&format!("{}|", spec.name)));
+            }
+            // ACL rules can name every category a command is in
+            for category in spec
+                .subcommands
+                .iter()
+                .chain([spec])
+                .flat_map(|s| s.acl_categories)
+            {
+                assert!(crate::ACL_CATEGORIES.contains(category), "{}", category);
+            }
+        }
+
+        assert_eq!(lookup_command(b"GeT").map(|s| s.name), Some("get"));
+        assert!(lookup_command(b"nope").is_none());
+        assert_eq!(
+            find_spec(&["command", "getkeys"]).map(|s| s.arity),
+            Some(-3)
+        );
+
+        let get = lookup_command(b"get").unwrap();
+        assert!(get.check_arity(2) && !get.check_arity(3));
+        let ts_add = lookup_command(b"ts.add").unwrap();
+        assert!(!ts_add.check_arity(3) && ts_add.check_arity(6));
+        assert_eq!(get.legacy_key_range(), (1, 1, 1));
+        assert_eq!(get.keys(&["get", "foo"]), vec![&"foo"]);
+    }
+}
@@ -0,0 +1,545 @@
+use crate::cmd::{CommandError, TsAdd, TsCreate, TsMRange, TsRange, RESP_OK};
+use crate::{
+    Aggregation, BulkString, DuplicatePolicy, KeyType, LabelFilter, RespArray, RespFrame,
+    SimpleError, TimeSeriesOptions,
+};
+use std::iter::Peekable;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{
+    expect_type, extract_args, extract_string, parse_arg, validator_command, CommandExecutor,
+};
+
+impl CommandExecutor for TsCreate {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        if let Err(e) = expect_type(backend, &self.key, KeyType::TimeSeries) {
+            return e.into();
+        }
+        match backend.ts_create(self.key, self.opts) {
+            Ok(_) => RESP_OK.clone(),
+            Err(e) => SimpleError::new(e.to_string()).into(),
+        }
+    }
+}
+
+impl CommandExecutor for TsAdd {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        if let Err(e) = expect_type(backend, &self.key, KeyType::TimeSeries) {
+            return e.into();
+        }
+        let timestamp = self.timestamp.unwrap_or_else(now_millis);
+        match backend.ts_add(
+            self.key,
+            timestamp,
+            self.value,
+            self.opts,
+            self.on_duplicate,
+        ) {
+            Ok(ts) => ts.into(),
+            Err(e) => SimpleError::new(e.to_string()).into(),
+        }
+    }
+}
+
+impl CommandExecutor for TsRange {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        if let Err(e) = expect_type(backend, &self.key, KeyType::TimeSeries) {
+            return e.into();
+        }
+        match backend.ts_range(&self.key, self.from, self.to, self.aggregation, self.count) {
+            Ok(samples) => samples_to_frame(samples),
+            Err(e) => SimpleError::new(e.to_string()).into(),
+        }
+    }
+}
+
+impl CommandExecutor for TsMRange {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        let series = backend.ts_mrange(
+            self.from,
+            self.to,
+            self.aggregation,
+            self.count,
+            &self.filters,
+        );
+
+        let ret = series
+            .into_iter()
+            .map(|(key, labels, samples)| {
+                let labels = match self.with_labels {
+                    true => labels
+                        .into_iter()
+                        .map(|(k, v)| {
+                            RespArray::new([BulkString::new(k).into(), BulkString::new(v).into()])
+                                .into()
+                        })
+                        .collect::<Vec<RespFrame>>(),
+                    false => vec![],
+                };
+                RespArray::new([
+                    BulkString::new(key).into(),
+                    RespArray::new(labels).into(),
+                    samples_to_frame(samples),
+                ])
+                .into()
+            })
+            .collect::<Vec<RespFrame>>();
+
+        RespArray::new(ret).into()
+    }
+}
+
+impl TryFrom<RespArray> for TsCreate {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["ts.create"])?;
+
+        let mut args = extract_args(arr, 1)?.into_iter().peekable();
+        let key = extract_string(args.next(), "key")?;
+        let opts = parse_series_options(&mut args, None)?;
+
+        Ok(TsCreate { key, opts })
+    }
+}
+
+impl TryFrom<RespArray> for TsAdd {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["ts.add"])?;
+
+        let mut args = extract_args(arr, 1)?.into_iter().peekable();
+        let key = extract_string(args.next(), "key")?;
+        // like RedisTimeSeries, samples are never timestamped before the epoch, which also
+        // keeps downsampling clear of i64::MIN
+        let timestamp = match extract_string(args.next(), "timestamp")?.as_str() {
+            "*" => None,
+            ts => Some(
+                ts.parse::<i64>()
+                    .ok()
+                    .filter(|ts| *ts >= 0)
+                    .ok_or_else(|| {
+                        CommandError::InvalidArgument("Invalid timestamp".to_string())
+                    })?,
+            ),
+        };
+        let value = parse_arg(args.next(), "value")?;
+        let mut on_duplicate = None;
+        let opts = parse_series_options(&mut args, Some(&mut on_duplicate))?;
+
+        Ok(TsAdd {
+            key,
+            timestamp,
+            value,
+            opts,
+            on_duplicate,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for TsRange {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["ts.range"])?;
+
+        let mut args = extract_args(arr, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let from = parse_timestamp(args.next(), "fromTimestamp")?;
+        let to = parse_timestamp(args.next(), "toTimestamp")?;
+
+        let mut ret = TsRange {
+            key,
+            from,
+            to,
+            aggregation: None,
+            count: None,
+        };
+
+        while let Some(arg) = args.next() {
+            match extract_string(Some(arg), "option")?
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "aggregation" => ret.aggregation = Some(parse_aggregation(&mut args)?),
+                "count" => ret.count = Some(parse_arg(args.next(), "count")?),
+                opt => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Unknown option: {}",
+                        opt
+                    )))
+                }
+            }
+        }
+
+        Ok(ret)
+    }
+}
+
+impl TryFrom<RespArray> for TsMRange {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["ts.mrange"])?;
+
+        let mut args = extract_args(arr, 1)?.into_iter();
+        let from = parse_timestamp(args.next(), "fromTimestamp")?;
+        let to = parse_timestamp(args.next(), "toTimestamp")?;
+
+        let mut ret = TsMRange {
+            from,
+            to,
+            aggregation: None,
+            count: None,
+            with_labels: false,
+            filters: vec![],
+        };
+
+        while let Some(arg) = args.next() {
+            match extract_string(Some(arg), "option")?
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "aggregation" => ret.aggregation = Some(parse_aggregation(&mut args)?),
+                "count" => ret.count = Some(parse_arg(args.next(), "count")?),
+                "withlabels" => ret.with_labels = true,
+                "filter" => {
+                    for arg in args.by_ref() {
+                        let filter = extract_string(Some(arg), "filter")?;
+                        let filter = LabelFilter::parse(&filter).ok_or_else(|| {
+                            CommandError::InvalidArgument(format!("Invalid filter: {}", filter))
+                        })?;
+                        ret.filters.push(filter);
+                    }
+                }
+                opt => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Unknown option: {}",
+                        opt
+                    )))
+                }
+            }
+        }
+
+        // at least one matcher must select by value, otherwise every series would match
+        if !ret
+            .filters
+            .iter()
+            .any(|f| matches!(f, LabelFilter::Equal(_, v) if v.iter().any(|x| !x.is_empty())))
+        {
+            return Err(CommandError::InvalidArgument(
+                "FILTER must contain at least one label=value matcher".to_string(),
+            ));
+        }
+
+        Ok(ret)
+    }
+}
+
+const SERIES_OPTIONS: [&str; 4] = ["retention", "duplicate_policy", "on_duplicate", "labels"];
+
+// the key's options, as TS.CREATE takes them and TS.ADD when it creates the key; only TS.ADD
+// passes `on_duplicate` to take ON_DUPLICATE
+fn parse_series_options(
+    args: &mut Peekable<impl Iterator<Item = RespFrame>>,
+    mut on_duplicate: Option<&mut Option<DuplicatePolicy>>,
+) -> Result<TimeSeriesOptions, CommandError> {
+    let mut opts = TimeSeriesOptions::default();
+
+    while let Some(arg) = args.next() {
+        match extract_string(Some(arg), "option")?
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "retention" => opts.retention = Some(parse_arg(args.next(), "retention")?),
+            "duplicate_policy" => opts.duplicate_policy = Some(parse_policy(args.next())?),
+            "on_duplicate" if on_duplicate.is_some() => {
+                if let Some(policy) = on_duplicate.as_deref_mut() {
+                    *policy = Some(parse_policy(args.next())?);
+                }
+            }
+            "labels" => {
+                // pairs run up to the next option, so LABELS needn't come last
+                let mut labels = vec![];
+                while let Some(label) = args.next_if(|arg| !is_series_option(arg)) {
+                    let label = extract_string(Some(label), "label")?;
+                    let value = extract_string(args.next(), "label value")?;
+                    labels.push((label, value));
+                }
+                opts.labels = Some(labels);
+            }
+            opt => {
+                return Err(CommandError::InvalidArgument(format!(
+                    "Unknown option: {}",
+                    opt
+                )))
+            }
+        }
+    }
+
+    Ok(opts)
+}
+
+fn is_series_option(arg: &RespFrame) -> bool {
+    matches!(arg, RespFrame::BulkString(s)
+        if SERIES_OPTIONS.iter().any(|opt| s.eq_ignore_ascii_case(opt.as_bytes())))
+}
+
+fn parse_policy(arg: Option<RespFrame>) -> Result<DuplicatePolicy, CommandError> {
+    extract_string(arg, "duplicate policy")?
+        .parse()
+        .map_err(|e: crate::TimeSeriesError| CommandError::InvalidArgument(e.to_string()))
+}
+
+fn parse_aggregation(
+    args: &mut impl Iterator<Item = RespFrame>,
+) -> Result<(Aggregation, u64), CommandError> {
+    let agg: Aggregation = extract_string(args.next(), "aggregation")?
+        .parse()
+        .map_err(|e: crate::TimeSeriesError| CommandError::InvalidArgument(e.to_string()))?;
+    let bucket: u64 = parse_arg(args.next(), "bucketDuration")?;
+    if bucket == 0 {
+        return Err(CommandError::InvalidArgument(
+            "bucketDuration must be greater than zero".to_string(),
+        ));
+    }
+    Ok((agg, bucket))
+}
+
+fn parse_timestamp(arg: Option<RespFrame>, name: &str) -> Result<i64, CommandError> {
+    match extract_string(arg, name)?.as_str() {
+        "-" => Ok(i64::MIN),
+        "+" => Ok(i64::MAX),
+        ts => ts
+            .parse()
+            .map_err(|_| CommandError::InvalidArgument(format!("Invalid {}", name))),
+    }
+}
+
+fn samples_to_frame(samples: Vec<(i64, f64)>) -> RespFrame {
+    let ret = samples
+        .into_iter()
+        .map(|(ts, value)| RespArray::new([ts.into(), value.into()]).into())
+        .collect::<Vec<RespFrame>>();
+    RespArray::new(ret).into()
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn frames(args: &[&str]) -> RespArray {
+        RespArray::new(
+            args.iter()
+                .map(|s| BulkString::from(*s).into())
+                .collect::<Vec<RespFrame>>(),
+        )
+    }
+
+    #[test]
+    fn test_ts_add_try_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*10\r\n$6\r\nts.add\r\n$3\r\ncpu\r\n$4\r\n1000\r\n$3\r\n1.5\r\n$9\r\nRETENTION\r\n$3\r\n100\r\n$6\r\nLABELS\r\n$4\r\nhost\r\n$1\r\na\r\n$1\r\nb\r\n");
+
+        let arr = RespArray::decode(&mut buf)?;
+        let ret = TsAdd::try_from(arr);
+        assert!(ret.is_err());
+
+        buf.extend_from_slice(b"*9\r\n$6\r\nts.add\r\n$3\r\ncpu\r\n$1\r\n*\r\n$3\r\n1.5\r\n$9\r\nRETENTION\r\n$3\r\n100\r\n$6\r\nLABELS\r\n$4\r\nhost\r\n$1\r\na\r\n");
+        let arr = RespArray::decode(&mut buf)?;
+        let add = TsAdd::try_from(arr)?;
+        assert_eq!(add.key, "cpu");
+        assert_eq!(add.timestamp, None);
+        assert_eq!(add.value, 1.5);
+        assert_eq!(add.opts.retention, Some(100));
+        assert_eq!(
+            add.opts.labels,
+            Some(vec![("host".to_string(), "a".to_string())])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ts_options_try_from_resp_array() -> Result<()> {
+        // LABELS stops at the next option
+        let add = TsAdd::try_from(frames(&[
+            "ts.add",
+            "cpu",
+            "1",
+            "1.5",
+            "LABELS",
+            "host",
+            "a",
+            "RETENTION",
+            "100",
+            "DUPLICATE_POLICY",
+            "sum",
+            "ON_DUPLICATE",
+            "max",
+        ]))?;
+        assert_eq!(
+            add.opts.labels,
+            Some(vec![("host".to_string(), "a".to_string())])
+        );
+        assert_eq!(add.opts.retention, Some(100));
+        assert_eq!(add.opts.duplicate_policy, Some(DuplicatePolicy::Sum));
+        assert_eq!(add.on_duplicate, Some(DuplicatePolicy::Max));
+
+        let ret = TsAdd::try_from(frames(&["ts.add", "cpu", "1", "1.5", "LABELS", "host"]));
+        assert!(ret.is_err());
+        for ts in ["-1", "-9223372036854775808"] {
+            let ret = TsAdd::try_from(frames(&["ts.add", "cpu", ts, "1.5"]));
+            assert!(ret.is_err());
+        }
+        // ON_DUPLICATE is per sample, there's none to TS.CREATE
+        let ret = TsCreate::try_from(frames(&["ts.create", "cpu", "ON_DUPLICATE", "last"]));
+        assert!(ret.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ts_range_try_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*7\r\n$8\r\nts.range\r\n$3\r\ncpu\r\n$1\r\n-\r\n$1\r\n+\r\n$11\r\nAGGREGATION\r\n$3\r\navg\r\n$2\r\n10\r\n");
+
+        let arr = RespArray::decode(&mut buf)?;
+        let range = TsRange::try_from(arr)?;
+        assert_eq!(range.from, i64::MIN);
+        assert_eq!(range.to, i64::MAX);
+        assert_eq!(range.aggregation, Some((Aggregation::Avg, 10)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ts_commands() -> Result<()> {
+        let backend = crate::Backend::new();
+        let labels = |v: &str| TimeSeriesOptions {
+            labels: Some(vec![("host".to_string(), v.to_string())]),
+            duplicate_policy: Some(DuplicatePolicy::Sum),
+            ..Default::default()
+        };
+
+        let cmd = TsCreate {
+            key: "cpu:a".to_string(),
+            opts: labels("a"),
+        };
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+
+        let cmd = TsCreate {
+            key: "cpu:a".to_string(),
+            opts: labels("a"),
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            SimpleError::new("TSDB: key already exists").into()
+        );
+
+        for (ts, value) in [(1, 1.0), (2, 3.0), (2, 1.0), (11, 4.0)] {
+            let cmd = TsAdd {
+                key: "cpu:a".to_string(),
+                timestamp: Some(ts),
+                value,
+                opts: Default::default(),
+                on_duplicate: None,
+            };
+            assert_eq!(cmd.execute(&backend), ts.into());
+        }
+
+        let cmd = TsAdd {
+            key: "cpu:b".to_string(),
+            timestamp: Some(5),
+            value: 10.0,
+            opts: labels("b"),
+            on_duplicate: None,
+        };
+        cmd.execute(&backend);
+
+        let cmd = TsRange {
+            key: "cpu:a".to_string(),
+            from: i64::MIN,
+            to: i64::MAX,
+            aggregation: Some((Aggregation::Avg, 10)),
+            count: None,
+        };
+        let expected = RespArray::new([
+            RespArray::new([0.into(), 2.5.into()]).into(),
+            RespArray::new([10.into(), 4.0.into()]).into(),
+        ]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        let cmd = TsMRange {
+            from: 0,
+            to: 100,
+            aggregation: Some((Aggregation::Count, 100)),
+            count: None,
+            with_labels: true,
+            filters: vec![LabelFilter::parse("host=(b,c)").unwrap()],
+        };
+        let expected = RespArray::new([RespArray::new([
+            BulkString::from("cpu:b").into(),
+            RespArray::new([RespArray::new([
+                BulkString::from("host").into(),
+                BulkString::from("b").into(),
+            ])
+            .into()])
+            .into(),
+            RespArray::new([RespArray::new([0.into(), 1.0.into()]).into()]).into(),
+        ])
+        .into()]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ts_retention_and_duplicate_policy() {
+        let backend = crate::Backend::new();
+        let opts = TimeSeriesOptions {
+            retention: Some(10),
+            ..Default::default()
+        };
+        backend.ts_create("t".to_string(), opts).unwrap();
+
+        backend
+            .ts_add("t".to_string(), 100, 1.0, Default::default(), None)
+            .unwrap();
+        let ret = backend.ts_add("t".to_string(), 100, 2.0, Default::default(), None);
+        assert_eq!(ret, Err(crate::TimeSeriesError::DuplicateBlocked));
+        let ret = backend.ts_add("t".to_string(), 50, 2.0, Default::default(), None);
+        assert_eq!(ret, Err(crate::TimeSeriesError::TooOld));
+
+        backend
+            .ts_add("t".to_string(), 120, 2.0, Default::default(), None)
+            .unwrap();
+        let samples = backend.ts_range("t", 0, 200, None, None).unwrap();
+        assert_eq!(samples, vec![(120, 2.0)]);
+
+        // ON_DUPLICATE overrides the key's policy for one sample and leaves it as it was
+        let ret = backend.ts_add(
+            "t".to_string(),
+            120,
+            5.0,
+            Default::default(),
+            Some(DuplicatePolicy::Last),
+        );
+        assert_eq!(ret, Ok(120));
+        let ret = backend.ts_add("t".to_string(), 120, 6.0, Default::default(), None);
+        assert_eq!(ret, Err(crate::TimeSeriesError::DuplicateBlocked));
+        let samples = backend.ts_range("t", 0, 200, None, None).unwrap();
+        assert_eq!(samples, vec![(120, 5.0)]);
+    }
+}
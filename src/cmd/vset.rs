@@ -0,0 +1,566 @@
+use crate::cmd::{CommandError, VAdd, VCard, VEmb, VRem, VSim};
+use crate::{
+    BulkString, FilterExpr, KeyType, Quantization, RespArray, RespFrame, RespNull, SimpleError,
+    VAddOptions, VSimQuery,
+};
+
+use super::{
+    expect_type, extract_args, extract_string, parse_arg, validator_command, CommandExecutor,
+};
+
+const DEFAULT_COUNT: usize = 10;
+const DEFAULT_EF_SEARCH: usize = 100;
+// where the default width of a filtered search stops growing with COUNT, it still widens
+// past this while too few candidates match
+const MAX_FILTER_EF: usize = 100_000;
+
+impl CommandExecutor for VAdd {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        if let Err(e) = expect_type(backend, &self.key, KeyType::VectorSet) {
+            return e.into();
+        }
+        match backend.vadd(self.key, self.element, &self.vector, self.opts) {
+            Ok(added) => (added as i64).into(),
+            Err(e) => SimpleError::new(e.to_string()).into(),
+        }
+    }
+}
+
+impl CommandExecutor for VSim {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        if let Err(e) = expect_type(backend, &self.key, KeyType::VectorSet) {
+            return e.into();
+        }
+        // filtered searches explore more candidates since many of them may be rejected
+        let ef = match self.filter {
+            Some(_) => self.filter_ef.unwrap_or(
+                self.count
+                    .saturating_mul(DEFAULT_EF_SEARCH)
+                    .min(MAX_FILTER_EF),
+            ),
+            None => self.ef.unwrap_or(DEFAULT_EF_SEARCH),
+        };
+
+        match backend.vsim(&self.key, self.query, self.count, ef, self.filter.as_ref()) {
+            Ok(found) => {
+                let ret = found
+                    .into_iter()
+                    .flat_map(|(name, score)| {
+                        let name = BulkString::new(name).into();
+                        match self.with_scores {
+                            true => vec![name, score.into()],
+                            false => vec![name],
+                        }
+                    })
+                    .collect::<Vec<RespFrame>>();
+                RespArray::new(ret).into()
+            }
+            Err(e) => SimpleError::new(e.to_string()).into(),
+        }
+    }
+}
+
+impl CommandExecutor for VRem {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        if let Err(e) = expect_type(backend, &self.key, KeyType::VectorSet) {
+            return e.into();
+        }
+        (backend.vrem(&self.key, &self.element) as i64).into()
+    }
+}
+
+impl CommandExecutor for VCard {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        if let Err(e) = expect_type(backend, &self.key, KeyType::VectorSet) {
+            return e.into();
+        }
+        (backend.vcard(&self.key) as i64).into()
+    }
+}
+
+impl CommandExecutor for VEmb {
+    fn execute(self, backend: &crate::Backend) -> RespFrame {
+        if let Err(e) = expect_type(backend, &self.key, KeyType::VectorSet) {
+            return e.into();
+        }
+        match backend.vemb(&self.key, &self.element) {
+            Some(v) => {
+                let ret = v
+                    .into_iter()
+                    .map(|x| (x as f64).into())
+                    .collect::<Vec<RespFrame>>();
+                RespArray::new(ret).into()
+            }
+            None => RespFrame::Null(RespNull),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for VAdd {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["vadd"])?;
+
+        let mut args = extract_args(arr, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let vector = parse_vector(&mut args)?;
+        let element = extract_string(args.next(), "element")?;
+
+        let mut opts = VAddOptions::default();
+        while let Some(arg) = args.next() {
+            match extract_string(Some(arg), "option")?
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "cas" => {}
+                "noquant" => opts.quantization = Some(Quantization::NoQuant),
+                "q8" => opts.quantization = Some(Quantization::Q8),
+                "bin" => opts.quantization = Some(Quantization::Binary),
+                "ef" => opts.ef = Some(parse_arg(args.next(), "EF")?),
+                "m" => opts.m = Some(parse_arg(args.next(), "M")?),
+                "setattr" => opts.attributes = Some(extract_string(args.next(), "attributes")?),
+                opt => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Unknown option: {}",
+                        opt
+                    )))
+                }
+            }
+        }
+
+        Ok(VAdd {
+            key,
+            element,
+            vector,
+            opts,
+        })
+    }
+}
+
+impl TryFrom<RespArray> for VSim {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["vsim"])?;
+
+        let mut args = extract_args(arr, 1)?.into_iter().peekable();
+        let key = extract_string(args.next(), "key")?;
+
+        let is_element = matches!(
+            args.peek(),
+            Some(RespFrame::BulkString(s)) if s.eq_ignore_ascii_case(b"ele")
+        );
+        let query = match is_element {
+            true => {
+                args.next();
+                VSimQuery::Element(extract_string(args.next(), "element")?)
+            }
+            false => VSimQuery::Vector(parse_vector(&mut args)?),
+        };
+
+        let mut ret = VSim {
+            key,
+            query,
+            with_scores: false,
+            count: DEFAULT_COUNT,
+            ef: None,
+            filter: None,
+            filter_ef: None,
+        };
+
+        while let Some(arg) = args.next() {
+            match extract_string(Some(arg), "option")?
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "withscores" => ret.with_scores = true,
+                "count" => ret.count = parse_arg(args.next(), "COUNT")?,
+                "ef" => ret.ef = Some(parse_arg(args.next(), "EF")?),
+                "filter-ef" => ret.filter_ef = Some(parse_arg(args.next(), "FILTER-EF")?),
+                "filter" => {
+                    let expr = extract_string(args.next(), "FILTER")?;
+                    let expr = expr.parse::<FilterExpr>().map_err(|e| {
+                        CommandError::InvalidArgument(format!("Invalid FILTER expression: {}", e))
+                    })?;
+                    ret.filter = Some(expr);
+                }
+                opt => {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Unknown option: {}",
+                        opt
+                    )))
+                }
+            }
+        }
+
+        Ok(ret)
+    }
+}
+
+impl TryFrom<RespArray> for VRem {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["vrem"])?;
+
+        let mut args = extract_args(arr, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let element = extract_string(args.next(), "element")?;
+
+        Ok(VRem { key, element })
+    }
+}
+
+impl TryFrom<RespArray> for VCard {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["vcard"])?;
+
+        let mut args = extract_args(arr, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+
+        Ok(VCard { key })
+    }
+}
+
+impl TryFrom<RespArray> for VEmb {
+    type Error = CommandError;
+
+    fn try_from(arr: RespArray) -> Result<Self, Self::Error> {
+        validator_command(&arr, &["vemb"])?;
+
+        let mut args = extract_args(arr, 1)?.into_iter();
+        let key = extract_string(args.next(), "key")?;
+        let element = extract_string(args.next(), "element")?;
+
+        Ok(VEmb { key, element })
+    }
+}
+
+// FP32 <little-endian blob> | VALUES <num> <v1> ... <vn>
+fn parse_vector(args: &mut impl Iterator<Item = RespFrame>) -> Result<Vec<f32>, CommandError> {
+    match extract_string(args.next(), "vector format")?
+        .to_ascii_lowercase()
+        .as_str()
+    {
+        "fp32" => match args.next() {
+            Some(RespFrame::BulkString(blob)) if blob.len() % 4 == 0 && !blob.is_empty() => {
+                Ok(blob
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect())
+            }
+            _ => Err(CommandError::InvalidArgument(
+                "Invalid FP32 vector blob".to_string(),
+            )),
+        },
+        "values" => {
+            let n: usize = parse_arg(args.next(), "vector dimension")?;
+            if n == 0 {
+                return Err(CommandError::InvalidArgument(
+                    "Vector dimension must be greater than zero".to_string(),
+                ));
+            }
+            (0..n)
+                .map(|_| parse_arg(args.next(), "vector value"))
+                .collect()
+        }
+        format => Err(CommandError::InvalidArgument(format!(
+            "Unknown vector format: {}",
+            format
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespDecode;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    fn vadd(backend: &crate::Backend, element: &str, vector: &[f32], attrs: Option<&str>) {
+        let cmd = VAdd {
+            key: "points".to_string(),
+            element: element.to_string(),
+            vector: vector.to_vec(),
+            opts: VAddOptions {
+                quantization: Some(Quantization::NoQuant),
+                attributes: attrs.map(|s| s.to_string()),
+                ..Default::default()
+            },
+        };
+        cmd.execute(backend);
+    }
+
+    #[test]
+    fn test_vadd_try_from_resp_array() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"*10\r\n$4\r\nvadd\r\n$6\r\npoints\r\n$6\r\nVALUES\r\n$1\r\n2\r\n$1\r\n1\r\n$3\r\n0.5\r\n$1\r\na\r\n$7\r\nNOQUANT\r\n$7\r\nSETATTR\r\n$11\r\n{\"year\": 1}\r\n");
+
+        let arr = RespArray::decode(&mut buf)?;
+        let cmd = VAdd::try_from(arr)?;
+        assert_eq!(cmd.key, "points");
+        assert_eq!(cmd.element, "a");
+        assert_eq!(cmd.vector, vec![1.0, 0.5]);
+        assert_eq!(cmd.opts.quantization, Some(Quantization::NoQuant));
+        assert_eq!(cmd.opts.attributes.as_deref(), Some("{\"year\": 1}"));
+
+        let mut blob = 1f32.to_le_bytes().to_vec();
+        blob.extend_from_slice(&2f32.to_le_bytes());
+        let arr = RespArray::new([
+            b"vadd".into(),
+            b"points".into(),
+            b"FP32".into(),
+            blob.as_slice().into(),
+            b"b".into(),
+        ]);
+        let cmd = VAdd::try_from(arr)?;
+        assert_eq!(cmd.vector, vec![1.0, 2.0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_vsim_try_from_resp_array() -> Result<()> {
+        let arr = RespArray::new([
+            b"vsim".into(),
+            b"points".into(),
+            b"ELE".into(),
+            b"a".into(),
+            b"WITHSCORES".into(),
+            b"COUNT".into(),
+            b"3".into(),
+            b"FILTER".into(),
+            b".year > 1".into(),
+        ]);
+        let cmd = VSim::try_from(arr)?;
+        assert_eq!(cmd.query, VSimQuery::Element("a".to_string()));
+        assert!(cmd.with_scores);
+        assert_eq!(cmd.count, 3);
+        assert!(cmd.filter.is_some());
+
+        // too deep a FILTER is refused instead of overflowing the stack
+        for open in ["(", "!", "["] {
+            let expr = format!("{}.year", open.repeat(100_000));
+            let arr = RespArray::new([
+                b"vsim".into(),
+                b"points".into(),
+                b"ELE".into(),
+                b"a".into(),
+                b"FILTER".into(),
+                BulkString::new(expr).into(),
+            ]);
+            assert!(VSim::try_from(arr).is_err());
+        }
+        let expr = format!("{}.year > 1{}", "(".repeat(100), ")".repeat(100));
+        assert!(expr.parse::<FilterExpr>().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_vset_commands() {
+        let backend = crate::Backend::new();
+        vadd(&backend, "x", &[1.0, 0.0], Some(r#"{"axis": "x", "n": 1}"#));
+        vadd(&backend, "y", &[0.0, 1.0], Some(r#"{"axis": "y", "n": 2}"#));
+        vadd(
+            &backend,
+            "xy",
+            &[1.0, 1.0],
+            Some(r#"{"axis": "xy", "n": 3}"#),
+        );
+        vadd(&backend, "-x", &[-1.0, 0.0], None);
+
+        let cmd = VCard {
+            key: "points".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), 4.into());
+
+        let cmd = VSim {
+            key: "points".to_string(),
+            query: VSimQuery::Vector(vec![2.0, 0.1]),
+            with_scores: false,
+            count: 2,
+            ef: None,
+            filter: None,
+            filter_ef: None,
+        };
+        let expected = RespArray::new([b"x".into(), b"xy".into()]);
+        assert_eq!(cmd.execute(&backend), expected.into());
+
+        let cmd = VSim {
+            key: "points".to_string(),
+            query: VSimQuery::Element("x".to_string()),
+            with_scores: true,
+            count: 10,
+            ef: None,
+            filter: Some(".n >= 2 and .axis in [\"y\", \"xy\"]".parse().unwrap()),
+            filter_ef: None,
+        };
+        let RespFrame::Array(ret) = cmd.execute(&backend) else {
+            panic!("expected an array");
+        };
+        assert_eq!(ret.len(), 4);
+        assert_eq!(ret[0], b"xy".into());
+        assert_eq!(ret[2], b"y".into());
+        assert_eq!(ret[3], 0.5.into());
+
+        let cmd = VEmb {
+            key: "points".to_string(),
+            element: "y".to_string(),
+        };
+        assert_eq!(
+            cmd.execute(&backend),
+            RespArray::new([0.0.into(), 1.0.into()]).into()
+        );
+
+        let cmd = VRem {
+            key: "points".to_string(),
+            element: "x".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), 1.into());
+        let cmd = VRem {
+            key: "points".to_string(),
+            element: "x".to_string(),
+        };
+        assert_eq!(cmd.execute(&backend), 0.into());
+
+        let ret = backend.vadd(
+            "points".to_string(),
+            "z".to_string(),
+            &[1.0, 2.0, 3.0],
+            Default::default(),
+        );
+        assert_eq!(ret, Err(crate::VectorSetError::DimensionMismatch(3, 2)));
+    }
+
+    #[test]
+    fn test_vsim_recall_on_larger_set() {
+        let backend = crate::Backend::new();
+        for i in 0..500 {
+            let angle = i as f32 * std::f32::consts::TAU / 500.0;
+            let element = format!("p{}", i);
+            vadd(&backend, &element, &[angle.cos(), angle.sin()], None);
+        }
+
+        let ret = backend
+            .vsim(
+                "points",
+                VSimQuery::Element("p100".to_string()),
+                3,
+                100,
+                None,
+            )
+            .unwrap();
+        let names = ret.into_iter().map(|(n, _)| n).collect::<Vec<_>>();
+        assert_eq!(names[0], "p100");
+        assert!(names.contains(&"p99".to_string()) || names.contains(&"p101".to_string()));
+    }
+
+    #[test]
+    fn test_vrem_keeps_the_graph_searchable() {
+        let backend = crate::Backend::new();
+        let point = |i: usize| {
+            let angle = i as f32 * std::f32::consts::TAU / 120.0;
+            [angle.cos(), angle.sin()]
+        };
+        for i in 0..120 {
+            vadd(&backend, &format!("p{}", i), &point(i), None);
+        }
+        // every odd one goes, and the even ones are added again, which relinks them
+        for i in 0..120 {
+            let element = format!("p{}", i);
+            match i % 2 {
+                0 => vadd(&backend, &element, &point(i), None),
+                _ => assert!(backend.vrem("points", &element)),
+            }
+        }
+        assert_eq!(
+            VCard {
+                key: "points".to_string()
+            }
+            .execute(&backend),
+            60.into()
+        );
+
+        for query in [0, 50, 118] {
+            let ret = backend
+                .vsim(
+                    "points",
+                    VSimQuery::Element(format!("p{}", query)),
+                    3,
+                    100,
+                    None,
+                )
+                .unwrap();
+            let names = ret.into_iter().map(|(n, _)| n).collect::<Vec<_>>();
+            assert_eq!(names.len(), 3);
+            assert_eq!(names[0], format!("p{}", query));
+            assert!(names
+                .iter()
+                .all(|n| n[1..].parse::<usize>().unwrap() % 2 == 0));
+        }
+    }
+
+    #[test]
+    fn test_vsim_filter_widens_until_count_matches() {
+        let backend = crate::Backend::new();
+        for i in 0..500 {
+            let angle = i as f32 * std::f32::consts::TAU / 500.0;
+            let element = format!("p{}", i);
+            // only a handful of points, all far from the query, match the filter
+            let attrs = format!(r#"{{"rare": {}}}"#, i % 100 == 50);
+            vadd(
+                &backend,
+                &element,
+                &[angle.cos(), angle.sin()],
+                Some(&attrs),
+            );
+        }
+
+        let cmd = VSim {
+            key: "points".to_string(),
+            query: VSimQuery::Element("p0".to_string()),
+            with_scores: false,
+            count: 5,
+            ef: None,
+            filter: Some(".rare".parse().unwrap()),
+            filter_ef: Some(10),
+        };
+        let RespFrame::Array(ret) = cmd.execute(&backend) else {
+            panic!("expected an array");
+        };
+        assert_eq!(ret.len(), 5);
+
+        // a COUNT too large to multiply out is capped rather than overflowing
+        let cmd = VSim {
+            key: "points".to_string(),
+            query: VSimQuery::Element("p0".to_string()),
+            with_scores: false,
+            count: usize::MAX,
+            ef: None,
+            filter: Some(".rare".parse().unwrap()),
+            filter_ef: None,
+        };
+        let RespFrame::Array(ret) = cmd.execute(&backend) else {
+            panic!("expected an array");
+        };
+        assert_eq!(ret.len(), 5);
+    }
+
+    #[test]
+    fn test_vadd_rejected_leaves_no_key() {
+        let backend = crate::Backend::new();
+        let cmd = VAdd {
+            key: "points".to_string(),
+            element: "zero".to_string(),
+            vector: vec![0.0, 0.0],
+            opts: VAddOptions::default(),
+        };
+        assert!(matches!(cmd.execute(&backend), RespFrame::Error(_)));
+        assert_eq!(backend.key_type("points"), None);
+    }
+}
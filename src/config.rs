@@ -0,0 +1,914 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use thiserror::Error;
+
+use crate::network::split_args;
+use crate::tls::{TlsAuthClients, TlsOptions};
+use crate::{
+    glob_match, ClientType, OutputBufferLimit, OutputBufferLimits, ShutdownOptions,
+    DEFAULT_DATABASES,
+};
+
+// CONFIG REWRITE appends the options the file didn't mention after this line
+const REWRITE_MARKER: &str = "# Generated by CONFIG REWRITE";
+
+/// The server's settings, read from the command line and a redis.conf style file.
+///
+/// Each one is a parameter in [`CONFIG_PARAMS`] under its redis name, which is how CONFIG
+/// GET, SET and REWRITE see it.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub bind: Vec<String>,
+    /// 0 turns the plain TCP listener off.
+    pub port: u16,
+    pub tls_port: u16,
+    pub tls: TlsOptions,
+    pub unixsocket: Option<String>,
+    /// Octal permissions for the socket file, 0 leaves them alone.
+    pub unixsocketperm: u32,
+    pub databases: usize,
+    pub maxclients: usize,
+    /// Close clients idle this long, never when zero.
+    pub timeout: Duration,
+    /// Interval of TCP keepalive probes on client sockets, off when zero.
+    pub tcp_keepalive: Duration,
+    pub tcp_nodelay: bool,
+    /// The default user's password, which then has to AUTH.
+    pub requirepass: Option<String>,
+    /// Where users are loaded from at startup and by ACL LOAD, and ACL SAVE writes them.
+    pub aclfile: Option<PathBuf>,
+    pub acllog_max_len: usize,
+    pub proto_max_bulk_len: usize,
+    /// The most elements one array, map or set sent by a client may hold.
+    pub proto_max_multibulk_len: usize,
+    /// How deep a client's aggregates may nest.
+    pub proto_max_nesting_depth: usize,
+    pub client_query_buffer_limit: usize,
+    pub client_output_buffer_limit: OutputBufferLimits,
+    /// Whether FLUSHDB and FLUSHALL without a mode free the data in the background.
+    pub lazyfree_lazy_user_flush: bool,
+    /// How long a shutdown waits for running commands, and then for clients to close.
+    pub shutdown_timeout: Duration,
+    pub shutdown_on_sigterm: ShutdownOptions,
+    pub shutdown_on_sigint: ShutdownOptions,
+    /// Where the configuration was read from, and where CONFIG REWRITE writes it.
+    pub file: Option<PathBuf>,
+}
+
+/// One configuration parameter: its redis name and how to read and change it.
+#[derive(Debug)]
+pub struct ConfigParam {
+    pub name: &'static str,
+    /// Whether CONFIG SET may change it while the server runs.
+    pub mutable: bool,
+    /// Takes several arguments in the config file, like `bind 127.0.0.1 ::1`.
+    pub multi_arg: bool,
+    get: fn(&Config) -> String,
+    set: fn(&mut Config, &str) -> Result<(), String>,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    #[error("unknown option '{0}'")]
+    Unknown(String),
+    #[error("can't set immutable config")]
+    Immutable(String),
+    #[error("duplicate parameter")]
+    Duplicate(String),
+    #[error("{reason}")]
+    Invalid { name: String, reason: String },
+}
+
+pub static CONFIG_PARAMS: &[ConfigParam] = &[
+    ConfigParam {
+        name: "bind",
+        mutable: false,
+        multi_arg: true,
+        get: |c| c.bind.join(" "),
+        set: |c, v| {
+            c.bind = split_list(v);
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "port",
+        mutable: false,
+        multi_arg: false,
+        get: |c| c.port.to_string(),
+        set: |c, v| {
+            c.port = parse_int(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "tls-port",
+        mutable: false,
+        multi_arg: false,
+        get: |c| c.tls_port.to_string(),
+        set: |c, v| {
+            c.tls_port = parse_int(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "tls-cert-file",
+        mutable: false,
+        multi_arg: false,
+        get: |c| c.tls.cert_file.display().to_string(),
+        set: |c, v| {
+            c.tls.cert_file = v.into();
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "tls-key-file",
+        mutable: false,
+        multi_arg: false,
+        get: |c| c.tls.key_file.display().to_string(),
+        set: |c, v| {
+            c.tls.key_file = v.into();
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "tls-ca-cert-file",
+        mutable: false,
+        multi_arg: false,
+        get: |c| {
+            c.tls
+                .ca_cert_file
+                .as_ref()
+                .map(|f| f.display().to_string())
+                .unwrap_or_default()
+        },
+        set: |c, v| {
+            c.tls.ca_cert_file = (!v.is_empty()).then(|| v.into());
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "tls-auth-clients",
+        mutable: false,
+        multi_arg: false,
+        get: |c| {
+            match c.tls.auth_clients {
+                TlsAuthClients::No => "no",
+                TlsAuthClients::Optional => "optional",
+                TlsAuthClients::Yes => "yes",
+            }
+            .to_string()
+        },
+        set: |c, v| {
+            c.tls.auth_clients =
+                TlsAuthClients::parse(v).ok_or("argument must be 'yes', 'no' or 'optional'")?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "tls-protocols",
+        mutable: false,
+        multi_arg: false,
+        get: |c| c.tls.protocols.join(" "),
+        set: |c, v| {
+            c.tls.protocols = split_list(v);
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "tls-ciphersuites",
+        mutable: false,
+        multi_arg: false,
+        get: |c| c.tls.ciphersuites.join(" "),
+        set: |c, v| {
+            c.tls.ciphersuites = split_list(v);
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "unixsocket",
+        mutable: false,
+        multi_arg: false,
+        get: |c| c.unixsocket.clone().unwrap_or_default(),
+        set: |c, v| {
+            c.unixsocket = (!v.is_empty()).then(|| v.to_string());
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "unixsocketperm",
+        mutable: false,
+        multi_arg: false,
+        get: |c| format!("{:o}", c.unixsocketperm),
+        set: |c, v| {
+            c.unixsocketperm = u32::from_str_radix(v, 8)
+                .ok()
+                .filter(|perm| *perm <= 0o777)
+                .ok_or("argument must be an octal number between 0 and 777")?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "databases",
+        mutable: false,
+        multi_arg: false,
+        get: |c| c.databases.to_string(),
+        set: |c, v| match parse_int(v)? {
+            0 => Err("argument must be greater than 0".to_string()),
+            n => {
+                c.databases = n;
+                Ok(())
+            }
+        },
+    },
+    ConfigParam {
+        name: "maxclients",
+        mutable: true,
+        multi_arg: false,
+        get: |c| c.maxclients.to_string(),
+        set: |c, v| match parse_int(v)? {
+            0 => Err("argument must be greater than 0".to_string()),
+            n => {
+                c.maxclients = n;
+                Ok(())
+            }
+        },
+    },
+    ConfigParam {
+        name: "timeout",
+        mutable: true,
+        multi_arg: false,
+        get: |c| c.timeout.as_secs().to_string(),
+        set: |c, v| {
+            c.timeout = Duration::from_secs(parse_int(v)?);
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "tcp-keepalive",
+        mutable: true,
+        multi_arg: false,
+        get: |c| c.tcp_keepalive.as_secs().to_string(),
+        set: |c, v| {
+            c.tcp_keepalive = Duration::from_secs(parse_int(v)?);
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "tcp-nodelay",
+        mutable: true,
+        multi_arg: false,
+        get: |c| yes_no(c.tcp_nodelay),
+        set: |c, v| {
+            c.tcp_nodelay = parse_bool(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "requirepass",
+        mutable: true,
+        multi_arg: false,
+        get: |c| c.requirepass.clone().unwrap_or_default(),
+        set: |c, v| {
+            c.requirepass = (!v.is_empty()).then(|| v.to_string());
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "aclfile",
+        mutable: false,
+        multi_arg: false,
+        get: |c| {
+            c.aclfile
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default()
+        },
+        set: |c, v| {
+            c.aclfile = (!v.is_empty()).then(|| PathBuf::from(v));
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "acllog-max-len",
+        mutable: true,
+        multi_arg: false,
+        get: |c| c.acllog_max_len.to_string(),
+        set: |c, v| {
+            c.acllog_max_len = parse_int(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "proto-max-bulk-len",
+        mutable: true,
+        multi_arg: false,
+        get: |c| c.proto_max_bulk_len.to_string(),
+        set: |c, v| {
+            c.proto_max_bulk_len = parse_memory(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "proto-max-multibulk-len",
+        mutable: true,
+        multi_arg: false,
+        get: |c| c.proto_max_multibulk_len.to_string(),
+        set: |c, v| {
+            c.proto_max_multibulk_len = parse_int(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "proto-max-nesting-depth",
+        mutable: true,
+        multi_arg: false,
+        get: |c| c.proto_max_nesting_depth.to_string(),
+        set: |c, v| {
+            c.proto_max_nesting_depth = parse_int(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "client-query-buffer-limit",
+        mutable: true,
+        multi_arg: false,
+        get: |c| c.client_query_buffer_limit.to_string(),
+        set: |c, v| {
+            c.client_query_buffer_limit = parse_memory(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "client-output-buffer-limit",
+        mutable: true,
+        multi_arg: true,
+        get: |c| c.client_output_buffer_limit.to_string(),
+        set: |c, v| parse_output_buffer_limits(&mut c.client_output_buffer_limit, v),
+    },
+    ConfigParam {
+        name: "lazyfree-lazy-user-flush",
+        mutable: true,
+        multi_arg: false,
+        get: |c| yes_no(c.lazyfree_lazy_user_flush),
+        set: |c, v| {
+            c.lazyfree_lazy_user_flush = parse_bool(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "shutdown-timeout",
+        mutable: true,
+        multi_arg: false,
+        get: |c| c.shutdown_timeout.as_secs().to_string(),
+        set: |c, v| {
+            c.shutdown_timeout = Duration::from_secs(parse_int(v)?);
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "shutdown-on-sigterm",
+        mutable: true,
+        multi_arg: false,
+        get: |c| c.shutdown_on_sigterm.to_string(),
+        set: |c, v| {
+            c.shutdown_on_sigterm = parse_shutdown(v)?;
+            Ok(())
+        },
+    },
+    ConfigParam {
+        name: "shutdown-on-sigint",
+        mutable: true,
+        multi_arg: false,
+        get: |c| c.shutdown_on_sigint.to_string(),
+        set: |c, v| {
+            c.shutdown_on_sigint = parse_shutdown(v)?;
+            Ok(())
+        },
+    },
+];
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: vec!["0.0.0.0".to_string()],
+            port: 6379,
+            tls_port: 0,
+            tls: TlsOptions::default(),
+            unixsocket: None,
+            unixsocketperm: 0,
+            databases: DEFAULT_DATABASES,
+            maxclients: 10000,
+            timeout: Duration::ZERO,
+            tcp_keepalive: Duration::from_secs(300),
+            tcp_nodelay: true,
+            requirepass: None,
+            aclfile: None,
+            acllog_max_len: 128,
+            proto_max_bulk_len: 512 * 1024 * 1024,
+            proto_max_multibulk_len: 1024 * 1024,
+            proto_max_nesting_depth: 32,
+            client_query_buffer_limit: 1024 * 1024 * 1024,
+            client_output_buffer_limit: OutputBufferLimits::default(),
+            lazyfree_lazy_user_flush: false,
+            shutdown_timeout: Duration::from_secs(10),
+            shutdown_on_sigterm: ShutdownOptions::default(),
+            shutdown_on_sigint: ShutdownOptions::default(),
+            file: None,
+        }
+    }
+}
+
+impl ConfigParam {
+    /// Look a parameter up by name, ignoring case.
+    pub fn lookup(name: &str) -> Option<&'static ConfigParam> {
+        CONFIG_PARAMS
+            .iter()
+            .find(|param| param.name.eq_ignore_ascii_case(name))
+    }
+}
+
+impl ConfigError {
+    /// The parameter the error is about.
+    pub fn name(&self) -> &str {
+        match self {
+            ConfigError::Unknown(name)
+            | ConfigError::Immutable(name)
+            | ConfigError::Duplicate(name)
+            | ConfigError::Invalid { name, .. } => name,
+        }
+    }
+}
+
+impl Config {
+    /// Redis style arguments: an optional config file first, then `--name value...` options
+    /// that override it, e.g. `redis.conf --port 6380 --bind 127.0.0.1 ::1`.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut args = args.into_iter().peekable();
+        let mut config = Config::default();
+        if let Some(file) = args.next_if(|arg| !arg.starts_with("--")) {
+            let text = std::fs::read_to_string(&file)
+                .map_err(|e| anyhow!("can't read config file '{}': {}", file, e))?;
+            config.file = Some(std::fs::canonicalize(&file)?);
+            config.load(&text)?;
+        }
+
+        // every option takes the values up to the next one
+        let mut options = String::new();
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                return Err(anyhow!("invalid option '{}'", arg));
+            };
+            options.push_str(name);
+            while let Some(value) = args.next_if(|arg| !arg.starts_with("--")) {
+                options.push(' ');
+                options.push_str(&quote(&value));
+            }
+            options.push('\n');
+        }
+        config
+            .load(&options)
+            .map_err(|e| anyhow!("command line {}", e))?;
+        Ok(config)
+    }
+
+    /// Apply every directive in redis.conf format text; later ones win.
+    pub fn load(&mut self, text: &str) -> Result<()> {
+        for (n, line) in text.lines().enumerate() {
+            let fail = |reason: &dyn std::fmt::Display| {
+                anyhow!("line {}: '{}': {}", n + 1, line.trim(), reason)
+            };
+            let Some((param, args)) = parse_line(line).map_err(|e| fail(&e))? else {
+                continue;
+            };
+            let param = param.ok_or_else(|| fail(&"Bad directive or wrong number of arguments"))?;
+            if !param.multi_arg && args.len() != 1 {
+                return Err(fail(&"wrong number of arguments"));
+            }
+            self.set(param.name, &args.join(" "))
+                .map_err(|e| fail(&e))?;
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        ConfigParam::lookup(name).map(|param| (param.get)(self))
+    }
+
+    /// The parameters whose name matches any of the glob patterns, in table order.
+    pub fn matching(&self, patterns: &[String]) -> Vec<(&'static str, String)> {
+        CONFIG_PARAMS
+            .iter()
+            .filter(|param| {
+                patterns
+                    .iter()
+                    .any(|p| glob_match(p.as_bytes(), param.name.as_bytes(), true))
+            })
+            .map(|param| (param.name, (param.get)(self)))
+            .collect()
+    }
+
+    /// Change one parameter, mutable or not.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), ConfigError> {
+        let param = ConfigParam::lookup(name).ok_or_else(|| ConfigError::Unknown(name.into()))?;
+        (param.set)(self, value).map_err(|reason| ConfigError::Invalid {
+            name: param.name.to_string(),
+            reason,
+        })
+    }
+
+    /// Change several parameters at runtime, all of them or none.
+    pub fn update(&mut self, changes: &[(String, String)]) -> Result<(), ConfigError> {
+        let mut seen = HashSet::new();
+        let mut updated = self.clone();
+        for (name, value) in changes {
+            let param =
+                ConfigParam::lookup(name).ok_or_else(|| ConfigError::Unknown(name.clone()))?;
+            if !param.mutable {
+                return Err(ConfigError::Immutable(param.name.to_string()));
+            }
+            if !seen.insert(param.name) {
+                return Err(ConfigError::Duplicate(param.name.to_string()));
+            }
+            updated.set(param.name, value)?;
+        }
+        *self = updated;
+        Ok(())
+    }
+
+    /// Write the current settings back to the config file, see [`Config::rewrite_text`].
+    pub fn rewrite(&self, path: &Path) -> std::io::Result<()> {
+        let old = match std::fs::read_to_string(path) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            old => old?,
+        };
+        // write a sibling and rename it over, so a crash never leaves half a file
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let tmp = path.with_file_name(format!(".{}.rewrite", name));
+        std::fs::write(&tmp, self.rewrite_text(&old))?;
+        std::fs::rename(&tmp, path)
+    }
+
+    /// `old` with every known directive set to its current value, like redis' CONFIG
+    /// REWRITE: comments and unknown lines stay, repeated directives collapse into the
+    /// first, and settings that moved off their default are appended at the end.
+    pub fn rewrite_text(&self, old: &str) -> String {
+        let mut written = HashSet::new();
+        let mut lines = vec![];
+        let mut has_marker = false;
+        for line in old.lines() {
+            has_marker |= line.trim() == REWRITE_MARKER;
+            match parse_line(line) {
+                Ok(Some((Some(param), _))) => {
+                    if written.insert(param.name) {
+                        lines.push(self.directive(param));
+                    }
+                }
+                _ => lines.push(line.to_string()),
+            }
+        }
+
+        let defaults = Config::default();
+        let added = CONFIG_PARAMS
+            .iter()
+            .filter(|param| !written.contains(param.name))
+            .filter(|param| (param.get)(self) != (param.get)(&defaults))
+            .map(|param| self.directive(param))
+            .collect::<Vec<_>>();
+        if !added.is_empty() && !has_marker {
+            lines.push(REWRITE_MARKER.to_string());
+        }
+        lines.extend(added);
+
+        let mut text = lines.join("\n");
+        text.push('\n');
+        text
+    }
+
+    fn directive(&self, param: &ConfigParam) -> String {
+        let value = (param.get)(self);
+        let args = match param.multi_arg {
+            true => split_list(&value),
+            false => vec![value],
+        };
+        std::iter::once(param.name.to_string())
+            .chain(args.iter().map(|arg| quote(arg)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+// a directive's parameter, `None` if unknown, and its arguments; `None` for blank and
+// comment lines
+type Directive = Option<(Option<&'static ConfigParam>, Vec<String>)>;
+
+fn parse_line(line: &str) -> Result<Directive, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let mut args = split_args(line.as_bytes())
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|arg| String::from_utf8_lossy(&arg).into_owned());
+    let Some(name) = args.next() else {
+        return Ok(None);
+    };
+    Ok(Some((ConfigParam::lookup(&name), args.collect())))
+}
+
+// quoted the way split_args reads it back, only when it has to be
+fn quote(value: &str) -> String {
+    let plain = !value.is_empty()
+        && value
+            .bytes()
+            .all(|c| c.is_ascii_graphic() && c != b'"' && c != b'\'' && c != b'\\');
+    if plain {
+        return value.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in value.bytes() {
+        match c {
+            b'\\' => quoted.push_str("\\\\"),
+            b'"' => quoted.push_str("\\\""),
+            b'\n' => quoted.push_str("\\n"),
+            b'\r' => quoted.push_str("\\r"),
+            b'\t' => quoted.push_str("\\t"),
+            c if c == b' ' || c.is_ascii_graphic() => quoted.push(c as char),
+            c => quoted.push_str(&format!("\\x{:02x}", c)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value.split_whitespace().map(String::from).collect()
+}
+
+fn yes_no(value: bool) -> String {
+    match value {
+        true => "yes",
+        false => "no",
+    }
+    .to_string()
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err("argument must be 'yes' or 'no'".to_string()),
+    }
+}
+
+fn parse_int<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| "argument couldn't be parsed into an integer".to_string())
+}
+
+fn parse_shutdown(value: &str) -> Result<ShutdownOptions, String> {
+    ShutdownOptions::parse(value).ok_or_else(|| {
+        "argument must be 'default' or a combination of 'save', 'nosave', 'now' and 'force'"
+            .to_string()
+    })
+}
+
+// "<class> <hard> <soft> <soft seconds>" repeated; classes left out keep their limits
+fn parse_output_buffer_limits(limits: &mut OutputBufferLimits, value: &str) -> Result<(), String> {
+    let args = split_list(value);
+    if args.is_empty() || !args.len().is_multiple_of(4) {
+        return Err("Wrong number of arguments in buffer limit configuration.".to_string());
+    }
+    let mut parsed = *limits;
+    for class in args.chunks(4) {
+        let kind = ClientType::parse(&class[0])
+            .ok_or("Invalid client class specified in buffer limit configuration.")?;
+        *parsed.get_mut(kind) = OutputBufferLimit {
+            hard: parse_memory(&class[1])?,
+            soft: parse_memory(&class[2])?,
+            soft_seconds: Duration::from_secs(parse_int(&class[3])?),
+        };
+    }
+    *limits = parsed;
+    Ok(())
+}
+
+// redis' memtoll: "1k" is 1000 bytes, "1kb" is 1024
+fn parse_memory(value: &str) -> Result<usize, String> {
+    let lower = value.to_ascii_lowercase();
+    let split = lower
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(lower.len());
+    let (number, unit) = lower.split_at(split);
+    let unit = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return Err("argument must be a memory value".to_string()),
+    };
+    number
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .ok_or_else(|| "argument must be a memory value".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_load() -> Result<()> {
+        let mut config = Config::default();
+        config.load(
+            "# a comment\n\
+             \n\
+             bind 127.0.0.1 ::1\n\
+             PORT 6380\n\
+             tls-protocols \"TLSv1.2 TLSv1.3\"\n\
+             unixsocketperm 700\n\
+             proto-max-bulk-len 1mb\n\
+             proto-max-nesting-depth 8\n\
+             lazyfree-lazy-user-flush yes\n",
+        )?;
+        assert_eq!(config.bind, vec!["127.0.0.1", "::1"]);
+        assert_eq!(config.port, 6380);
+        assert_eq!(config.tls.protocols, vec!["TLSv1.2", "TLSv1.3"]);
+        assert_eq!(config.unixsocketperm, 0o700);
+        assert_eq!(config.proto_max_bulk_len, 1024 * 1024);
+        assert_eq!(config.proto_max_nesting_depth, 8);
+        assert!(config.lazyfree_lazy_user_flush);
+        assert_eq!(config.get("unixsocketperm").as_deref(), Some("700"));
+
+        let err = config.load("port 6379\nport abc\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2: 'port abc': argument couldn't be parsed into an integer"
+        );
+        let err = config.load("nosuchthing 1\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 1: 'nosuchthing 1': Bad directive or wrong number of arguments"
+        );
+        assert!(config.load("port 1 2\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_from_args() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("simple-redis-{}.conf", std::process::id()));
+        std::fs::write(&path, "port 7000\ndatabases 4\n")?;
+        let args = [
+            path.display().to_string(),
+            "--port".to_string(),
+            "7001".to_string(),
+            "--bind".to_string(),
+            "127.0.0.1".to_string(),
+            "::1".to_string(),
+            "--tls-protocols".to_string(),
+            "TLSv1.2 TLSv1.3".to_string(),
+        ];
+        let config = Config::from_args(args)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(config.port, 7001);
+        assert_eq!(config.databases, 4);
+        assert_eq!(config.bind, vec!["127.0.0.1", "::1"]);
+        assert_eq!(config.tls.protocols, vec!["TLSv1.2", "TLSv1.3"]);
+        assert!(config.file.is_some());
+
+        let err = Config::from_args(["--port".to_string()]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "command line line 1: 'port': wrong number of arguments"
+        );
+        assert!(Config::from_args(["--nope".to_string(), "1".to_string()]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_update() {
+        let mut config = Config::default();
+        let change = |name: &str, value: &str| (name.to_string(), value.to_string());
+
+        assert_eq!(
+            config.update(&[change("port", "1")]),
+            Err(ConfigError::Immutable("port".to_string()))
+        );
+        assert_eq!(
+            config.update(&[change("nope", "1")]),
+            Err(ConfigError::Unknown("nope".to_string()))
+        );
+        // nothing changes when any of them fails
+        let err = config
+            .update(&[
+                change("lazyfree-lazy-user-flush", "yes"),
+                change("proto-max-bulk-len", "lots"),
+            ])
+            .unwrap_err();
+        assert_eq!(err.name(), "proto-max-bulk-len");
+        assert_eq!(err.to_string(), "argument must be a memory value");
+        assert!(!config.lazyfree_lazy_user_flush);
+        assert_eq!(
+            config.update(&[
+                change("lazyfree-lazy-user-flush", "yes"),
+                change("LAZYFREE-lazy-user-flush", "no"),
+            ]),
+            Err(ConfigError::Duplicate(
+                "lazyfree-lazy-user-flush".to_string()
+            ))
+        );
+
+        config
+            .update(&[
+                change("lazyfree-lazy-user-flush", "yes"),
+                change("proto-max-bulk-len", "100kb"),
+            ])
+            .unwrap();
+        assert!(config.lazyfree_lazy_user_flush);
+        assert_eq!(config.proto_max_bulk_len, 100 * 1024);
+
+        // only the classes given change
+        config
+            .update(&[change(
+                "client-output-buffer-limit",
+                "normal 1mb 512kb 10 replica 0 0 0",
+            )])
+            .unwrap();
+        assert_eq!(
+            config.get("client-output-buffer-limit").unwrap(),
+            "normal 1048576 524288 10 slave 0 0 0 pubsub 33554432 8388608 60"
+        );
+        for (value, reason) in [
+            (
+                "normal 1mb 512kb",
+                "Wrong number of arguments in buffer limit configuration.",
+            ),
+            (
+                "master 0 0 0",
+                "Invalid client class specified in buffer limit configuration.",
+            ),
+        ] {
+            let err = config
+                .update(&[change("client-output-buffer-limit", value)])
+                .unwrap_err();
+            assert_eq!(err.to_string(), reason);
+        }
+    }
+
+    #[test]
+    fn test_config_matching() {
+        let config = Config::default();
+        let patterns = ["tls-*-file".to_string(), "PORT".to_string()];
+        let names = config
+            .matching(&patterns)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec!["port", "tls-cert-file", "tls-key-file", "tls-ca-cert-file"]
+        );
+    }
+
+    #[test]
+    fn test_config_rewrite_text() -> Result<()> {
+        let old = "# the port\n\
+                   port 7000\n\
+                   \n\
+                   # unknown lines are kept\n\
+                   loglevel notice\n\
+                   port 7001\n\
+                   databases 16\n";
+        let mut config = Config::default();
+        config.load("port 7001\nbind 127.0.0.1 ::1\n")?;
+        config.set("unixsocket", "/tmp/redis sock").unwrap();
+
+        let text = config.rewrite_text(old);
+        assert_eq!(
+            text,
+            "# the port\n\
+             port 7001\n\
+             \n\
+             # unknown lines are kept\n\
+             loglevel notice\n\
+             databases 16\n\
+             # Generated by CONFIG REWRITE\n\
+             bind 127.0.0.1 ::1\n\
+             unixsocket \"/tmp/redis sock\"\n"
+        );
+        // rewriting again changes nothing, and the quoted path reads back whole
+        assert_eq!(config.rewrite_text(&text), text);
+        let mut reloaded = Config::default();
+        reloaded.load(&text.replace("loglevel notice\n", ""))?;
+        assert_eq!(reloaded.unixsocket.as_deref(), Some("/tmp/redis sock"));
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+/// Match `s` against a redis glob pattern (`*`, `?`, `[a-z]`, `[^abc]` and `\` escapes),
+/// as KEYS, CONFIG GET and PSUBSCRIBE do.
+pub fn glob_match(pattern: &[u8], s: &[u8], nocase: bool) -> bool {
+    let eq = |a: u8, b: u8| match nocase {
+        true => a.eq_ignore_ascii_case(&b),
+        false => a == b,
+    };
+
+    let (mut p, mut i) = (0, 0);
+    while p < pattern.len() {
+        match pattern[p] {
+            b'*' => {
+                while pattern.get(p + 1) == Some(&b'*') {
+                    p += 1;
+                }
+                if p + 1 == pattern.len() {
+                    return true;
+                }
+                return (i..=s.len())
+                    .any(|start| glob_match(&pattern[p + 1..], &s[start..], nocase));
+            }
+            b'?' => {
+                if i == s.len() {
+                    return false;
+                }
+                i += 1;
+            }
+            b'[' => {
+                let Some(&c) = s.get(i) else {
+                    return false;
+                };
+                p += 1;
+                let not = pattern.get(p) == Some(&b'^');
+                if not {
+                    p += 1;
+                }
+                let mut matched = false;
+                while p < pattern.len() && pattern[p] != b']' {
+                    if pattern[p] == b'\\' && p + 1 < pattern.len() {
+                        p += 1;
+                        matched |= eq(pattern[p], c);
+                    } else if pattern.get(p + 1) == Some(&b'-') && p + 2 < pattern.len() {
+                        let (mut start, mut end, mut c) = (pattern[p], pattern[p + 2], c);
+                        if nocase {
+                            start = start.to_ascii_lowercase();
+                            end = end.to_ascii_lowercase();
+                            c = c.to_ascii_lowercase();
+                        }
+                        if start > end {
+                            std::mem::swap(&mut start, &mut end);
+                        }
+                        matched |= (start..=end).contains(&c);
+                        p += 2;
+                    } else {
+                        matched |= eq(pattern[p], c);
+                    }
+                    p += 1;
+                }
+                if matched == not {
+                    return false;
+                }
+                i += 1;
+            }
+            b'\\' if p + 1 < pattern.len() => {
+                p += 1;
+                if i == s.len() || !eq(pattern[p], s[i]) {
+                    return false;
+                }
+                i += 1;
+            }
+            c => {
+                if i == s.len() || !eq(c, s[i]) {
+                    return false;
+                }
+                i += 1;
+            }
+        }
+        p += 1;
+    }
+    i == s.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        let cases: &[(&str, &str, bool)] = &[
+            ("*", "", true),
+            ("*", "anything", true),
+            ("h?llo", "hello", true),
+            ("h?llo", "hllo", false),
+            ("h*llo", "heeeello", true),
+            ("h*llo", "hellox", false),
+            ("h[ae]llo", "hallo", true),
+            ("h[ae]llo", "hillo", false),
+            ("h[^e]llo", "hallo", true),
+            ("h[^e]llo", "hello", false),
+            ("h[a-b]llo", "hbllo", true),
+            ("h[b-a]llo", "hbllo", true),
+            ("h[a-b]llo", "hcllo", false),
+            ("h\\*llo", "h*llo", true),
+            ("h\\*llo", "hello", false),
+            ("news.*", "news.tech", true),
+            ("tls-*", "port", false),
+            ("*max-*-len", "proto-max-bulk-len", true),
+        ];
+        for (pattern, s, expected) in cases {
+            assert_eq!(
+                glob_match(pattern.as_bytes(), s.as_bytes(), false),
+                *expected,
+                "{} against {}",
+                pattern,
+                s
+            );
+        }
+        assert!(!glob_match(b"PORT", b"port", false));
+        assert!(glob_match(b"PORT", b"port", true));
+        assert!(glob_match(b"[A-C]*", b"bind", true));
+    }
+}
@@ -1,7 +1,22 @@
+mod acl;
 mod backend;
+mod client;
 pub mod cmd;
+mod config;
+mod glob;
 pub mod network;
+mod pubsub;
 mod resp;
+mod shutdown;
+mod slot;
+pub mod tls;
 
+pub use acl::*;
 pub use backend::*;
+pub use client::*;
+pub use config::*;
+pub use glob::*;
+pub use pubsub::*;
 pub use resp::*;
+pub use shutdown::*;
+pub use slot::*;
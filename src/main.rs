@@ -1,32 +1,175 @@
-use anyhow::Result;
-use simple_redis::network;
-use simple_redis::Backend;
-use tokio::net::TcpListener;
+use anyhow::{anyhow, Result};
+use simple_redis::network::{self, ClientStream};
+use simple_redis::tls::TlsContext;
+use simple_redis::{Backend, Config, ShutdownOptions};
+use std::fmt::Display;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream, UnixListener};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::task::JoinSet;
 use tracing::{info, warn};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
-    let addr = "0.0.0.0:6379";
-    info!("Simple-redis-server is listening on {}", addr);
-    let listener = TcpListener::bind(addr).await?;
+    // like redis-server: an optional config file, then --name value overrides
+    let config = Config::from_args(std::env::args().skip(1))?;
+    if let Some(file) = &config.file {
+        info!("Configuration loaded from {}", file.display());
+    }
+    let backend = Backend::with_config(config.clone());
+    if let Some(path) = &config.aclfile {
+        backend.acl().load_file(path)?;
+        info!("Users loaded from {}", path.display());
+    }
+    let mut listeners = JoinSet::new();
+
+    if config.port != 0 {
+        for addr in &config.bind {
+            let listener = bind_tcp(addr, config.port).await?;
+            listeners.spawn(accept_tcp(listener, backend.clone()));
+        }
+    }
+    if config.tls_port != 0 {
+        let tls = Arc::new(TlsContext::new(config.tls)?);
+        listeners.spawn(reload_tls_on_sighup(tls.clone()));
+        for addr in &config.bind {
+            let listener = bind_tcp(addr, config.tls_port).await?;
+            listeners.spawn(accept_tls(listener, tls.clone(), backend.clone()));
+        }
+    }
+    if let Some(path) = &config.unixsocket {
+        info!("Simple-redis-server is listening on {}", path);
+        let perm = (config.unixsocketperm != 0).then_some(config.unixsocketperm);
+        let unix = network::bind_unix(path, perm)?;
+        listeners.spawn(accept_unix(unix, path.clone(), backend.clone()));
+    }
+    if listeners.is_empty() {
+        return Err(anyhow!(
+            "nothing to listen on, set a port, tls-port or unixsocket"
+        ));
+    }
+    listeners.spawn(shutdown_on_signal(backend.clone()));
+
+    // the accept loops only return on error, a shutdown ends the server
+    tokio::select! {
+        res = async {
+            while let Some(res) = listeners.join_next().await {
+                res??;
+            }
+            Ok::<_, anyhow::Error>(())
+        } => res?,
+        res = backend.shutdown().run(&backend) => res?,
+    }
+    if let Some(path) = &config.unixsocket {
+        let _ = std::fs::remove_file(path);
+    }
+    Ok(())
+}
 
-    let backend = Backend::new();
+// SIGTERM and SIGINT go through the same shutdown as SHUTDOWN; a second SIGINT while one
+// is pending stops waiting and ignores errors
+async fn shutdown_on_signal(backend: Backend) -> Result<()> {
+    let mut term = signal(SignalKind::terminate())?;
+    let mut int = signal(SignalKind::interrupt())?;
+    loop {
+        let options = tokio::select! {
+            _ = term.recv() => {
+                info!("Received SIGTERM scheduling shutdown...");
+                backend.config().shutdown_on_sigterm
+            }
+            _ = int.recv() => {
+                info!("Received SIGINT scheduling shutdown...");
+                match backend.shutdown().accepting() {
+                    true => backend.config().shutdown_on_sigint,
+                    false => ShutdownOptions {
+                        now: true,
+                        force: true,
+                        ..Default::default()
+                    },
+                }
+            }
+        };
+        backend.shutdown().request(options, None);
+    }
+}
+
+async fn bind_tcp(addr: &str, port: u16) -> Result<TcpListener> {
+    // IPv6 addresses need brackets once the port is on
+    let addr = match addr.contains(':') {
+        true => format!("[{}]:{}", addr, port),
+        false => format!("{}:{}", addr, port),
+    };
+    info!("Simple-redis-server is listening on {}", addr);
+    Ok(TcpListener::bind(addr).await?)
+}
 
+async fn accept_tcp(listener: TcpListener, backend: Backend) -> Result<()> {
     loop {
         let (stream, raddr) = listener.accept().await?;
-        info!("Accepted connection from {}", raddr);
-        let cloned_backend = backend.clone();
+        configure_tcp(&stream, &backend);
+        spawn_client(stream, raddr, backend.clone());
+    }
+}
 
+async fn accept_tls(listener: TcpListener, tls: Arc<TlsContext>, backend: Backend) -> Result<()> {
+    loop {
+        let (stream, raddr) = listener.accept().await?;
+        configure_tcp(&stream, &backend);
+        let (tls, backend) = (tls.clone(), backend.clone());
+        // handshake off the accept loop so a slow client doesn't hold up the others
         tokio::spawn(async move {
-            match network::stream_handler(stream, cloned_backend).await {
-                Ok(_) => {
-                    info!("Connection from {} exited", raddr);
-                }
-                Err(e) => {
-                    warn!("handle error for {}: {:?}", raddr, e);
-                }
+            match tls.accept(stream).await {
+                Ok(stream) => spawn_client(stream, raddr, backend),
+                Err(e) => warn!("TLS handshake with {} failed: {:?}", raddr, e),
             }
         });
     }
 }
+
+// a socket option that can't be set isn't worth refusing the client over
+fn configure_tcp(stream: &TcpStream, backend: &Backend) {
+    if let Err(e) = network::configure_tcp(stream, &backend.config()) {
+        warn!("failed to set socket options: {:?}", e);
+    }
+}
+
+// new certificates apply to connections accepted afterwards
+async fn reload_tls_on_sighup(tls: Arc<TlsContext>) -> Result<()> {
+    let mut hangup = signal(SignalKind::hangup())?;
+    while hangup.recv().await.is_some() {
+        match tls.reload() {
+            Ok(()) => info!("Reloaded TLS certificates"),
+            Err(e) => warn!("failed to reload TLS certificates: {:?}", e),
+        }
+    }
+    Ok(())
+}
+
+async fn accept_unix(listener: UnixListener, path: String, backend: Backend) -> Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        spawn_client(stream, path.clone(), backend.clone());
+    }
+}
+
+fn spawn_client<S>(stream: S, raddr: impl Display + Send + 'static, backend: Backend)
+where
+    S: ClientStream + Send + 'static,
+{
+    if !backend.shutdown().accepting() {
+        info!("Refused connection from {} while shutting down", raddr);
+        return;
+    }
+    info!("Accepted connection from {}", raddr);
+    tokio::spawn(async move {
+        match network::stream_handler(stream, backend).await {
+            Ok(_) => {
+                info!("Connection from {} exited", raddr);
+            }
+            Err(e) => {
+                warn!("handle error for {}: {:?}", raddr, e);
+            }
+        }
+    });
+}
@@ -1,26 +1,66 @@
 use crate::{
-    cmd::{Command, CommandExecutor},
-    Backend, RespDecode, RespEncode, RespError,
+    cmd::{resolve_command, Command, CommandExecutor, CommandFlag},
+    AclDenial, Backend, BulkString, ClientHandle, ClientInfo, Config, ProtocolLimits, RespArray,
+    RespDecoder, RespEncode, RespError, RespVersion, SimpleError, Subscriptions,
 };
 use anyhow::Result;
-use futures::SinkExt;
+use futures::{future::poll_fn, Sink, SinkExt, Stream};
+use socket2::{SockRef, TcpKeepalive};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpStream;
-use tokio_stream::StreamExt;
-use tracing::info;
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info, trace, warn};
 
 use tokio_util::codec::{Decoder, Encoder, Framed};
 
 use crate::RespFrame;
 
-#[derive(Debug)]
-struct RespFrameCodec;
+// like redis' PROTO_INLINE_MAX_SIZE, an inline command must fit in a line this long
+const MAX_INLINE_LEN: usize = 64 * 1024;
+
+// replies queued past this are written out even if the pipeline has not been drained
+const OUTPUT_HIGH_WATER: usize = 64 * 1024;
+
+// all a RESP2 client may run while subscribed, as its replies are mixed in with messages
+const SUBSCRIBED_MODE_COMMANDS: &[&str] = &[
+    "subscribe",
+    "ssubscribe",
+    "psubscribe",
+    "unsubscribe",
+    "sunsubscribe",
+    "punsubscribe",
+    "ping",
+    "quit",
+    "reset",
+];
+
+#[derive(Debug, Default)]
+struct RespFrameCodec {
+    decoder: RespDecoder,
+}
+
+impl RespFrameCodec {
+    fn new(limits: ProtocolLimits) -> Self {
+        Self {
+            decoder: RespDecoder::with_limits(limits),
+        }
+    }
+}
 
 impl Encoder<RespFrame> for RespFrameCodec {
     type Error = anyhow::Error;
 
     fn encode(&mut self, item: RespFrame, dst: &mut bytes::BytesMut) -> Result<()> {
-        let encoded = item.encode();
-        dst.extend_from_slice(&encoded);
+        dst.reserve(item.encoded_len());
+        item.encode_to(dst);
         Ok(())
     }
 }
@@ -30,10 +70,165 @@ impl Decoder for RespFrameCodec {
     type Error = anyhow::Error;
 
     fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Self::Item>> {
-        match RespFrame::decode(src) {
-            Ok(frame) => Ok(Some(frame)),
-            Err(RespError::NotComplete) => Ok(None),
-            Err(e) => Err(e.into()),
+        // like redis, anything that doesn't start as a multibulk is an inline command
+        while !src.is_empty() && src[0] != b'*' {
+            let Some(args) = decode_inline(src)? else {
+                return Ok(None);
+            };
+            // blank lines are ignored, as telnet users tend to hit enter
+            if !args.is_empty() {
+                let args = args
+                    .into_iter()
+                    .map(|arg| BulkString::new(arg).into())
+                    .collect::<Vec<RespFrame>>();
+                return Ok(Some(RespArray::new(args).into()));
+            }
+        }
+
+        Ok(self.decoder.decode(src)?)
+    }
+}
+
+fn decode_inline(src: &mut bytes::BytesMut) -> Result<Option<Vec<Vec<u8>>>, RespError> {
+    let end = src
+        .iter()
+        .take(MAX_INLINE_LEN + 1)
+        .position(|b| *b == b'\n');
+    let Some(end) = end else {
+        if src.len() > MAX_INLINE_LEN {
+            return Err(RespError::LimitExceeded("too big inline request"));
+        }
+        return Ok(None);
+    };
+    let line = src.split_to(end + 1);
+    let line = line[..end].strip_suffix(b"\r").unwrap_or(&line[..end]);
+    split_args(line).map(Some)
+}
+
+/// Split an inline command line with the same quoting rules as redis-cli (`sdssplitargs`).
+pub(crate) fn split_args(line: &[u8]) -> Result<Vec<Vec<u8>>, RespError> {
+    let mut args = vec![];
+    let mut i = 0;
+
+    loop {
+        while i < line.len() && line[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i == line.len() {
+            return Ok(args);
+        }
+
+        let mut arg = vec![];
+        let mut in_double = false;
+        let mut in_single = false;
+        loop {
+            let c = line.get(i).copied();
+            if in_double {
+                match c {
+                    None => return Err(unbalanced_quotes()),
+                    Some(b'\\')
+                        if line.get(i + 1) == Some(&b'x') && hex_pair(line, i + 2).is_some() =>
+                    {
+                        arg.push(hex_pair(line, i + 2).unwrap_or_default());
+                        i += 3;
+                    }
+                    Some(b'\\') if i + 1 < line.len() => {
+                        i += 1;
+                        arg.push(match line[i] {
+                            b'n' => b'\n',
+                            b'r' => b'\r',
+                            b't' => b'\t',
+                            b'b' => 0x08,
+                            b'a' => 0x07,
+                            other => other,
+                        });
+                    }
+                    Some(b'"') => {
+                        // the closing quote must be followed by a space or nothing at all
+                        if line.get(i + 1).is_some_and(|c| !c.is_ascii_whitespace()) {
+                            return Err(unbalanced_quotes());
+                        }
+                        i += 1;
+                        break;
+                    }
+                    Some(c) => arg.push(c),
+                }
+            } else if in_single {
+                match c {
+                    None => return Err(unbalanced_quotes()),
+                    Some(b'\\') if line.get(i + 1) == Some(&b'\'') => {
+                        i += 1;
+                        arg.push(b'\'');
+                    }
+                    Some(b'\'') => {
+                        if line.get(i + 1).is_some_and(|c| !c.is_ascii_whitespace()) {
+                            return Err(unbalanced_quotes());
+                        }
+                        i += 1;
+                        break;
+                    }
+                    Some(c) => arg.push(c),
+                }
+            } else {
+                match c {
+                    None => break,
+                    Some(c) if c.is_ascii_whitespace() => break,
+                    Some(b'"') => in_double = true,
+                    Some(b'\'') => in_single = true,
+                    Some(c) => arg.push(c),
+                }
+            }
+            i += 1;
+        }
+        args.push(arg);
+    }
+}
+
+fn hex_pair(line: &[u8], i: usize) -> Option<u8> {
+    let hex = line.get(i..i + 2)?;
+    u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()
+}
+
+fn unbalanced_quotes() -> RespError {
+    RespError::InvalidFrame("unbalanced quotes in request".to_string())
+}
+
+/// Per-connection context handed to commands: what the client negotiated, and its entry in
+/// the server-wide client registry.
+#[derive(Debug, Default)]
+pub struct ConnectionState {
+    pub info: ClientInfo,
+    handle: Option<ClientHandle>,
+    /// Set by a command that answers later, like SHUTDOWN; what it returned is not sent and
+    /// the client gets this reply instead, or is hung up on if the sender goes away.
+    pub(crate) blocked: Option<oneshot::Receiver<RespFrame>>,
+    /// Logged in as `info.user`; until then only commands flagged no_auth run.
+    pub(crate) authenticated: bool,
+    pub(crate) subscriptions: Subscriptions,
+    /// Set by a command that answers with several frames, like SUBSCRIBE with one per
+    /// channel; these are sent in place of what it returned.
+    pub(crate) replies: Vec<RespFrame>,
+}
+
+impl Deref for ConnectionState {
+    type Target = ClientInfo;
+
+    fn deref(&self) -> &Self::Target {
+        &self.info
+    }
+}
+
+impl DerefMut for ConnectionState {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.info
+    }
+}
+
+impl ConnectionState {
+    // let CLIENT LIST on other connections see what the last command changed
+    fn publish(&self) {
+        if let Some(handle) = &self.handle {
+            handle.publish(&self.info);
         }
     }
 }
@@ -46,36 +241,1109 @@ struct RedisRequest {
 
 #[derive(Debug)]
 struct RedisResponse {
-    frame: RespFrame,
+    frames: Vec<RespFrame>,
+}
+
+/// Per-connection tuning, the defaults match a stock server.
+#[derive(Debug, Clone)]
+pub struct ConnectionOptions {
+    pub limits: ProtocolLimits,
+    /// Queued reply bytes that force a write in the middle of a pipelined batch; 0 writes
+    /// every reply as soon as it is produced.
+    pub output_high_water: usize,
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        Self {
+            limits: ProtocolLimits::default(),
+            output_high_water: OUTPUT_HIGH_WATER,
+        }
+    }
+}
+
+impl From<&Config> for ConnectionOptions {
+    fn from(config: &Config) -> Self {
+        Self {
+            limits: ProtocolLimits {
+                max_bulk_len: config.proto_max_bulk_len,
+                max_multibulk_len: config.proto_max_multibulk_len,
+                max_depth: config.proto_max_nesting_depth,
+                max_query_buffer: config.client_query_buffer_limit,
+            },
+            ..Default::default()
+        }
+    }
+}
+
+/// A stream clients talk to the server over.
+pub trait ClientStream: AsyncRead + AsyncWrite + Unpin {
+    /// The peer and local addresses, as CLIENT LIST shows them.
+    fn addrs(&self) -> Result<(String, String)>;
+
+    fn is_unix_socket(&self) -> bool {
+        false
+    }
+}
+
+impl ClientStream for TcpStream {
+    fn addrs(&self) -> Result<(String, String)> {
+        Ok((
+            self.peer_addr()?.to_string(),
+            self.local_addr()?.to_string(),
+        ))
+    }
+}
+
+// clients are unnamed, so like redis both ends show as the socket path
+#[cfg(unix)]
+impl ClientStream for UnixStream {
+    fn addrs(&self) -> Result<(String, String)> {
+        let addr = self.local_addr()?;
+        let path = addr
+            .as_pathname()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        Ok((format!("{}:0", path), format!("{}:0", path)))
+    }
+
+    fn is_unix_socket(&self) -> bool {
+        true
+    }
+}
+
+/// Listen on a Unix socket, replacing a stale socket file and applying `perm` (e.g. 0o700).
+#[cfg(unix)]
+pub fn bind_unix(path: impl AsRef<std::path::Path>, perm: Option<u32>) -> Result<UnixListener> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = path.as_ref();
+    // a previous run that died leaves its socket file behind
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    let listener = UnixListener::bind(path)?;
+    if let Some(perm) = perm {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(perm))?;
+    }
+    Ok(listener)
 }
 
-pub async fn stream_handler(stream: TcpStream, backend: Backend) -> Result<()> {
+/// Apply the tcp-nodelay and tcp-keepalive settings to an accepted socket.
+pub fn configure_tcp(stream: &TcpStream, config: &Config) -> Result<()> {
+    stream.set_nodelay(config.tcp_nodelay)?;
+    let sock = SockRef::from(stream);
+    if config.tcp_keepalive.is_zero() {
+        sock.set_keepalive(false)?;
+        return Ok(());
+    }
+    // like redis, probe every third of the idle time and give up after three
+    let keepalive = TcpKeepalive::new()
+        .with_time(config.tcp_keepalive)
+        .with_interval((config.tcp_keepalive / 3).max(Duration::from_secs(1)));
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+    let keepalive = keepalive.with_retries(3);
+    sock.set_tcp_keepalive(&keepalive)?;
+    Ok(())
+}
+
+/// Serve a client with the options the configuration asks for at the time it connects.
+pub async fn stream_handler<S: ClientStream>(stream: S, backend: Backend) -> Result<()> {
+    let options = ConnectionOptions::from(&*backend.config());
+    stream_handler_with_options(stream, backend, options).await
+}
+
+pub async fn stream_handler_with_options<S: ClientStream>(
+    stream: S,
+    backend: Backend,
+    options: ConnectionOptions,
+) -> Result<()> {
+    let (addr, laddr) = stream.addrs()?;
+    let (mut info, handle) = backend.clients().register(&addr, &laddr);
+    backend
+        .stats()
+        .connections_received
+        .fetch_add(1, Ordering::Relaxed);
+    info.flags.unix_socket = stream.is_unix_socket();
+    handle.publish(&info);
+    let (sender, mut messages) = mpsc::unbounded_channel();
+    let mut state = ConnectionState {
+        info,
+        handle: Some(handle.clone()),
+        blocked: None,
+        authenticated: !backend.acl().auth_required(),
+        subscriptions: Subscriptions {
+            sender: Some(sender),
+            ..Default::default()
+        },
+        replies: vec![],
+    };
+
     //how to get a frame from a stream
-    let mut framed = Framed::new(stream, RespFrameCodec);
+    let mut framed = Framed::new(stream, RespFrameCodec::new(options.limits));
+    // counted after registering, so clients connecting together can't all slip in
+    if backend.clients().len() > backend.config().maxclients {
+        backend
+            .stats()
+            .rejected_connections
+            .fetch_add(1, Ordering::Relaxed);
+        backend.clients().unregister(state.id);
+        let reply = SimpleError::new("ERR max number of clients reached");
+        framed.send(reply.into()).await?;
+        return Ok(());
+    }
+    framed.set_backpressure_boundary(options.output_high_water);
+    let ret = tokio::select! {
+        ret = serve(&mut framed, &backend, &mut state, &mut messages) => ret,
+        // CLIENT KILL from another connection
+        _ = handle.killed() => Ok(()),
+    };
+    backend
+        .pubsub()
+        .unsubscribe_all(state.id, &mut state.subscriptions);
+    backend.clients().unregister(state.id);
+    ret
+}
+
+async fn serve<S: ClientStream>(
+    framed: &mut Framed<S, RespFrameCodec>,
+    backend: &Backend,
+    state: &mut ConnectionState,
+    messages: &mut mpsc::UnboundedReceiver<RespFrame>,
+) -> Result<()> {
+    let shutdown = backend.shutdown();
+    // a shutdown waits for the requests being answered, until their replies are written
+    let mut busy = None;
+    // when the queued replies went over the soft limit
+    let mut soft_since = None;
     loop {
-        match framed.next().await {
-            Some(Ok(frame)) => {
-                info!("Received frame: {:?}", frame);
+        let queued = framed.write_buffer().len();
+        // read each time round, CONFIG SET applies to connected clients too
+        let (timeout, limit) = {
+            let config = backend.config();
+            let limit = config.client_output_buffer_limit.get(state.kind());
+            (config.timeout, limit)
+        };
+        // checked as replies pile up, and again when the soft limit's time is up
+        if limit.exceeded(queued, &mut soft_since) {
+            warn!(
+                "Client {} closed for overcoming of output buffer limits.",
+                state.info
+            );
+            backend
+                .stats()
+                .output_buffer_limit_disconnections
+                .fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+        if queued == 0 {
+            busy = None;
+            // anything pipelined after QUIT is dropped
+            if state.flags.close_after_reply {
+                return Ok(());
+            }
+        }
 
-                let req = RedisRequest {
-                    frame,
-                    backend: backend.clone(),
-                };
-                let res = request_handler(req).await?;
-                info!("Sending frame: {:?}", res.frame);
-                framed.send(res.frame).await?;
+        // the rest of the pipeline waits until a blocked command is answered
+        let reading = state.blocked.is_none() && !state.flags.close_after_reply;
+        // subscribers only listen, and blocked clients are waiting on the server
+        let idle = (queued == 0 && reading && !state.flags.pubsub && !timeout.is_zero())
+            .then(|| state.last_interaction + timeout);
+        let soft_deadline = soft_since.map(|since: Instant| since + limit.soft_seconds);
+        let blocked = async {
+            match state.blocked.as_mut() {
+                Some(blocked) => blocked.await,
+                None => std::future::pending().await,
             }
-            Some(Err(e)) => return Err(e),
-            None => return Ok(()),
+        };
+
+        tokio::select! {
+            io = next_io(framed, reading) => match io {
+                Io::Flushed(res) => {
+                    res?;
+                    state.last_interaction = Instant::now();
+                }
+                Io::Request(None) => return Ok(()),
+                Io::Request(Some(Err(e))) => {
+                    // like redis, tell the client what was wrong before hanging up on it
+                    let reply = SimpleError::new(format!("ERR Protocol error: {}", e));
+                    framed.send(reply.into()).await?;
+                    return Err(e);
+                }
+                Io::Request(Some(Ok(frame))) => {
+                    busy.get_or_insert_with(|| shutdown.busy());
+                    trace!("Received frame: {}", Redacted(&frame));
+                    let req = RedisRequest {
+                        frame,
+                        backend: backend.clone(),
+                    };
+                    let res = request_handler(req, state).await?;
+                    // a blocked command's reply comes later
+                    if state.blocked.is_none() {
+                        for frame in res.frames {
+                            trace!("Sending frame: {:?}", frame);
+                            queue_reply(framed, frame);
+                        }
+                    }
+                }
+            },
+            // queued at once whether or not the client keeps up, its output limits decide
+            Some(message) = messages.recv() => {
+                queue_reply(framed, message.into_version(state.protocol));
+            }
+            reply = blocked => {
+                state.blocked = None;
+                match reply {
+                    Ok(reply) => queue_reply(framed, reply.into_version(state.protocol)),
+                    // the command gave up on the client
+                    Err(_) => return Ok(()),
+                }
+            }
+            // everything the client asked for has been answered, so it's safe to go
+            _ = shutdown.closing() => return Ok(()),
+            _ = sleep_until(idle) => {
+                info!("Closing idle client {}", state.id);
+                return Ok(());
+            }
+            // time to see whether the client caught up
+            _ = sleep_until(soft_deadline) => {}
         }
     }
 }
 
-async fn request_handler(req: RedisRequest) -> Result<RedisResponse> {
+// what the socket had for the connection: a request, or word that every queued reply has
+// been written
+enum Io {
+    Request(Option<Result<RespFrame>>),
+    Flushed(Result<()>),
+}
+
+// requests come first so a pipelined batch is answered with one write, unless the queued
+// replies already reach the high-water mark; either way the client is read from while its
+// replies wait to be written, so one that doesn't read runs into its output limits
+async fn next_io<S: ClientStream>(framed: &mut Framed<S, RespFrameCodec>, reading: bool) -> Io {
+    poll_fn(|cx| {
+        let queued = framed.write_buffer().len();
+        let flush_first = queued > 0 && queued >= framed.backpressure_boundary();
+        if flush_first {
+            if let Poll::Ready(res) = Pin::new(&mut *framed).poll_flush(cx) {
+                return Poll::Ready(Io::Flushed(res));
+            }
+        }
+        if reading {
+            if let Poll::Ready(frame) = Pin::new(&mut *framed).poll_next(cx) {
+                return Poll::Ready(Io::Request(frame));
+            }
+        }
+        if queued > 0 && !flush_first {
+            if let Poll::Ready(res) = Pin::new(&mut *framed).poll_flush(cx) {
+                return Poll::Ready(Io::Flushed(res));
+            }
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+// added to the output without waiting for the socket, serve writes it out
+fn queue_reply<S: ClientStream>(framed: &mut Framed<S, RespFrameCodec>, frame: RespFrame) {
+    let buf = framed.write_buffer_mut();
+    buf.reserve(frame.encoded_len());
+    frame.encode_to(buf);
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn request_handler(req: RedisRequest, state: &mut ConnectionState) -> Result<RedisResponse> {
     let (frame, backend) = (req.frame, req.backend);
-    let cmd: Command = frame.try_into()?;
-    info!("Executing command: {:?}", cmd);
+    state.last_interaction = Instant::now();
+    backend
+        .stats()
+        .commands_processed
+        .fetch_add(1, Ordering::Relaxed);
+    // a bad command is the client's mistake, answer it and keep the connection
+    let checked = authorize(&backend, state, &frame).and_then(|()| subscribed_mode(state, &frame));
+    let ret = match checked {
+        Err(denied) => denied.into(),
+        Ok(()) => {
+            debug!("Executing command: {}", Redacted(&frame));
+            match Command::try_from(frame) {
+                Ok(cmd) => {
+                    // looked up for every command, SWAPDB may have changed what the index
+                    // points to
+                    let db = backend.select(state.db).unwrap_or(backend);
+                    cmd.execute_for(&db, state)
+                }
+                Err(e) => e.into(),
+            }
+        }
+    };
+    state.publish();
+    let frames = match state.replies.is_empty() {
+        true => vec![ret],
+        false => std::mem::take(&mut state.replies),
+    };
+    Ok(RedisResponse {
+        frames: frames
+            .into_iter()
+            .map(|frame| frame.into_version(state.protocol))
+            .collect(),
+    })
+}
+
+// like redis, an unknown command or a wrong number of arguments is reported before the
+// client is asked to log in, so those are left to parsing
+// commands whose arguments may hold a password, as the name and subcommand they start with
+const SECRET_COMMANDS: &[&[&str]] = &[
+    &["auth"],
+    &["hello"],
+    &["acl", "setuser"],
+    &["config", "set"],
+];
+
+/// A request as it may go to the log: the arguments of commands that can carry passwords
+/// are left out.
+struct Redacted<'a>(&'a RespFrame);
+
+impl fmt::Display for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let RespFrame::Array(args) = self.0 else {
+            return write!(f, "{:?}", self.0);
+        };
+        let word = |arg: &RespFrame| match arg {
+            RespFrame::BulkString(s) => String::from_utf8_lossy(s).into_owned(),
+            other => format!("{:?}", other),
+        };
+        let words = args.iter().map(word).collect::<Vec<_>>();
+        let secret = SECRET_COMMANDS.iter().find(|names| {
+            names.len() <= words.len()
+                && names
+                    .iter()
+                    .zip(&words)
+                    .all(|(name, word)| name.eq_ignore_ascii_case(word))
+        });
+        match secret {
+            Some(names) => write!(f, "{} (redacted)", words[..names.len()].join(" ")),
+            None => write!(f, "{}", words.join(" ")),
+        }
+    }
+}
+
+fn authorize(
+    backend: &Backend,
+    state: &ConnectionState,
+    frame: &RespFrame,
+) -> Result<(), SimpleError> {
+    let RespFrame::Array(args) = frame else {
+        return Ok(());
+    };
+    let Some(spec) = resolve_command(args) else {
+        return Ok(());
+    };
+    if !state.authenticated && !spec.flags.contains(&CommandFlag::NoAuth) {
+        return Err(SimpleError::new("NOAUTH Authentication required."));
+    }
+    let acl = backend.acl();
+    let Err((reason, object)) = acl.check_command(&state.user, spec, args) else {
+        return Ok(());
+    };
+    acl.log(
+        reason,
+        &object,
+        &state.user,
+        state.info.to_string(),
+        backend.config().acllog_max_len,
+    );
+    Err(match reason {
+        AclDenial::Key => SimpleError::new("NOPERM No permissions to access a key"),
+        AclDenial::Channel => SimpleError::new("NOPERM No permissions to access a channel"),
+        _ => SimpleError::new(format!(
+            "NOPERM User {} has no permissions to run the '{}' command",
+            state.user, object
+        )),
+    })
+}
+
+// RESP3 tells messages apart from replies by their type, RESP2 clients can't
+fn subscribed_mode(state: &ConnectionState, frame: &RespFrame) -> Result<(), SimpleError> {
+    if !state.flags.pubsub || state.protocol != RespVersion::Resp2 {
+        return Ok(());
+    }
+    let RespFrame::Array(args) = frame else {
+        return Ok(());
+    };
+    match resolve_command(args) {
+        Some(spec) if !SUBSCRIBED_MODE_COMMANDS.contains(&spec.name) => Err(SimpleError::new(
+            format!(
+                "ERR Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+                spec.name
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BytesMut;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn decode_all(input: &[u8]) -> Result<Vec<RespFrame>> {
+        let mut codec = RespFrameCodec::default();
+        let mut buf = BytesMut::from(input);
+        let mut frames = vec![];
+        while let Some(frame) = codec.decode(&mut buf)? {
+            frames.push(frame);
+        }
+        Ok(frames)
+    }
+
+    fn command(args: &[&[u8]]) -> RespFrame {
+        RespArray::new(
+            args.iter()
+                .map(|a| BulkString::new(a.to_vec()).into())
+                .collect::<Vec<RespFrame>>(),
+        )
+        .into()
+    }
+
+    #[test]
+    fn test_inline_command_decode() -> Result<()> {
+        let frames = decode_all(b"set hello world\r\n\r\nget hello\n*1\r\n$4\r\nping\r\n")?;
+        assert_eq!(
+            frames,
+            vec![
+                command(&[b"set", b"hello", b"world"]),
+                command(&[b"get", b"hello"]),
+                command(&[b"ping"]),
+            ]
+        );
+
+        let mut codec = RespFrameCodec::default();
+        let mut buf = BytesMut::from(&b"get hel"[..]);
+        assert_eq!(codec.decode(&mut buf)?, None);
+        buf.extend_from_slice(b"lo\r\n");
+        assert_eq!(codec.decode(&mut buf)?, Some(command(&[b"get", b"hello"])));
+        Ok(())
+    }
+
+    #[test]
+    fn test_inline_command_quoting() -> Result<()> {
+        let frames = decode_all(b"set \"a b\" 'it\\'s' \"\\x41\\n\\\"\" ''\r\n")?;
+        assert_eq!(
+            frames,
+            vec![command(&[b"set", b"a b", b"it's", b"A\n\"", b""])]
+        );
+
+        assert!(decode_all(b"set \"unterminated\r\n").is_err());
+        assert!(decode_all(b"set \"a\"b\r\n").is_err());
+        assert!(decode_all(b"set 'a'b\r\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_inline_command_too_big() {
+        let mut input = b"set key ".to_vec();
+        input.resize(MAX_INLINE_LEN + 1, b'x');
+        assert!(decode_all(&input).is_err());
+    }
+
+    #[test]
+    fn test_redacted_log_line() {
+        let frame = |args: &[&str]| -> RespFrame {
+            RespArray::new(
+                args.iter()
+                    .map(|a| BulkString::from(*a).into())
+                    .collect::<Vec<RespFrame>>(),
+            )
+            .into()
+        };
+        let line = |args: &[&str]| Redacted(&frame(args)).to_string();
+        assert_eq!(line(&["get", "key"]), "get key");
+        assert_eq!(line(&["AUTH", "alice", "secret"]), "AUTH (redacted)");
+        assert_eq!(
+            line(&["hello", "3", "AUTH", "alice", "secret"]),
+            "hello (redacted)"
+        );
+        assert_eq!(
+            line(&["acl", "SETUSER", "alice", ">secret"]),
+            "acl SETUSER (redacted)"
+        );
+        assert_eq!(line(&["acl", "whoami"]), "acl whoami");
+        assert_eq!(
+            line(&["config", "set", "requirepass", "secret"]),
+            "config set (redacted)"
+        );
+    }
+
+    #[test]
+    fn test_connection_options_from_config() {
+        let config = Config {
+            proto_max_bulk_len: 1024,
+            proto_max_multibulk_len: 16,
+            proto_max_nesting_depth: 4,
+            client_query_buffer_limit: 4096,
+            ..Default::default()
+        };
+        let expected = ProtocolLimits {
+            max_bulk_len: 1024,
+            max_multibulk_len: 16,
+            max_depth: 4,
+            max_query_buffer: 4096,
+        };
+        assert_eq!(ConnectionOptions::from(&config).limits, expected);
+        assert_eq!(
+            ConnectionOptions::from(&Config::default()).limits,
+            ProtocolLimits::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_protocol_error_replies_then_closes() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            let options = ConnectionOptions {
+                limits: ProtocolLimits {
+                    max_multibulk_len: 16,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            stream_handler_with_options(stream, Backend::new(), options).await
+        });
+
+        let mut client = TcpStream::connect(addr).await?;
+        client.write_all(b"*2147483647\r\n").await?;
+        let mut reply = vec![];
+        client.read_to_end(&mut reply).await?;
+        assert_eq!(
+            reply,
+            b"-ERR Protocol error: invalid multibulk length\r\n".to_vec()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_command_errors_keep_the_connection() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            stream_handler(stream, Backend::new()).await
+        });
+
+        let mut client = TcpStream::connect(addr).await?;
+        client
+            .write_all(b"foo bar\r\nget\r\nset a 1\r\nhget a f\r\nget a\r\n")
+            .await?;
+        let expected: &[u8] = b"-ERR unknown command 'foo', with args beginning with: 'bar' \r\n\
+            -ERR wrong number of arguments for 'get' command\r\n\
+            +OK\r\n\
+            -WRONGTYPE Operation against a key holding the wrong kind of value\r\n\
+            $1\r\n1\r\n";
+        let mut reply = vec![0; expected.len()];
+        client.read_exact(&mut reply).await?;
+        assert_eq!(
+            String::from_utf8_lossy(&reply),
+            String::from_utf8_lossy(expected)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_replies_are_flushed_before_a_protocol_error() -> Result<()> {
+        let mut request = b"set a 1\r\n".to_vec();
+        let mut expected = b"+OK\r\n".to_vec();
+        for _ in 0..2000 {
+            request.extend_from_slice(b"*2\r\n$3\r\nget\r\n$1\r\na\r\n");
+            expected.extend_from_slice(b"$1\r\n1\r\n");
+        }
+        request.extend_from_slice(b"*2147483647\r\n");
+        expected.extend_from_slice(b"-ERR Protocol error: invalid multibulk length\r\n");
+
+        // batched with the default mark, written one reply at a time with none at all
+        for output_high_water in [OUTPUT_HIGH_WATER, 0] {
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            let addr = listener.local_addr()?;
+            tokio::spawn(async move {
+                let (stream, _) = listener.accept().await?;
+                let options = ConnectionOptions {
+                    output_high_water,
+                    ..Default::default()
+                };
+                stream_handler_with_options(stream, Backend::new(), options).await
+            });
+
+            let mut client = TcpStream::connect(addr).await?;
+            client.write_all(&request).await?;
+            let mut reply = vec![];
+            client.read_to_end(&mut reply).await?;
+            assert_eq!(reply, expected);
+        }
+        Ok(())
+    }
+
+    async fn read_reply(client: &mut TcpStream) -> Result<String> {
+        let mut buf = vec![0; 4096];
+        let n = client.read(&mut buf).await?;
+        Ok(String::from_utf8_lossy(&buf[..n]).into_owned())
+    }
+
+    #[tokio::test]
+    async fn test_client_list_kill_and_quit() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let backend = Backend::new();
+        let server_backend = backend.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(stream_handler(stream, server_backend.clone()));
+            }
+        });
+
+        let mut first = TcpStream::connect(addr).await?;
+        first
+            .write_all(b"client setname first\r\nclient id\r\n")
+            .await?;
+        let mut reply = read_reply(&mut first).await?;
+        while !reply.ends_with(":+1\r\n") {
+            reply += &read_reply(&mut first).await?;
+        }
+        assert_eq!(reply, "+OK\r\n:+1\r\n");
+
+        let mut second = TcpStream::connect(addr).await?;
+        second.write_all(b"client list\r\n").await?;
+        let list = read_reply(&mut second).await?;
+        assert!(list.contains("id=1 addr="), "{}", list);
+        assert!(list.contains(" name=first "), "{}", list);
+        assert!(list.contains("id=2 addr="), "{}", list);
+
+        second.write_all(b"client kill id 1\r\n").await?;
+        assert_eq!(read_reply(&mut second).await?, ":+1\r\n");
+        let mut rest = vec![];
+        first.read_to_end(&mut rest).await?;
+        assert!(rest.is_empty());
+
+        // anything pipelined after QUIT is never run
+        second.write_all(b"quit\r\nset a 1\r\n").await?;
+        let mut rest = vec![];
+        second.read_to_end(&mut rest).await?;
+        assert_eq!(rest, b"+OK\r\n");
+        assert_eq!(backend.get("a"), None);
+        Ok(())
+    }
 
-    let ret = cmd.execute(&backend);
-    Ok(RedisResponse { frame: ret })
+    #[tokio::test]
+    async fn test_maxclients_and_idle_timeout() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let backend = Backend::new();
+        backend.update_config(&[
+            ("maxclients".to_string(), "1".to_string()),
+            ("timeout".to_string(), "1".to_string()),
+        ])?;
+        let server_backend = backend.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(stream_handler(stream, server_backend.clone()));
+            }
+        });
+
+        let mut first = TcpStream::connect(addr).await?;
+        first.write_all(b"ping\r\n").await?;
+        assert_eq!(read_reply(&mut first).await?, "+PONG\r\n");
+
+        let mut second = TcpStream::connect(addr).await?;
+        let mut rest = vec![];
+        second.read_to_end(&mut rest).await?;
+        assert_eq!(rest, b"-ERR max number of clients reached\r\n");
+        assert_eq!(
+            backend.stats().rejected_connections.load(Ordering::Relaxed),
+            1
+        );
+
+        // nothing sent for longer than the timeout
+        let started = Instant::now();
+        let mut rest = vec![];
+        first.read_to_end(&mut rest).await?;
+        assert!(rest.is_empty());
+        assert!(started.elapsed() >= Duration::from_millis(500));
+
+        // the slot is free again, and a client that keeps talking stays connected
+        let mut third = TcpStream::connect(addr).await?;
+        for _ in 0..3 {
+            tokio::time::sleep(Duration::from_millis(600)).await;
+            third.write_all(b"ping\r\n").await?;
+            assert_eq!(read_reply(&mut third).await?, "+PONG\r\n");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_output_buffer_limits_disconnect_slow_readers() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let backend = Backend::new();
+        for i in 0..100 {
+            let value = BulkString::new(vec![b'x'; 1024]).into();
+            backend.hset("big".to_string(), format!("field{}", i), value);
+        }
+        let server_backend = backend.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(stream_handler(stream, server_backend.clone()));
+            }
+        });
+        let disconnections = || {
+            backend
+                .stats()
+                .output_buffer_limit_disconnections
+                .load(Ordering::Relaxed)
+        };
+
+        // the hard limit cuts a client off at once, the soft one after it stayed over it a while
+        for (n, limit) in [(1, "normal 1mb 0 0"), (2, "normal 0 1mb 1")] {
+            backend
+                .update_config(&[("client-output-buffer-limit".to_string(), limit.to_string())])?;
+            let started = Instant::now();
+            let mut client = TcpStream::connect(addr).await?;
+            // about 50mb of replies that are never read
+            let _ = client.write_all(&b"hgetall big\r\n".repeat(500)).await;
+            while disconnections() < n {
+                assert!(started.elapsed() < Duration::from_secs(10));
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            if n == 2 {
+                assert!(started.elapsed() >= Duration::from_secs(1));
+            }
+        }
+
+        // a client that reads its replies is never over
+        let mut client = TcpStream::connect(addr).await?;
+        client.write_all(&b"hgetall big\r\n".repeat(50)).await?;
+        let mut buf = vec![0; 1024 * 1024];
+        let mut total = 0;
+        while total < 50 * 100 * 1024 {
+            total += client.read(&mut buf).await?;
+        }
+        assert_eq!(disconnections(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_auth_and_acl_checks() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let backend = Backend::new();
+        backend.update_config(&[("requirepass".to_string(), "pw".to_string())])?;
+        let server_backend = backend.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(stream_handler(stream, server_backend.clone()));
+            }
+        });
+
+        let mut client = TcpStream::connect(addr).await?;
+        let mut expect = async |request: &str, reply: &str| -> Result<()> {
+            client.write_all(request.as_bytes()).await?;
+            let mut got = read_reply(&mut client).await?;
+            while got.len() < reply.len() {
+                got += &read_reply(&mut client).await?;
+            }
+            assert_eq!(got, reply, "{}", request);
+            Ok(())
+        };
+        // bad commands are reported before the client is asked to log in
+        expect(
+            "nope\r\n",
+            "-ERR unknown command 'nope', with args beginning with: \r\n",
+        )
+        .await?;
+        expect(
+            "get\r\n",
+            "-ERR wrong number of arguments for 'get' command\r\n",
+        )
+        .await?;
+        expect("get a\r\n", "-NOAUTH Authentication required.\r\n").await?;
+        expect(
+            "auth wrong\r\n",
+            "-WRONGPASS invalid username-password pair or user is disabled.\r\n",
+        )
+        .await?;
+        expect("auth pw\r\nget a\r\n", "+OK\r\n$-1\r\n").await?;
+
+        expect(
+            "acl setuser alice on >secret ~cache:* +get +set\r\n",
+            "+OK\r\n",
+        )
+        .await?;
+        expect(
+            "auth alice secret\r\nacl whoami\r\n",
+            "+OK\r\n-NOPERM User alice has no permissions to run the 'acl|whoami' command\r\n",
+        )
+        .await?;
+        expect("set cache:1 v\r\nget cache:1\r\n", "+OK\r\n$1\r\nv\r\n").await?;
+        expect(
+            "get other\r\n",
+            "-NOPERM No permissions to access a key\r\n",
+        )
+        .await?;
+        expect(
+            "hgetall cache:1\r\n",
+            "-NOPERM User alice has no permissions to run the 'hgetall' command\r\n",
+        )
+        .await?;
+
+        let log = backend.acl().log_entries(10);
+        let denied = log
+            .iter()
+            .map(|e| (e.reason, e.object.as_str(), e.username.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            denied,
+            vec![
+                (AclDenial::Command, "hgetall", "alice"),
+                (AclDenial::Key, "other", "alice"),
+                (AclDenial::Command, "acl|whoami", "alice"),
+                (AclDenial::Auth, "AUTH", "default"),
+            ]
+        );
+        assert!(log[0].client_info.contains(" user=alice "));
+
+        // RESET logs out when the default user needs a password
+        expect(
+            "reset\r\nget a\r\n",
+            "+RESET\r\n-NOAUTH Authentication required.\r\n",
+        )
+        .await?;
+        expect(
+            "hello 3\r\n",
+            "-NOAUTH HELLO must be called with the client already authenticated, otherwise the HELLO <proto> AUTH <user> <pass> option can be used to authenticate the client and select the RESP protocol version at the same time\r\n",
+        )
+        .await?;
+        client.write_all(b"hello 3 auth default pw\r\n").await?;
+        assert!(read_reply(&mut client).await?.starts_with("%7\r\n"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_configure_tcp() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let client = TcpStream::connect(listener.local_addr()?).await?;
+        let mut config = Config::default();
+        configure_tcp(&client, &config)?;
+        assert!(client.nodelay()?);
+        let sock = SockRef::from(&client);
+        assert!(sock.keepalive()?);
+        assert_eq!(sock.keepalive_time()?, Duration::from_secs(300));
+
+        config.set("tcp-keepalive", "0")?;
+        config.set("tcp-nodelay", "no")?;
+        configure_tcp(&client, &config)?;
+        assert!(!client.nodelay()?);
+        assert!(!SockRef::from(&client).keepalive()?);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("simple-redis-{}.sock", std::process::id()));
+        // a stale socket file is replaced
+        std::fs::write(&path, b"")?;
+        let listener = bind_unix(&path, Some(0o700))?;
+        assert_eq!(
+            std::fs::metadata(&path)?.permissions().mode() & 0o777,
+            0o700
+        );
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            stream_handler(stream, Backend::new()).await
+        });
+
+        let mut client = UnixStream::connect(&path).await?;
+        client.write_all(b"client info\r\n").await?;
+        let mut buf = vec![0; 1024];
+        let n = client.read(&mut buf).await?;
+        let info = String::from_utf8_lossy(&buf[..n]).into_owned();
+        let addr = format!("addr={}:0 laddr={}:0 ", path.display(), path.display());
+        assert!(info.contains(&addr), "{}", info);
+        assert!(info.contains(" flags=U "), "{}", info);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    // reads until `reply` has come in full, whatever the socket split it into
+    async fn expect(client: &mut TcpStream, request: &str, reply: &str) -> Result<()> {
+        client.write_all(request.as_bytes()).await?;
+        let mut got = String::new();
+        while got.len() < reply.len() {
+            got += &read_reply(client).await?;
+        }
+        assert_eq!(got, reply, "{}", request);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pubsub_subscribed_mode() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let backend = Backend::new();
+        let server_backend = backend.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(stream_handler(stream, server_backend.clone()));
+            }
+        });
+
+        // RESP2 subscribers only get to run the pub/sub commands
+        let mut sub = TcpStream::connect(addr).await?;
+        expect(
+            &mut sub,
+            "subscribe news\r\n",
+            "*3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:+1\r\n",
+        )
+        .await?;
+        expect(
+            &mut sub,
+            "get a\r\n",
+            "-ERR Can't execute 'get': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context\r\n",
+        )
+        .await?;
+        expect(&mut sub, "ping\r\n", "*2\r\n$4\r\npong\r\n$0\r\n\r\n").await?;
+        expect(
+            &mut sub,
+            "psubscribe n*\r\n",
+            "*3\r\n$10\r\npsubscribe\r\n$2\r\nn*\r\n:+2\r\n",
+        )
+        .await?;
+
+        // RESP3 ones get pushes and can go on running anything
+        let mut sub3 = TcpStream::connect(addr).await?;
+        sub3.write_all(b"hello 3\r\nsubscribe news\r\n").await?;
+        let mut got = read_reply(&mut sub3).await?;
+        while !got.ends_with(">3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:+1\r\n") {
+            got += &read_reply(&mut sub3).await?;
+        }
+        expect(&mut sub3, "get a\r\n", "_\r\n").await?;
+
+        let mut publisher = TcpStream::connect(addr).await?;
+        expect(&mut publisher, "publish news hi\r\n", ":+3\r\n").await?;
+        expect(
+            &mut sub,
+            "",
+            "*3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$2\r\nhi\r\n*4\r\n$8\r\npmessage\r\n$2\r\nn*\r\n$4\r\nnews\r\n$2\r\nhi\r\n",
+        )
+        .await?;
+        expect(
+            &mut sub3,
+            "",
+            ">3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$2\r\nhi\r\n",
+        )
+        .await?;
+        expect(&mut sub, "reset\r\n", "+RESET\r\n").await?;
+        expect(
+            &mut publisher,
+            "pubsub numsub news\r\n",
+            "*2\r\n$4\r\nnews\r\n:+1\r\n",
+        )
+        .await?;
+
+        // shard channels don't share the global namespace, and route by slot like keys
+        expect(
+            &mut sub,
+            "ssubscribe news\r\n",
+            "*3\r\n$10\r\nssubscribe\r\n$4\r\nnews\r\n:+1\r\n",
+        )
+        .await?;
+        expect(
+            &mut publisher,
+            "spublish news hi\r\npublish news hi\r\n",
+            ":+1\r\n:+1\r\n",
+        )
+        .await?;
+        expect(
+            &mut sub,
+            "",
+            "*3\r\n$8\r\nsmessage\r\n$4\r\nnews\r\n$2\r\nhi\r\n",
+        )
+        .await?;
+        expect(
+            &mut publisher,
+            "command getkeys spublish news hi\r\n",
+            "*1\r\n$4\r\nnews\r\n",
+        )
+        .await?;
+
+        // a subscriber that doesn't read is cut off, the publisher carries on regardless
+        backend.update_config(&[(
+            "client-output-buffer-limit".to_string(),
+            "pubsub 1mb 0 0".to_string(),
+        )])?;
+        let mut slow = TcpStream::connect(addr).await?;
+        expect(
+            &mut slow,
+            "subscribe flood\r\n",
+            "*3\r\n$9\r\nsubscribe\r\n$5\r\nflood\r\n:+1\r\n",
+        )
+        .await?;
+        // inline, so each one stays under the inline request limit
+        let message = "x".repeat(32 * 1024);
+        let batch = format!("publish flood {}\r\n", message).repeat(200);
+        publisher.write_all(batch.as_bytes()).await?;
+        let mut replies = String::new();
+        while replies.matches("\r\n").count() < 200 {
+            replies += &read_reply(&mut publisher).await?;
+        }
+        let started = Instant::now();
+        while backend
+            .stats()
+            .output_buffer_limit_disconnections
+            .load(Ordering::Relaxed)
+            == 0
+        {
+            assert!(started.elapsed() < Duration::from_secs(10));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        // channels are checked against the user's rules
+        expect(
+            &mut publisher,
+            "acl setuser bob on nopass +@all ~* resetchannels &news\r\nauth bob x\r\n",
+            "+OK\r\n+OK\r\n",
+        )
+        .await?;
+        expect(
+            &mut publisher,
+            "publish sports hi\r\n",
+            "-NOPERM No permissions to access a channel\r\n",
+        )
+        .await?;
+        expect(
+            &mut publisher,
+            "spublish sports hi\r\n",
+            "-NOPERM No permissions to access a channel\r\n",
+        )
+        .await?;
+        expect(
+            &mut publisher,
+            "psubscribe *\r\n",
+            "-NOPERM No permissions to access a channel\r\n",
+        )
+        .await?;
+        Ok(())
+    }
 }
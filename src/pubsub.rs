@@ -0,0 +1,298 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::RwLock;
+
+use dashmap::DashMap;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{glob_match, BulkString, RespFrame, RespPush};
+
+/// Where the broker delivers a connection's messages. It is unbounded so a publisher never
+/// waits on a slow subscriber: the subscriber's connection moves what arrives to its output
+/// straight away, where the pubsub client-output-buffer-limit catches one that falls behind.
+pub type MessageSender = UnboundedSender<RespFrame>;
+
+type Subscribers = HashMap<u64, MessageSender>;
+
+/// The server-wide pub/sub broker: who listens to which channel or pattern, by client id.
+/// Shard channels are a namespace of their own, a message on one never reaches the
+/// subscribers of a global channel with the same name nor any pattern.
+#[derive(Debug, Default)]
+pub struct PubSub {
+    channels: ChannelMap,
+    // every publish walks them all, so they sit behind one lock rather than in shards
+    patterns: RwLock<HashMap<Vec<u8>, Subscribers>>,
+    shard_channels: ChannelMap,
+}
+
+#[derive(Debug, Default)]
+struct ChannelMap(DashMap<Vec<u8>, Subscribers>);
+
+/// What one connection is subscribed to, so it can answer UNSUBSCRIBE without arguments and
+/// leave everything behind when it goes away.
+#[derive(Debug, Default)]
+pub struct Subscriptions {
+    /// Unset for commands run outside a connection, which can't receive anything.
+    pub(crate) sender: Option<MessageSender>,
+    pub(crate) channels: BTreeSet<Vec<u8>>,
+    pub(crate) patterns: BTreeSet<Vec<u8>>,
+    pub(crate) shard_channels: BTreeSet<Vec<u8>>,
+}
+
+impl Subscriptions {
+    /// The channels and patterns, as SUBSCRIBE and PSUBSCRIBE count them; shard channels
+    /// are counted on their own.
+    pub fn count(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+
+    /// Without any subscription left the client is out of subscribed mode.
+    pub fn is_empty(&self) -> bool {
+        self.count() == 0 && self.shard_channels.is_empty()
+    }
+}
+
+impl ChannelMap {
+    fn subscribe(&self, channel: &[u8], id: u64, sender: MessageSender) {
+        self.0
+            .entry(channel.to_vec())
+            .or_default()
+            .insert(id, sender);
+    }
+
+    fn unsubscribe(&self, channel: &[u8], id: u64) {
+        if let Some(mut subscribers) = self.0.get_mut(channel) {
+            subscribers.remove(&id);
+        }
+        // a channel without subscribers is no longer listed
+        self.0
+            .remove_if(channel, |_, subscribers| subscribers.is_empty());
+    }
+
+    fn publish(&self, kind: &[u8], channel: &[u8], message: &[u8]) -> usize {
+        match self.0.get(channel) {
+            Some(subscribers) => deliver(&subscribers, &push(&[kind, channel, message])),
+            None => 0,
+        }
+    }
+
+    fn names(&self, pattern: Option<&[u8]>) -> Vec<Vec<u8>> {
+        let mut channels = self
+            .0
+            .iter()
+            .filter(|entry| pattern.is_none_or(|pattern| glob_match(pattern, entry.key(), false)))
+            .map(|entry| entry.key().clone())
+            .collect::<Vec<_>>();
+        channels.sort();
+        channels
+    }
+
+    fn numsub(&self, channel: &[u8]) -> usize {
+        self.0.get(channel).map_or(0, |s| s.len())
+    }
+}
+
+impl PubSub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(&self, channel: &[u8], id: u64, sender: MessageSender) {
+        self.channels.subscribe(channel, id, sender);
+    }
+
+    pub fn unsubscribe(&self, channel: &[u8], id: u64) {
+        self.channels.unsubscribe(channel, id);
+    }
+
+    pub fn psubscribe(&self, pattern: &[u8], id: u64, sender: MessageSender) {
+        let mut patterns = self.patterns.write().unwrap();
+        patterns
+            .entry(pattern.to_vec())
+            .or_default()
+            .insert(id, sender);
+    }
+
+    pub fn punsubscribe(&self, pattern: &[u8], id: u64) {
+        let mut patterns = self.patterns.write().unwrap();
+        if let Some(subscribers) = patterns.get_mut(pattern) {
+            subscribers.remove(&id);
+            if subscribers.is_empty() {
+                patterns.remove(pattern);
+            }
+        }
+    }
+
+    /// Drop every subscription a connection holds, without telling it.
+    pub fn unsubscribe_all(&self, id: u64, subscriptions: &mut Subscriptions) {
+        for channel in std::mem::take(&mut subscriptions.channels) {
+            self.unsubscribe(&channel, id);
+        }
+        for pattern in std::mem::take(&mut subscriptions.patterns) {
+            self.punsubscribe(&pattern, id);
+        }
+        for channel in std::mem::take(&mut subscriptions.shard_channels) {
+            self.sunsubscribe(&channel, id);
+        }
+    }
+
+    pub fn ssubscribe(&self, channel: &[u8], id: u64, sender: MessageSender) {
+        self.shard_channels.subscribe(channel, id, sender);
+    }
+
+    pub fn sunsubscribe(&self, channel: &[u8], id: u64) {
+        self.shard_channels.unsubscribe(channel, id);
+    }
+
+    /// PUBLISH: hand the message to the channel's subscribers and to those of every pattern
+    /// matching it, returning how many got it.
+    pub fn publish(&self, channel: &[u8], message: &[u8]) -> usize {
+        let mut receivers = self.channels.publish(b"message", channel, message);
+        let patterns = self.patterns.read().unwrap();
+        for (pattern, subscribers) in patterns.iter() {
+            if glob_match(pattern, channel, false) {
+                let frame = push(&[b"pmessage", pattern, channel, message]);
+                receivers += deliver(subscribers, &frame);
+            }
+        }
+        receivers
+    }
+
+    /// SPUBLISH: only the shard channel's own subscribers get it, patterns don't apply.
+    pub fn spublish(&self, channel: &[u8], message: &[u8]) -> usize {
+        self.shard_channels.publish(b"smessage", channel, message)
+    }
+
+    /// PUBSUB CHANNELS: the channels with at least one subscriber, matching `pattern` if
+    /// given.
+    pub fn channels(&self, pattern: Option<&[u8]>) -> Vec<Vec<u8>> {
+        self.channels.names(pattern)
+    }
+
+    /// PUBSUB NUMSUB for one channel, not counting pattern subscribers.
+    pub fn numsub(&self, channel: &[u8]) -> usize {
+        self.channels.numsub(channel)
+    }
+
+    /// PUBSUB SHARDCHANNELS, like `channels` for the shard channels.
+    pub fn shard_channels(&self, pattern: Option<&[u8]>) -> Vec<Vec<u8>> {
+        self.shard_channels.names(pattern)
+    }
+
+    /// PUBSUB SHARDNUMSUB for one shard channel.
+    pub fn shard_numsub(&self, channel: &[u8]) -> usize {
+        self.shard_channels.numsub(channel)
+    }
+
+    /// PUBSUB NUMPAT: how many distinct patterns are subscribed to.
+    pub fn numpat(&self) -> usize {
+        self.patterns.read().unwrap().len()
+    }
+}
+
+// a connection that went away is unsubscribed as it closes, until then its sends just fail
+fn deliver(subscribers: &Subscribers, frame: &RespFrame) -> usize {
+    subscribers
+        .values()
+        .filter(|sender| sender.send(frame.clone()).is_ok())
+        .count()
+}
+
+fn push(parts: &[&[u8]]) -> RespFrame {
+    RespPush::new(
+        parts
+            .iter()
+            .map(|part| BulkString::from(*part).into())
+            .collect::<Vec<RespFrame>>(),
+    )
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_publish_reaches_channels_and_patterns() {
+        let pubsub = PubSub::new();
+        let (first, mut first_rx) = mpsc::unbounded_channel();
+        let (second, mut second_rx) = mpsc::unbounded_channel();
+        pubsub.subscribe(b"news.tech", 1, first.clone());
+        pubsub.psubscribe(b"news.*", 1, first);
+        pubsub.psubscribe(b"news.*", 2, second);
+
+        assert_eq!(pubsub.publish(b"news.tech", b"hi"), 3);
+        assert_eq!(
+            first_rx.try_recv().unwrap(),
+            push(&[b"message", b"news.tech", b"hi"])
+        );
+        assert_eq!(
+            first_rx.try_recv().unwrap(),
+            push(&[b"pmessage", b"news.*", b"news.tech", b"hi"])
+        );
+        assert_eq!(
+            second_rx.try_recv().unwrap(),
+            push(&[b"pmessage", b"news.*", b"news.tech", b"hi"])
+        );
+        assert_eq!(pubsub.publish(b"sports", b"hi"), 0);
+
+        assert_eq!(pubsub.channels(None), vec![b"news.tech".to_vec()]);
+        assert!(pubsub.channels(Some(b"sport*")).is_empty());
+        assert_eq!(pubsub.numsub(b"news.tech"), 1);
+        assert_eq!(pubsub.numpat(), 1);
+    }
+
+    #[test]
+    fn test_shard_channels_are_their_own_namespace() {
+        let pubsub = PubSub::new();
+        let (global, mut global_rx) = mpsc::unbounded_channel();
+        let (shard, mut shard_rx) = mpsc::unbounded_channel();
+        pubsub.subscribe(b"orders", 1, global.clone());
+        pubsub.psubscribe(b"*", 1, global);
+        pubsub.ssubscribe(b"orders", 2, shard);
+
+        assert_eq!(pubsub.spublish(b"orders", b"hi"), 1);
+        assert_eq!(
+            shard_rx.try_recv().unwrap(),
+            push(&[b"smessage", b"orders", b"hi"])
+        );
+        assert!(global_rx.try_recv().is_err());
+        assert_eq!(pubsub.publish(b"orders", b"hi"), 2);
+        assert!(shard_rx.try_recv().is_err());
+
+        assert_eq!(
+            pubsub.shard_channels(Some(b"ord*")),
+            vec![b"orders".to_vec()]
+        );
+        assert_eq!(pubsub.shard_numsub(b"orders"), 1);
+        pubsub.sunsubscribe(b"orders", 2);
+        assert!(pubsub.shard_channels(None).is_empty());
+        assert_eq!(pubsub.numsub(b"orders"), 1);
+    }
+
+    #[test]
+    fn test_unsubscribe_all() {
+        let pubsub = PubSub::new();
+        let (sender, _rx) = mpsc::unbounded_channel();
+        let mut subscriptions = Subscriptions {
+            sender: Some(sender.clone()),
+            ..Default::default()
+        };
+        for channel in [&b"a"[..], b"b"] {
+            pubsub.subscribe(channel, 7, sender.clone());
+            subscriptions.channels.insert(channel.to_vec());
+        }
+        pubsub.psubscribe(b"*", 7, sender.clone());
+        subscriptions.patterns.insert(b"*".to_vec());
+        pubsub.ssubscribe(b"a", 7, sender);
+        subscriptions.shard_channels.insert(b"a".to_vec());
+        assert_eq!(subscriptions.count(), 3);
+
+        pubsub.unsubscribe_all(7, &mut subscriptions);
+        assert!(subscriptions.is_empty());
+        assert!(pubsub.channels(None).is_empty());
+        assert!(pubsub.shard_channels(None).is_empty());
+        assert_eq!(pubsub.numpat(), 0);
+        assert_eq!(pubsub.publish(b"a", b"gone"), 0);
+    }
+}
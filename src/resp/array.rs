@@ -3,16 +3,12 @@ use crate::RespEncode;
 use crate::RespError;
 use crate::RespFrame;
 
-use bytes::Buf;
-use bytes::BytesMut;
+use bytes::{BufMut, BytesMut};
 
 use std::ops::Deref;
 
-use super::calc_total_length;
-use super::extract_fixed_data;
-use super::parse_length;
-use super::BUF_CAP;
-use super::CRLF_LEN;
+use super::decoder::{decode_frame, frame_length, unexpected_frame};
+use super::{header_len, put_header};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct RespArray(pub(crate) Vec<RespFrame>);
@@ -23,20 +19,26 @@ pub struct RespNullArray;
 //- array: "*<number-of-elements>\r\n<element-1>...<element-n>"
 //        - "*2\r\n$3\r\nget\r\n$5\r\nhello\r\n"
 impl RespEncode for RespArray {
-    fn encode(self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(BUF_CAP);
-        buf.extend_from_slice(format!("*{}\r\n", self.len()).as_bytes());
-        for frame in self.0 {
-            buf.extend_from_slice(&frame.encode());
+    fn encode_to<B: BufMut>(&self, buf: &mut B) {
+        put_header(buf, b'*', self.len());
+        for frame in &self.0 {
+            frame.encode_to(buf);
         }
-        buf
+    }
+
+    fn encoded_len(&self) -> usize {
+        header_len(self.len()) + self.iter().map(|f| f.encoded_len()).sum::<usize>()
     }
 }
 
 //- null array: "*-1\r\n"
 impl RespEncode for RespNullArray {
-    fn encode(self) -> Vec<u8> {
-        b"*-1\r\n".to_vec()
+    fn encode_to<B: BufMut>(&self, buf: &mut B) {
+        buf.put_slice(b"*-1\r\n");
+    }
+
+    fn encoded_len(&self) -> usize {
+        5
     }
 }
 
@@ -44,26 +46,14 @@ impl RespDecode for RespArray {
     const PREFIX: &'static str = "*";
 
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        let total = calc_total_length(buf, len, end, Self::PREFIX)?;
-        if total > buf.len() {
-            return Err(RespError::NotComplete);
+        match decode_frame(buf, Self::PREFIX)? {
+            RespFrame::Array(arr) => Ok(arr),
+            frame => Err(unexpected_frame("Array", frame)),
         }
-        buf.advance(end + CRLF_LEN);
-
-        let mut array = Vec::new();
-        for _ in 0..len {
-            let frame = RespFrame::decode(buf)?;
-            array.push(frame);
-        }
-
-        Ok(RespArray::new(array))
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        let total = calc_total_length(buf, len, end, Self::PREFIX)?;
-        Ok(total)
+        frame_length(buf, Self::PREFIX)
     }
 }
 
@@ -71,12 +61,14 @@ impl RespDecode for RespNullArray {
     const PREFIX: &'static str = "*";
 
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        extract_fixed_data(buf, "*-1\r\n", "NullArray")?;
-        Ok(RespNullArray)
+        match decode_frame(buf, Self::PREFIX)? {
+            RespFrame::NullArray(arr) => Ok(arr),
+            frame => Err(unexpected_frame("NullArray", frame)),
+        }
     }
 
-    fn expect_length(_buf: &[u8]) -> Result<usize, RespError> {
-        Ok(5)
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        frame_length(buf, Self::PREFIX)
     }
 }
 
@@ -0,0 +1,105 @@
+use crate::resp::RespFrame;
+use crate::RespDecode;
+use crate::RespEncode;
+use crate::RespError;
+use crate::RespMap;
+
+use bytes::{BufMut, BytesMut};
+
+use std::ops::{Deref, DerefMut};
+
+use super::decoder::{decode_frame, frame_length, unexpected_frame};
+use super::put_header;
+
+/// Auxiliary key/value data that precedes the reply it describes.
+///
+/// The attribute is a frame of its own on the wire, so it is decoded separately from
+/// the reply that follows it; clients that don't care about it can simply drop it.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Default)]
+pub struct RespAttribute(pub(crate) RespMap);
+
+//- attribute: "|<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>"
+impl RespEncode for RespAttribute {
+    fn encode_to<B: BufMut>(&self, buf: &mut B) {
+        put_header(buf, b'|', self.len());
+        for (key, value) in self.iter() {
+            key.encode_to(buf);
+            value.encode_to(buf);
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        // the same entries as a map, only the prefix differs
+        self.0.encoded_len()
+    }
+}
+
+impl RespDecode for RespAttribute {
+    const PREFIX: &'static str = "|";
+
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        match decode_frame(buf, Self::PREFIX)? {
+            RespFrame::Attribute(attrs) => Ok(attrs),
+            frame => Err(unexpected_frame("Attribute", frame)),
+        }
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        frame_length(buf, Self::PREFIX)
+    }
+}
+
+impl Deref for RespAttribute {
+    type Target = RespMap;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for RespAttribute {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl RespAttribute {
+    pub fn new() -> Self {
+        RespAttribute(RespMap::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_attribute() {
+        let mut attrs = RespAttribute::new();
+        attrs.insert("ttl", 3600.into());
+
+        let frame: RespFrame = attrs.into();
+        assert_eq!(&frame.encode(), b"|1\r\n+ttl\r\n:+3600\r\n");
+    }
+
+    #[test]
+    fn test_attribute_decode() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b"|1\r\n+key-popularity\r\n%1\r\n+a\r\n,0.1923\r\n:+5\r\n");
+
+        let frame = RespFrame::decode(&mut buf)?;
+        let mut popularity = RespMap::new();
+        popularity.insert("a", 0.1923.into());
+        let mut attrs = RespAttribute::new();
+        attrs.insert("key-popularity", popularity.into());
+        assert_eq!(frame, attrs.into());
+
+        // the reply the attribute describes follows as its own frame
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, RespFrame::Integer(5));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use crate::RespDecode;
+use crate::RespEncode;
+use crate::RespError;
+use crate::RespFrame;
+
+use bytes::{BufMut, BytesMut};
+
+use std::ops::Deref;
+
+use super::decoder::{decode_frame, frame_length, unexpected_frame};
+use super::{CRLF, CRLF_LEN};
+
+/// An integer outside the i64 range, kept as its decimal digits.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct BigNumber(pub(crate) String);
+
+//- big number: "([+|-]<number>\r\n"
+impl RespEncode for BigNumber {
+    fn encode_to<B: BufMut>(&self, buf: &mut B) {
+        buf.put_u8(b'(');
+        buf.put_slice(self.0.as_bytes());
+        buf.put_slice(CRLF);
+    }
+
+    fn encoded_len(&self) -> usize {
+        1 + self.0.len() + CRLF_LEN
+    }
+}
+
+impl RespDecode for BigNumber {
+    const PREFIX: &'static str = "(";
+
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        match decode_frame(buf, Self::PREFIX)? {
+            RespFrame::BigNumber(n) => Ok(n),
+            frame => Err(unexpected_frame("BigNumber", frame)),
+        }
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        frame_length(buf, Self::PREFIX)
+    }
+}
+
+impl Deref for BigNumber {
+    type Target = String;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl BigNumber {
+    pub fn new(s: impl Into<String>) -> Result<Self, RespError> {
+        let s = s.into();
+        let digits = s.strip_prefix(['+', '-']).unwrap_or(&s);
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(RespError::InvalidFrame(format!(
+                "invalid big number: {}",
+                s
+            )));
+        }
+        Ok(BigNumber(s))
+    }
+}
+
+impl From<i128> for BigNumber {
+    fn from(n: i128) -> Self {
+        BigNumber(n.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::RespFrame;
+    use anyhow::Result;
+
+    #[test]
+    fn test_big_number() -> Result<()> {
+        let frame: RespFrame =
+            BigNumber::new("3492890328409238509324850943850943825024385")?.into();
+        assert_eq!(
+            frame.encode(),
+            b"(3492890328409238509324850943850943825024385\r\n"
+        );
+
+        let frame: RespFrame = BigNumber::from(-(i64::MAX as i128) * 4).into();
+        assert_eq!(frame.encode(), b"(-36893488147419103228\r\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_big_number_decode() -> Result<()> {
+        let mut buf = BytesMut::from("(-3492890328409238509324850943850943825024385\r\n");
+        let frame = BigNumber::decode(&mut buf)?;
+        assert_eq!(
+            frame,
+            BigNumber::new("-3492890328409238509324850943850943825024385")?
+        );
+
+        let mut buf = BytesMut::from("(12a\r\n");
+        assert!(BigNumber::decode(&mut buf).is_err());
+
+        let mut buf = BytesMut::from("(1234");
+        let ret = BigNumber::decode(&mut buf);
+        assert_eq!(ret.unwrap_err(), RespError::NotComplete);
+
+        Ok(())
+    }
+}
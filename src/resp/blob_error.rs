@@ -0,0 +1,90 @@
+use crate::RespDecode;
+use crate::RespEncode;
+use crate::RespError;
+use crate::RespFrame;
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use std::ops::Deref;
+
+use super::decoder::{decode_frame, frame_length, unexpected_frame};
+use super::{header_len, put_header, CRLF, CRLF_LEN};
+
+/// A binary safe error, for messages that may contain CR or LF.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct BlobError(pub(crate) Bytes);
+
+//- blob error: "!<length>\r\n<error>\r\n"
+impl RespEncode for BlobError {
+    fn encode_to<B: BufMut>(&self, buf: &mut B) {
+        put_header(buf, b'!', self.len());
+        buf.put_slice(&self.0);
+        buf.put_slice(CRLF);
+    }
+
+    fn encoded_len(&self) -> usize {
+        header_len(self.len()) + self.len() + CRLF_LEN
+    }
+}
+
+impl RespDecode for BlobError {
+    const PREFIX: &'static str = "!";
+
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        match decode_frame(buf, Self::PREFIX)? {
+            RespFrame::BlobError(e) => Ok(e),
+            frame => Err(unexpected_frame("BlobError", frame)),
+        }
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        frame_length(buf, Self::PREFIX)
+    }
+}
+
+impl Deref for BlobError {
+    type Target = Bytes;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl BlobError {
+    pub fn new(s: impl Into<Bytes>) -> Self {
+        BlobError(s.into())
+    }
+}
+
+impl From<&str> for BlobError {
+    fn from(s: &str) -> Self {
+        BlobError(Bytes::copy_from_slice(s.as_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::RespFrame;
+    use anyhow::Result;
+
+    #[test]
+    fn test_blob_error() {
+        let frame: RespFrame = BlobError::from("SYNTAX invalid\r\nsyntax").into();
+        assert_eq!(frame.encode(), b"!22\r\nSYNTAX invalid\r\nsyntax\r\n");
+    }
+
+    #[test]
+    fn test_blob_error_decode() -> Result<()> {
+        let mut buf = BytesMut::from("!21\r\nSYNTAX invalid syntax\r\n");
+        let frame = BlobError::decode(&mut buf)?;
+        assert_eq!(frame, BlobError::from("SYNTAX invalid syntax"));
+
+        let mut buf = BytesMut::from("!21\r\nSYNTAX");
+        let ret = BlobError::decode(&mut buf);
+        assert_eq!(ret.unwrap_err(), RespError::NotComplete);
+
+        Ok(())
+    }
+}
@@ -1,14 +1,19 @@
 use crate::RespDecode;
 use crate::RespEncode;
 use crate::RespError;
-use bytes::BytesMut;
+use crate::RespFrame;
+use bytes::{BufMut, BytesMut};
 
-use super::extract_fixed_data;
+use super::decoder::{decode_frame, frame_length, unexpected_frame};
 
 //- boolean: "#<t|f>\r\n"
 impl RespEncode for bool {
-    fn encode(self) -> Vec<u8> {
-        format!("#{}\r\n", if self { "t" } else { "f" }).into_bytes()
+    fn encode_to<B: BufMut>(&self, buf: &mut B) {
+        buf.put_slice(if *self { b"#t\r\n" } else { b"#f\r\n" });
+    }
+
+    fn encoded_len(&self) -> usize {
+        4
     }
 }
 
@@ -16,18 +21,14 @@ impl RespDecode for bool {
     const PREFIX: &'static str = "#";
 
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        match extract_fixed_data(buf, "#t\r\n", "Bool") {
-            Ok(_) => Ok(true),
-            Err(RespError::NotComplete) => Err(RespError::NotComplete),
-            Err(_) => match extract_fixed_data(buf, "#f\r\n", "Bool") {
-                Ok(_) => Ok(false),
-                Err(e) => Err(e),
-            },
+        match decode_frame(buf, Self::PREFIX)? {
+            RespFrame::Boolean(b) => Ok(b),
+            frame => Err(unexpected_frame("Boolean", frame)),
         }
     }
 
-    fn expect_length(_buf: &[u8]) -> Result<usize, RespError> {
-        Ok(4)
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        frame_length(buf, Self::PREFIX)
     }
 }
 
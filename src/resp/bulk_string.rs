@@ -1,30 +1,31 @@
 use crate::RespDecode;
 use crate::RespEncode;
 use crate::RespError;
+use crate::RespFrame;
 
-use bytes::Buf;
-use bytes::BytesMut;
+use bytes::{BufMut, Bytes, BytesMut};
 
 use std::ops::Deref;
 
-use super::extract_fixed_data;
-use super::parse_length;
-use super::CRLF_LEN;
+use super::decoder::{decode_frame, frame_length, unexpected_frame};
+use super::{header_len, put_header, CRLF, CRLF_LEN};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct RespNullBulkString;
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
-pub struct BulkString(pub(crate) Vec<u8>);
+pub struct BulkString(pub(crate) Bytes);
 
 //- bulk string: "$<length>\r\n<data>\r\n"
 impl RespEncode for BulkString {
-    fn encode(self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(self.len() + 16);
-        buf.extend_from_slice(format!("${}\r\n", self.len()).as_bytes());
-        buf.extend_from_slice(&self);
-        buf.extend_from_slice(b"\r\n");
-        buf
+    fn encode_to<B: BufMut>(&self, buf: &mut B) {
+        put_header(buf, b'$', self.len());
+        buf.put_slice(&self.0);
+        buf.put_slice(CRLF);
+    }
+
+    fn encoded_len(&self) -> usize {
+        header_len(self.len()) + self.len() + CRLF_LEN
     }
 }
 
@@ -32,28 +33,25 @@ impl RespDecode for BulkString {
     const PREFIX: &'static str = "$";
 
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        let remained = &buf[end + CRLF_LEN..];
-        if remained.len() < len + CRLF_LEN {
-            return Err(RespError::NotComplete);
+        match decode_frame(buf, Self::PREFIX)? {
+            RespFrame::BulkString(s) => Ok(s),
+            frame => Err(unexpected_frame("BulkString", frame)),
         }
-
-        buf.advance(end + CRLF_LEN);
-
-        let data = buf.split_to(len + CRLF_LEN);
-        Ok(BulkString::new(data[..len].to_vec()))
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        Ok(end + CRLF_LEN + len + CRLF_LEN)
+        frame_length(buf, Self::PREFIX)
     }
 }
 
 //- null bulk string: "$-1\r\n"
 impl RespEncode for RespNullBulkString {
-    fn encode(self) -> Vec<u8> {
-        b"$-1\r\n".to_vec()
+    fn encode_to<B: BufMut>(&self, buf: &mut B) {
+        buf.put_slice(b"$-1\r\n");
+    }
+
+    fn encoded_len(&self) -> usize {
+        5
     }
 }
 
@@ -61,17 +59,19 @@ impl RespDecode for RespNullBulkString {
     const PREFIX: &'static str = "$";
 
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        extract_fixed_data(buf, "$-1\r\n", "NullBulkString")?;
-        Ok(RespNullBulkString)
+        match decode_frame(buf, Self::PREFIX)? {
+            RespFrame::NullBulkString(s) => Ok(s),
+            frame => Err(unexpected_frame("NullBulkString", frame)),
+        }
     }
 
-    fn expect_length(_buf: &[u8]) -> Result<usize, RespError> {
-        Ok(5)
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        frame_length(buf, Self::PREFIX)
     }
 }
 
 impl Deref for BulkString {
-    type Target = Vec<u8>;
+    type Target = Bytes;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -79,26 +79,26 @@ impl Deref for BulkString {
 }
 
 impl BulkString {
-    pub fn new(s: impl Into<Vec<u8>>) -> Self {
+    pub fn new(s: impl Into<Bytes>) -> Self {
         BulkString(s.into())
     }
 }
 
 impl From<&str> for BulkString {
     fn from(s: &str) -> Self {
-        BulkString(s.as_bytes().to_vec())
+        BulkString(Bytes::copy_from_slice(s.as_bytes()))
     }
 }
 
 impl From<&[u8]> for BulkString {
     fn from(s: &[u8]) -> Self {
-        BulkString(s.to_vec())
+        BulkString(Bytes::copy_from_slice(s))
     }
 }
 
 impl<const N: usize> From<&[u8; N]> for BulkString {
     fn from(s: &[u8; N]) -> Self {
-        BulkString(s.to_vec())
+        BulkString(Bytes::copy_from_slice(s))
     }
 }
 
@@ -0,0 +1,508 @@
+use crate::{
+    BigNumber, BlobError, BulkString, RespArray, RespAttribute, RespError, RespFrame, RespMap,
+    RespNull, RespNullArray, RespNullBulkString, RespPush, RespSet, SimpleError, SimpleString,
+    VerbatimString,
+};
+use bytes::{Bytes, BytesMut};
+
+use super::{CRLF, CRLF_LEN};
+
+/// Incremental RESP decoder.
+///
+/// Frame boundaries are found by a scanner that remembers where it stopped, so bytes that
+/// arrived in an earlier read are never looked at again, and nested aggregates are tracked
+/// with an explicit stack rather than by re-walking them. Only once a whole frame is
+/// buffered is it split off the read buffer and turned into a `RespFrame`, with bulk
+/// payloads sliced out of that buffer instead of copied.
+#[derive(Debug, Default)]
+pub struct RespDecoder {
+    limits: ProtocolLimits,
+    // start of the next frame (or element) to scan, relative to the read buffer
+    pos: usize,
+    // how far the search for the current line's CRLF already got
+    line_scanned: usize,
+    // aggregates that still wait for elements, innermost last
+    pending: Vec<Pending>,
+}
+
+/// Bounds on what a peer may send, so a single header can't make the server buffer or
+/// nest without end. Each is a config parameter: `proto-max-bulk-len`,
+/// `proto-max-multibulk-len`, `proto-max-nesting-depth` and `client-query-buffer-limit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolLimits {
+    pub max_bulk_len: usize,
+    pub max_multibulk_len: usize,
+    pub max_depth: usize,
+    pub max_query_buffer: usize,
+}
+
+impl Default for ProtocolLimits {
+    fn default() -> Self {
+        Self {
+            max_bulk_len: 512 * 1024 * 1024,
+            max_multibulk_len: 1024 * 1024,
+            max_depth: 32,
+            max_query_buffer: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Pending {
+    Frames(usize),
+    Streamed,
+    StreamedString,
+}
+
+impl RespDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_limits(limits: ProtocolLimits) -> Self {
+        Self {
+            limits,
+            ..Self::default()
+        }
+    }
+
+    pub fn limits(&self) -> &ProtocolLimits {
+        &self.limits
+    }
+
+    /// Take the next complete frame off `buf`, or return `None` and remember the progress
+    /// until more data has been read into it.
+    pub fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<RespFrame>, RespError> {
+        let Some(len) = self.scan(buf)? else {
+            return Ok(None);
+        };
+        let data = buf.split_to(len).freeze();
+        parse_frame(&data, &mut 0).map(Some)
+    }
+
+    /// The length of the first frame in `buf` once all of it has been buffered.
+    pub fn scan(&mut self, buf: &[u8]) -> Result<Option<usize>, RespError> {
+        let ret = self.scan_frames(buf);
+        match ret {
+            Ok(None) if buf.len() > self.limits.max_query_buffer => {
+                self.reset();
+                Err(RespError::LimitExceeded("query buffer limit exceeded"))
+            }
+            Ok(None) => Ok(None),
+            _ => {
+                self.reset();
+                ret
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.pos = 0;
+        self.line_scanned = 0;
+        self.pending.clear();
+    }
+
+    fn scan_frames(&mut self, buf: &[u8]) -> Result<Option<usize>, RespError> {
+        loop {
+            let Some(&prefix) = buf.get(self.pos) else {
+                return Ok(None);
+            };
+            let Some(end) = self.find_line_end(buf) else {
+                return Ok(None);
+            };
+            let line = &buf[self.pos + 1..end];
+            let next = end + CRLF_LEN;
+
+            match (self.pending.last(), prefix) {
+                (Some(Pending::StreamedString), b';') => match self.bulk_len(line)? {
+                    0 => {
+                        self.pending.pop();
+                        self.pos = next;
+                    }
+                    len => {
+                        let Some(total) = payload_end(buf, next, len)? else {
+                            return Ok(None);
+                        };
+                        self.pos = total;
+                        continue;
+                    }
+                },
+                (Some(Pending::StreamedString), _) => {
+                    return Err(RespError::InvalidFrame(format!(
+                        "expect streamed string chunk, got: {:?}",
+                        &buf[self.pos..next]
+                    )))
+                }
+                (Some(Pending::Streamed), b'.') if line.is_empty() => {
+                    self.pending.pop();
+                    self.pos = next;
+                }
+                (_, b'+' | b'-' | b':' | b'_' | b'#' | b',' | b'(') => self.pos = next,
+                (_, b'$') if line == b"?" => {
+                    self.nest()?;
+                    self.pending.push(Pending::StreamedString);
+                    self.pos = next;
+                    continue;
+                }
+                (_, b'$') if line == b"-1" => self.pos = next,
+                (_, b'$' | b'=' | b'!') => {
+                    let Some(total) = payload_end(buf, next, self.bulk_len(line)?)? else {
+                        return Ok(None);
+                    };
+                    self.pos = total;
+                }
+                (_, b'*' | b'%' | b'~') if line == b"?" => {
+                    self.nest()?;
+                    self.pending.push(Pending::Streamed);
+                    self.pos = next;
+                    continue;
+                }
+                (_, b'*') if line == b"-1" => self.pos = next,
+                (_, b'*' | b'~' | b'>' | b'%' | b'|') => {
+                    let len = match parse_len(line) {
+                        Ok(len) if len <= self.limits.max_multibulk_len => len,
+                        _ => return Err(RespError::LimitExceeded("invalid multibulk length")),
+                    };
+                    let len = match prefix {
+                        b'%' | b'|' => len * 2,
+                        _ => len,
+                    };
+                    self.pos = next;
+                    if len > 0 {
+                        self.nest()?;
+                        self.pending.push(Pending::Frames(len));
+                        continue;
+                    }
+                }
+                _ => {
+                    return Err(RespError::InvalidFrameType(format!(
+                        "unknown frame type: {:?}",
+                        &buf[self.pos..next]
+                    )))
+                }
+            }
+
+            if let Some(len) = self.complete() {
+                return Ok(Some(len));
+            }
+        }
+    }
+
+    fn bulk_len(&self, line: &[u8]) -> Result<usize, RespError> {
+        match parse_len(line) {
+            Ok(len) if len <= self.limits.max_bulk_len => Ok(len),
+            _ => Err(RespError::LimitExceeded("invalid bulk length")),
+        }
+    }
+
+    fn nest(&self) -> Result<(), RespError> {
+        if self.pending.len() >= self.limits.max_depth {
+            return Err(RespError::LimitExceeded("too many nested aggregates"));
+        }
+        Ok(())
+    }
+
+    // one frame ended at pos, count it against the enclosing aggregates
+    fn complete(&mut self) -> Option<usize> {
+        loop {
+            match self.pending.last_mut() {
+                None => return Some(self.pos),
+                Some(Pending::Frames(n)) => {
+                    *n -= 1;
+                    if *n > 0 {
+                        return None;
+                    }
+                    self.pending.pop();
+                }
+                Some(_) => return None,
+            }
+        }
+    }
+
+    fn find_line_end(&mut self, buf: &[u8]) -> Option<usize> {
+        let from = self.line_scanned.max(self.pos + 1);
+        match find_crlf(buf, from) {
+            Some(end) => Some(end),
+            None => {
+                // a trailing '\r' may still be followed by its '\n'
+                self.line_scanned = buf.len().saturating_sub(1).max(from);
+                None
+            }
+        }
+    }
+}
+
+/// Decode a single frame of the type announced by `prefix`, leaving `buf` untouched
+/// unless the whole frame is available.
+pub(crate) fn decode_frame(buf: &mut BytesMut, prefix: &str) -> Result<RespFrame, RespError> {
+    check_prefix(buf, prefix)?;
+    RespDecoder::new()
+        .decode(buf)?
+        .ok_or(RespError::NotComplete)
+}
+
+pub(crate) fn frame_length(buf: &[u8], prefix: &str) -> Result<usize, RespError> {
+    check_prefix(buf, prefix)?;
+    RespDecoder::new().scan(buf)?.ok_or(RespError::NotComplete)
+}
+
+pub(crate) fn unexpected_frame(expect: &str, frame: RespFrame) -> RespError {
+    RespError::InvalidFrameType(format!("expect: {}, got: {:?}", expect, frame))
+}
+
+fn check_prefix(buf: &[u8], prefix: &str) -> Result<(), RespError> {
+    if buf.is_empty() {
+        return Err(RespError::NotComplete);
+    }
+    if !buf.starts_with(prefix.as_bytes()) {
+        return Err(RespError::InvalidFrameType(format!(
+            "expect prefix: {}, got: {:?}",
+            prefix, buf
+        )));
+    }
+    Ok(())
+}
+
+fn find_crlf(buf: &[u8], from: usize) -> Option<usize> {
+    buf.get(from..)?
+        .windows(CRLF_LEN)
+        .position(|w| w == CRLF)
+        .map(|i| from + i)
+}
+
+fn parse_len(line: &[u8]) -> Result<usize, RespError> {
+    Ok(std::str::from_utf8(line)
+        .map_err(|_| RespError::InvalidFrame(format!("invalid length: {:?}", line)))?
+        .parse()?)
+}
+
+// end of a "<payload>\r\n" of len bytes starting at start, if it has been fully read
+fn payload_end(buf: &[u8], start: usize, len: usize) -> Result<Option<usize>, RespError> {
+    let end = start + len;
+    if buf.len() < end + CRLF_LEN {
+        return Ok(None);
+    }
+    if &buf[end..end + CRLF_LEN] != CRLF {
+        return Err(RespError::InvalidFrame(format!(
+            "payload longer than its declared length {}",
+            len
+        )));
+    }
+    Ok(Some(end + CRLF_LEN))
+}
+
+// parse a frame the scanner has already found complete
+fn parse_frame(data: &Bytes, pos: &mut usize) -> Result<RespFrame, RespError> {
+    let prefix = *data.get(*pos).ok_or(RespError::NotComplete)?;
+    let end = find_crlf(data, *pos + 1).ok_or(RespError::NotComplete)?;
+    let line = &data[*pos + 1..end];
+    *pos = end + CRLF_LEN;
+
+    let frame = match prefix {
+        b'+' => SimpleString::new(String::from_utf8_lossy(line)).into(),
+        b'-' => SimpleError::new(String::from_utf8_lossy(line)).into(),
+        b':' => RespFrame::Integer(String::from_utf8_lossy(line).parse()?),
+        b',' => RespFrame::Double(String::from_utf8_lossy(line).parse()?),
+        b'(' => BigNumber::new(String::from_utf8_lossy(line))?.into(),
+        b'_' if line.is_empty() => RespNull.into(),
+        b'#' if line == b"t" => true.into(),
+        b'#' if line == b"f" => false.into(),
+        b'$' if line == b"-1" => RespNullBulkString.into(),
+        b'$' if line == b"?" => {
+            let mut chunks = vec![];
+            loop {
+                let end = find_crlf(data, *pos + 1).ok_or(RespError::NotComplete)?;
+                let len = parse_len(&data[*pos + 1..end])?;
+                *pos = end + CRLF_LEN;
+                if len == 0 {
+                    break;
+                }
+                chunks.push(payload(data, pos, len)?);
+            }
+            match chunks.len() {
+                1 => BulkString::new(chunks.remove(0)).into(),
+                _ => BulkString::new(chunks.concat()).into(),
+            }
+        }
+        b'$' => BulkString::new(payload(data, pos, parse_len(line)?)?).into(),
+        b'!' => BlobError::new(payload(data, pos, parse_len(line)?)?).into(),
+        b'=' => {
+            let data = payload(data, pos, parse_len(line)?)?;
+            VerbatimString::from_payload(data)?.into()
+        }
+        b'*' if line == b"-1" => RespNullArray.into(),
+        b'*' | b'~' | b'>' => {
+            let items = match line {
+                b"?" => parse_streamed(data, pos, parse_frame)?,
+                _ => (0..parse_len(line)?)
+                    .map(|_| parse_frame(data, pos))
+                    .collect::<Result<Vec<_>, _>>()?,
+            };
+            match prefix {
+                b'*' => RespArray::new(items).into(),
+                b'~' => RespSet::new(items).into(),
+                _ => RespPush::new(items).into(),
+            }
+        }
+        b'%' | b'|' => {
+            let entry = |data: &Bytes, pos: &mut usize| -> Result<_, RespError> {
+                Ok((parse_frame(data, pos)?, parse_frame(data, pos)?))
+            };
+            let entries = match line {
+                b"?" => parse_streamed(data, pos, entry)?,
+                _ => (0..parse_len(line)?)
+                    .map(|_| entry(data, pos))
+                    .collect::<Result<Vec<_>, _>>()?,
+            };
+            let map = entries.into_iter().collect::<RespMap>();
+            match prefix {
+                b'%' => map.into(),
+                _ => RespAttribute(map).into(),
+            }
+        }
+        _ => {
+            return Err(RespError::InvalidFrameType(format!(
+                "unknown frame type: {:?}",
+                &data[..*pos]
+            )))
+        }
+    };
+    Ok(frame)
+}
+
+fn payload(data: &Bytes, pos: &mut usize, len: usize) -> Result<Bytes, RespError> {
+    let end = payload_end(data, *pos, len)?.ok_or(RespError::NotComplete)?;
+    let payload = data.slice(*pos..*pos + len);
+    *pos = end;
+    Ok(payload)
+}
+
+// elements up to the "." that ends a streamed aggregate
+fn parse_streamed<T>(
+    data: &Bytes,
+    pos: &mut usize,
+    parse: impl Fn(&Bytes, &mut usize) -> Result<T, RespError>,
+) -> Result<Vec<T>, RespError> {
+    let mut items = vec![];
+    while !data[*pos..].starts_with(b".\r\n") {
+        items.push(parse(data, pos)?);
+    }
+    *pos += b".\r\n".len();
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_decoder_resumes_partial_frames() -> Result<()> {
+        let frame = b"*3\r\n$3\r\nset\r\n%1\r\n+a\r\n*1\r\n:+1\r\n$5\r\nhello\r\n";
+        let mut decoder = RespDecoder::new();
+        let mut buf = BytesMut::new();
+
+        // feed the frame one byte at a time, it must only complete on the last one
+        for (i, b) in frame.iter().enumerate() {
+            buf.extend_from_slice(&[*b]);
+            let ret = decoder.decode(&mut buf)?;
+            if i + 1 < frame.len() {
+                assert_eq!(ret, None);
+                continue;
+            }
+            let mut map = RespMap::new();
+            map.insert("a", RespArray::new([1.into()]).into());
+            let expected = RespArray::new([b"set".into(), map.into(), b"hello".into()]);
+            assert_eq!(ret, Some(expected.into()));
+        }
+        assert!(buf.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decoder_pipeline() -> Result<()> {
+        let mut buf = BytesMut::from(&b"*1\r\n$4\r\nping\r\n*-1\r\n$-1\r\n*0\r\n*1\r\n$4"[..]);
+        let mut decoder = RespDecoder::new();
+        assert_eq!(
+            decoder.decode(&mut buf)?,
+            Some(RespArray::new([b"ping".into()]).into())
+        );
+        assert_eq!(decoder.decode(&mut buf)?, Some(RespNullArray.into()));
+        assert_eq!(decoder.decode(&mut buf)?, Some(RespNullBulkString.into()));
+        assert_eq!(decoder.decode(&mut buf)?, Some(RespArray::new([]).into()));
+        assert_eq!(decoder.decode(&mut buf)?, None);
+
+        buf.extend_from_slice(b"\r\nping\r\n");
+        assert_eq!(
+            decoder.decode(&mut buf)?,
+            Some(RespArray::new([b"ping".into()]).into())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decoder_bulk_payload_is_not_copied() -> Result<()> {
+        let mut buf = BytesMut::from(&b"$5\r\nhello\r\n"[..]);
+        let start = buf.as_ptr() as usize;
+        let Some(RespFrame::BulkString(s)) = RespDecoder::new().decode(&mut buf)? else {
+            panic!("expect a bulk string");
+        };
+        assert_eq!(s.as_ptr() as usize, start + b"$5\r\n".len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decoder_limits() {
+        let limits = ProtocolLimits {
+            max_bulk_len: 8,
+            max_multibulk_len: 4,
+            max_depth: 2,
+            max_query_buffer: 64,
+        };
+        let decode =
+            |input: &[u8]| RespDecoder::with_limits(limits).decode(&mut BytesMut::from(input));
+
+        assert!(matches!(decode(b"$8\r\n12345678\r\n"), Ok(Some(_))));
+        assert_eq!(
+            decode(b"$9\r\n"),
+            Err(RespError::LimitExceeded("invalid bulk length"))
+        );
+        assert_eq!(
+            decode(b"$-5\r\n"),
+            Err(RespError::LimitExceeded("invalid bulk length"))
+        );
+        assert_eq!(
+            decode(b"*2147483647\r\n"),
+            Err(RespError::LimitExceeded("invalid multibulk length"))
+        );
+        assert_eq!(
+            decode(b"%5\r\n"),
+            Err(RespError::LimitExceeded("invalid multibulk length"))
+        );
+        assert!(matches!(decode(b"*1\r\n*1\r\n:+1\r\n"), Ok(Some(_))));
+        assert_eq!(
+            decode(b"*1\r\n*1\r\n*1\r\n"),
+            Err(RespError::LimitExceeded("too many nested aggregates"))
+        );
+        assert_eq!(
+            decode(&[b'+'; 65]),
+            Err(RespError::LimitExceeded("query buffer limit exceeded"))
+        );
+    }
+
+    #[test]
+    fn test_decoder_rejects_bad_frames() {
+        let mut decoder = RespDecoder::new();
+        let mut buf = BytesMut::from(&b"$3\r\nhello\r\n"[..]);
+        assert!(decoder.decode(&mut buf).is_err());
+
+        let mut buf = BytesMut::from(&b"*1\r\n?foo\r\n"[..]);
+        assert!(decoder.decode(&mut buf).is_err());
+
+        let mut buf = BytesMut::from(&b"*x\r\n"[..]);
+        assert!(decoder.decode(&mut buf).is_err());
+    }
+}
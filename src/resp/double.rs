@@ -1,24 +1,38 @@
 use crate::RespDecode;
 use crate::RespEncode;
 use crate::RespError;
+use crate::RespFrame;
 
-use super::extract_simple_frame_data;
-use super::CRLF_LEN;
+use bytes::{BufMut, BytesMut};
+use std::fmt;
 
-use bytes::BytesMut;
+use super::decoder::{decode_frame, frame_length, unexpected_frame};
+use super::{display_len, put_display, CRLF, CRLF_LEN};
 
 //- double: ",[<+|->]<integral>[.<fractional>][<E|e>[sign]<exponent>]\r\n"
 impl RespEncode for f64 {
-    fn encode(self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(64);
-        let ret = if self.abs() > 1e+8 || self.abs() < 1e-8 {
-            format!(",{:+e}\r\n", self)
+    fn encode_to<B: BufMut>(&self, buf: &mut B) {
+        buf.put_u8(b',');
+        put_display(buf, Double(*self));
+        buf.put_slice(CRLF);
+    }
+
+    fn encoded_len(&self) -> usize {
+        1 + display_len(Double(*self)) + CRLF_LEN
+    }
+}
+
+struct Double(f64);
+
+impl fmt::Display for Double {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let v = self.0;
+        if v.abs() > 1e+8 || v.abs() < 1e-8 {
+            write!(f, "{:+e}", v)
         } else {
-            let sign = if self < 0.0 { "" } else { "+" };
-            format!(",{}{}\r\n", sign, self)
-        };
-        buf.extend_from_slice(ret.as_bytes());
-        buf
+            let sign = if v < 0.0 { "" } else { "+" };
+            write!(f, "{}{}", sign, v)
+        }
     }
 }
 
@@ -26,18 +40,14 @@ impl RespDecode for f64 {
     const PREFIX: &'static str = ",";
 
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-
-        let data = buf.split_to(end + CRLF_LEN);
-
-        let s = String::from_utf8_lossy(&data[Self::PREFIX.len()..end]);
-
-        Ok(s.parse()?)
+        match decode_frame(buf, Self::PREFIX)? {
+            RespFrame::Double(d) => Ok(d),
+            frame => Err(unexpected_frame("Double", frame)),
+        }
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-        Ok(end + CRLF_LEN)
+        frame_length(buf, Self::PREFIX)
     }
 }
 
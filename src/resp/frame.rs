@@ -1,10 +1,13 @@
 use crate::{
-    BulkString, RespArray, RespDecode, RespError, RespMap, RespNull, RespNullArray,
-    RespNullBulkString, RespSet, SimpleError, SimpleString,
+    BigNumber, BlobError, BulkString, RespArray, RespAttribute, RespDecode, RespError, RespMap,
+    RespNull, RespNullArray, RespNullBulkString, RespPush, RespSet, RespStreamed, SimpleError,
+    SimpleString, VerbatimString,
 };
 use bytes::BytesMut;
 use enum_dispatch::enum_dispatch;
 
+use super::decoder::{decode_frame, frame_length};
+
 #[enum_dispatch(RespEncode)]
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum RespFrame {
@@ -20,85 +23,23 @@ pub enum RespFrame {
     Double(f64),
     Map(RespMap),
     Set(RespSet),
+    VerbatimString(VerbatimString),
+    BigNumber(BigNumber),
+    BlobError(BlobError),
+    Attribute(RespAttribute),
+    Push(RespPush),
+    Streamed(RespStreamed),
 }
 
 impl RespDecode for RespFrame {
     const PREFIX: &'static str = "";
 
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let mut iter = buf.iter().peekable();
-        match iter.peek() {
-            Some(b'+') => {
-                let frame = SimpleString::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b'-') => {
-                let frame = SimpleError::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b':') => {
-                let frame = i64::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b'$') => match RespNullBulkString::decode(buf) {
-                Ok(frame) => Ok(frame.into()),
-                Err(RespError::NotComplete) => Err(RespError::NotComplete),
-                Err(_) => {
-                    let frame = BulkString::decode(buf)?;
-                    Ok(frame.into())
-                }
-            },
-            Some(b'*') => match RespNullArray::decode(buf) {
-                Ok(frame) => Ok(frame.into()),
-                Err(RespError::NotComplete) => Err(RespError::NotComplete),
-                Err(_) => {
-                    let frame = RespArray::decode(buf)?;
-                    Ok(frame.into())
-                }
-            },
-            Some(b'_') => {
-                let frame = RespNull::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b'#') => {
-                let frame = bool::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b',') => {
-                let frame = f64::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b'%') => {
-                let frame = RespMap::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b'~') => {
-                let frame = RespSet::decode(buf)?;
-                Ok(frame.into())
-            }
-            None => Err(RespError::NotComplete),
-            _ => Err(RespError::InvalidFrameType(format!(
-                "expect_length: unknown frame type: {:?}",
-                buf
-            ))),
-        }
+        decode_frame(buf, Self::PREFIX)
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let mut iter = buf.iter().peekable();
-        match iter.peek() {
-            Some(b'+') => SimpleString::expect_length(buf),
-            Some(b'-') => SimpleError::expect_length(buf),
-            Some(b':') => i64::expect_length(buf),
-            Some(b'$') => BulkString::expect_length(buf),
-            Some(b'*') => RespArray::expect_length(buf),
-            Some(b'_') => RespNull::expect_length(buf),
-            Some(b'#') => bool::expect_length(buf),
-            Some(b',') => f64::expect_length(buf),
-            Some(b'%') => RespMap::expect_length(buf),
-            Some(b'~') => RespSet::expect_length(buf),
-            _ => Err(RespError::NotComplete),
-        }
+        frame_length(buf, Self::PREFIX)
     }
 }
 
@@ -110,12 +51,133 @@ impl From<&str> for RespFrame {
 
 impl From<&[u8]> for RespFrame {
     fn from(s: &[u8]) -> Self {
-        BulkString(s.to_vec()).into()
+        BulkString::from(s).into()
     }
 }
 
 impl<const N: usize> From<&[u8; N]> for RespFrame {
     fn from(s: &[u8; N]) -> Self {
-        BulkString(s.to_vec()).into()
+        BulkString::from(s).into()
+    }
+}
+
+/// The protocol version negotiated with HELLO.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RespVersion {
+    #[default]
+    Resp2,
+    Resp3,
+}
+
+impl RespFrame {
+    /// Rewrite RESP3-only types into the closest RESP2 equivalent, recursively.
+    pub fn into_resp2(self) -> RespFrame {
+        match self {
+            RespFrame::Null(_) => RespNullBulkString.into(),
+            RespFrame::Boolean(b) => RespFrame::Integer(b as i64),
+            RespFrame::Double(d) => BulkString::new(d.to_string()).into(),
+            RespFrame::Array(arr) => RespArray::new(
+                arr.0
+                    .into_iter()
+                    .map(RespFrame::into_resp2)
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            RespFrame::Streamed(s) => s.into_sized().into_resp2(),
+            RespFrame::VerbatimString(s) => BulkString::new(s.data).into(),
+            RespFrame::BigNumber(n) => BulkString::new(n.0).into(),
+            RespFrame::BlobError(e) => {
+                SimpleError::new(String::from_utf8_lossy(&e).replace(['\r', '\n'], " ")).into()
+            }
+            RespFrame::Push(push) => RespArray::new(
+                push.0
+                    .into_iter()
+                    .map(RespFrame::into_resp2)
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            RespFrame::Set(set) => RespArray::new(
+                set.0
+                    .into_iter()
+                    .map(RespFrame::into_resp2)
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            RespFrame::Map(map) | RespFrame::Attribute(RespAttribute(map)) => RespArray::new(
+                map.into_iter()
+                    .flat_map(|(k, v)| [k.into_resp2(), v.into_resp2()])
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            frame => frame,
+        }
+    }
+
+    pub fn into_version(self, version: RespVersion) -> RespFrame {
+        match version {
+            RespVersion::Resp2 => self.into_resp2(),
+            RespVersion::Resp3 => self,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RespEncode;
+
+    #[test]
+    fn test_into_resp2() {
+        let mut map = RespMap::new();
+        map.insert(BulkString::from("flag"), true.into());
+        map.insert(BulkString::from("score"), 1.5.into());
+        map.insert(
+            BulkString::from("items"),
+            RespSet::new([RespNull.into(), b"a".into()]).into(),
+        );
+
+        let frame: RespFrame = map.into();
+        let expected = RespArray::new([
+            b"flag".into(),
+            1.into(),
+            b"score".into(),
+            b"1.5".into(),
+            b"items".into(),
+            RespArray::new([RespNullBulkString.into(), b"a".into()]).into(),
+        ]);
+        assert_eq!(frame.into_resp2(), expected.into());
+    }
+
+    #[test]
+    fn test_encoded_len_is_exact() {
+        let mut map = RespMap::new();
+        map.insert(BulkString::from("k"), (-1.5e-9).into());
+        map.insert(-42, RespNull.into());
+        let frames: Vec<RespFrame> = vec![
+            SimpleString::new("OK").into(),
+            SimpleError::new("ERR oops").into(),
+            0.into(),
+            i64::MIN.into(),
+            BulkString::new(vec![b'x'; 1000]).into(),
+            RespNullBulkString.into(),
+            RespNullArray.into(),
+            true.into(),
+            123.456.into(),
+            f64::INFINITY.into(),
+            map.into(),
+            RespSet::new([b"a".into()]).into(),
+            VerbatimString::text("hello").into(),
+            BigNumber::from(i128::MAX).into(),
+            BlobError::from("ERR\r\nblob").into(),
+            RespPush::new([b"message".into()]).into(),
+            RespStreamed::String(vec![b"ab".to_vec(), vec![], b"c".to_vec()]).into(),
+            RespStreamed::Map(vec![(b"a".into(), 1.into())]).into(),
+        ];
+
+        let nested: RespFrame = RespArray::new(frames.clone()).into();
+        for frame in frames.into_iter().chain([nested]) {
+            let len = frame.encoded_len();
+            assert_eq!(frame.encode().len(), len);
+        }
     }
 }
@@ -1,16 +1,25 @@
 use crate::RespDecode;
 use crate::RespEncode;
 use crate::RespError;
-use bytes::BytesMut;
+use crate::RespFrame;
+use bytes::{BufMut, BytesMut};
 
-use super::extract_simple_frame_data;
-use super::CRLF_LEN;
+use super::decoder::{decode_frame, frame_length, unexpected_frame};
+use super::{display_len, put_display, CRLF, CRLF_LEN};
 
 //- integer: ":[<+|->]<value>\r\n"
 impl RespEncode for i64 {
-    fn encode(self) -> Vec<u8> {
-        let sign = if self < 0 { "" } else { "+" };
-        format!(":{}{}\r\n", sign, self).into_bytes()
+    fn encode_to<B: BufMut>(&self, buf: &mut B) {
+        buf.put_u8(b':');
+        if *self >= 0 {
+            buf.put_u8(b'+');
+        }
+        put_display(buf, self);
+        buf.put_slice(CRLF);
+    }
+
+    fn encoded_len(&self) -> usize {
+        1 + (*self >= 0) as usize + display_len(self) + CRLF_LEN
     }
 }
 
@@ -18,18 +27,14 @@ impl RespDecode for i64 {
     const PREFIX: &'static str = ":";
 
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-
-        let data = buf.split_to(end + CRLF_LEN);
-
-        let s = String::from_utf8_lossy(&data[Self::PREFIX.len()..end]);
-
-        Ok(s.parse()?)
+        match decode_frame(buf, Self::PREFIX)? {
+            RespFrame::Integer(n) => Ok(n),
+            frame => Err(unexpected_frame("Integer", frame)),
+        }
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-        Ok(end + CRLF_LEN)
+        frame_length(buf, Self::PREFIX)
     }
 }
 
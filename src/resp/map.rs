@@ -2,34 +2,42 @@ use crate::resp::RespFrame;
 use crate::RespDecode;
 use crate::RespEncode;
 use crate::RespError;
-use crate::SimpleString;
+use crate::{RespArray, RespAttribute, RespPush, RespSet};
 
-use bytes::Buf;
-use bytes::BytesMut;
+use bytes::{BufMut, BytesMut};
 
-use std::collections::BTreeMap;
-use std::ops::{Deref, DerefMut};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::ops::Deref;
 
-use super::calc_total_length;
-use super::parse_length;
-use super::BUF_CAP;
-use super::CRLF_LEN;
+use super::decoder::{decode_frame, frame_length, unexpected_frame};
+use super::{header_len, put_header};
 
+/// Key/value pairs in insertion order, keyed by any frame type.
+///
+/// Frames like doubles are not `Eq`/`Hash`, so `insert` and `get` are a linear scan; building
+/// a whole map by collecting goes through a hash index instead, as that is how decoded maps
+/// and replies of any size are made.
 #[derive(Debug, Clone, PartialEq, PartialOrd, Default)]
-pub struct RespMap(pub(crate) BTreeMap<String, RespFrame>);
+pub struct RespMap(pub(crate) Vec<(RespFrame, RespFrame)>);
 
 //- map: "%<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n>"
 impl RespEncode for RespMap {
-    fn encode(self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(BUF_CAP);
-
-        buf.extend_from_slice(format!("%{}\r\n", self.len()).as_bytes());
-        for (key, value) in self.0 {
-            buf.extend_from_slice(&SimpleString::new(key).encode());
-
-            buf.extend_from_slice(&value.encode());
+    fn encode_to<B: BufMut>(&self, buf: &mut B) {
+        put_header(buf, b'%', self.len());
+        for (key, value) in &self.0 {
+            key.encode_to(buf);
+            value.encode_to(buf);
         }
-        buf
+    }
+
+    fn encoded_len(&self) -> usize {
+        header_len(self.len())
+            + self
+                .iter()
+                .map(|(k, v)| k.encoded_len() + v.encoded_len())
+                .sum::<usize>()
     }
 }
 
@@ -37,47 +45,109 @@ impl RespDecode for RespMap {
     const PREFIX: &'static str = "%";
 
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        let total = calc_total_length(buf, len, end, Self::PREFIX)?;
-        if total > buf.len() {
-            return Err(RespError::NotComplete);
-        }
-        buf.advance(end + CRLF_LEN);
-
-        let mut map = RespMap::new();
-        for _ in 0..len {
-            let key = SimpleString::decode(buf)?;
-            let value = RespFrame::decode(buf)?;
-            map.insert(key.0, value);
+        match decode_frame(buf, Self::PREFIX)? {
+            RespFrame::Map(map) => Ok(map),
+            frame => Err(unexpected_frame("Map", frame)),
         }
-
-        Ok(map)
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        let total = calc_total_length(buf, len, end, Self::PREFIX)?;
-        Ok(total)
+        frame_length(buf, Self::PREFIX)
     }
 }
 
 impl Deref for RespMap {
-    type Target = BTreeMap<String, RespFrame>;
+    type Target = [(RespFrame, RespFrame)];
 
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
 
-impl DerefMut for RespMap {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+impl RespMap {
+    pub fn new() -> Self {
+        RespMap(Vec::new())
+    }
+
+    /// Set the value for key, keeping the key's original position if it was already present.
+    pub fn insert(&mut self, key: impl Into<RespFrame>, value: RespFrame) -> Option<RespFrame> {
+        let key = key.into();
+        match self.0.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => Some(std::mem::replace(v, value)),
+            None => {
+                self.0.push((key, value));
+                None
+            }
+        }
+    }
+
+    pub fn get(&self, key: impl Into<RespFrame>) -> Option<&RespFrame> {
+        let key = key.into();
+        self.0.iter().find(|(k, _)| *k == key).map(|(_, v)| v)
     }
 }
 
-impl RespMap {
-    pub fn new() -> Self {
-        RespMap(BTreeMap::new())
+// like `insert`, a repeated key keeps its first position and takes the last value
+impl FromIterator<(RespFrame, RespFrame)> for RespMap {
+    fn from_iter<T: IntoIterator<Item = (RespFrame, RespFrame)>>(iter: T) -> Self {
+        let mut map = RespMap::new();
+        // randomly seeded, so a client can't pick keys that all land together
+        let hasher = RandomState::new();
+        let mut index: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (key, value) in iter {
+            let mut state = hasher.build_hasher();
+            hash_key(&key, &mut state);
+            let positions = index.entry(state.finish()).or_default();
+            match positions.iter().find(|&&i| map.0[i].0 == key) {
+                Some(&i) => map.0[i].1 = value,
+                None => {
+                    positions.push(map.0.len());
+                    map.0.push((key, value));
+                }
+            }
+        }
+        map
+    }
+}
+
+// a hash that agrees with `PartialEq`: equal frames encode the same, except doubles where
+// 0.0 == -0.0, so those are hashed by value wherever they are
+fn hash_key<H: Hasher>(key: &RespFrame, state: &mut H) {
+    std::mem::discriminant(key).hash(state);
+    match key {
+        RespFrame::Double(d) => match *d == 0.0 {
+            true => 0u64.hash(state),
+            false => d.to_bits().hash(state),
+        },
+        RespFrame::Array(RespArray(items))
+        | RespFrame::Set(RespSet(items))
+        | RespFrame::Push(RespPush(items)) => {
+            items.len().hash(state);
+            for item in items {
+                hash_key(item, state);
+            }
+        }
+        RespFrame::Map(map) | RespFrame::Attribute(RespAttribute(map)) => {
+            map.len().hash(state);
+            for (k, v) in map.iter() {
+                hash_key(k, state);
+                hash_key(v, state);
+            }
+        }
+        frame => {
+            let mut buf = Vec::with_capacity(frame.encoded_len());
+            frame.encode_to(&mut buf);
+            buf.hash(state);
+        }
+    }
+}
+
+impl IntoIterator for RespMap {
+    type Item = (RespFrame, RespFrame);
+    type IntoIter = std::vec::IntoIter<(RespFrame, RespFrame)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
     }
 }
 
@@ -92,30 +162,73 @@ mod tests {
     fn test_map() {
         let mut map = RespMap::new();
         map.insert(
-            "hello".to_string(),
+            BulkString::from("hello"),
             BulkString::new("world".to_string()).into(),
         );
-        map.insert("foo".to_string(), (-123456.789).into());
+        map.insert("foo", (-123456.789).into());
 
         let frame: RespFrame = map.into();
         assert_eq!(
             &frame.encode(),
-            b"%2\r\n+foo\r\n,-123456.789\r\n+hello\r\n$5\r\nworld\r\n"
+            b"%2\r\n$5\r\nhello\r\n$5\r\nworld\r\n+foo\r\n,-123456.789\r\n"
         );
     }
 
+    #[test]
+    fn test_map_collect_dedups_like_insert() {
+        let map = [
+            (BulkString::from("a").into(), 1.into()),
+            (RespFrame::Double(0.0), 2.into()),
+            ("a".into(), 3.into()),
+            (RespFrame::Double(-0.0), 4.into()),
+            (BulkString::from("a").into(), 5.into()),
+            (RespFrame::Double(f64::NAN), 6.into()),
+            (RespFrame::Double(f64::NAN), 7.into()),
+        ]
+        .into_iter()
+        .collect::<RespMap>();
+
+        assert_eq!(map.len(), 5);
+        assert_eq!(map[0], (BulkString::from("a").into(), 5.into()));
+        assert_eq!(map[1], (RespFrame::Double(0.0), 4.into()));
+        assert_eq!(map.get("a"), Some(&3.into()));
+
+        // large maps are built in linear time
+        let map = (0..200_000)
+            .map(|i| (RespFrame::Integer(i % 100_000), i.into()))
+            .collect::<RespMap>();
+        assert_eq!(map.len(), 100_000);
+        assert_eq!(map[7], (RespFrame::Integer(7), 100_007.into()));
+    }
+
+    #[test]
+    fn test_map_insert_keeps_order() {
+        let mut map = RespMap::new();
+        map.insert(BulkString::from("b"), 1.into());
+        map.insert(2, 2.into());
+        assert_eq!(map.insert(BulkString::from("b"), 3.into()), Some(1.into()));
+
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[0], (BulkString::from("b").into(), 3.into()));
+        assert_eq!(map.get(2), Some(&2.into()));
+        assert_eq!(map.get("b"), None);
+    }
+
     #[test]
     fn test_map_decode() -> Result<()> {
         let mut buf = BytesMut::new();
-        buf.extend_from_slice(b"%2\r\n+hello\r\n$5\r\nworld\r\n+foo\r\n$3\r\nbar\r\n");
+        buf.extend_from_slice(
+            b"%3\r\n+hello\r\n$5\r\nworld\r\n$8\r\nfoo\r\nbar\r\n$3\r\nbar\r\n:+1\r\n#t\r\n",
+        );
 
         let frame = RespMap::decode(&mut buf)?;
         let mut map = RespMap::new();
+        map.insert("hello", BulkString::new(b"world".to_vec()).into());
         map.insert(
-            "hello".to_string(),
-            BulkString::new(b"world".to_vec()).into(),
+            BulkString::from("foo\r\nbar"),
+            BulkString::new(b"bar".to_vec()).into(),
         );
-        map.insert("foo".to_string(), BulkString::new(b"bar".to_vec()).into());
+        map.insert(1, true.into());
         assert_eq!(frame, map);
 
         Ok(())
@@ -1,32 +1,53 @@
 mod array;
+mod attribute;
+mod big_number;
+mod blob_error;
 mod bool;
 mod bulk_string;
+mod decoder;
 mod double;
 mod frame;
 mod integer;
 mod map;
 mod null;
+mod push;
 mod set;
 mod simple_error;
 mod simple_string;
+mod streamed;
+mod verbatim_string;
 
 pub use self::{
-    array::RespArray, array::RespNullArray, bulk_string::BulkString,
-    bulk_string::RespNullBulkString, frame::RespFrame, map::RespMap, null::RespNull, set::RespSet,
-    simple_error::SimpleError, simple_string::SimpleString,
+    array::RespArray, array::RespNullArray, attribute::RespAttribute, big_number::BigNumber,
+    blob_error::BlobError, bulk_string::BulkString, bulk_string::RespNullBulkString,
+    decoder::ProtocolLimits, decoder::RespDecoder, frame::RespFrame, frame::RespVersion,
+    map::RespMap, null::RespNull, push::RespPush, set::RespSet, simple_error::SimpleError,
+    simple_string::SimpleString, streamed::RespStreamed, verbatim_string::VerbatimString,
 };
-use bytes::Buf;
-use bytes::BytesMut;
+use bytes::{BufMut, BytesMut};
 use enum_dispatch::enum_dispatch;
+use std::fmt;
 use thiserror::Error;
 
 const CRLF: &[u8] = b"\r\n";
 const CRLF_LEN: usize = CRLF.len();
-const BUF_CAP: usize = 4096;
 
 #[enum_dispatch]
 pub trait RespEncode {
-    fn encode(self) -> Vec<u8>;
+    /// Write the frame to `buf` without any intermediate allocation.
+    fn encode_to<B: BufMut>(&self, buf: &mut B);
+
+    /// The exact number of bytes `encode_to` writes, so the output can be reserved up front.
+    fn encoded_len(&self) -> usize;
+
+    fn encode(self) -> Vec<u8>
+    where
+        Self: Sized,
+    {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+        self.encode_to(&mut buf);
+        buf
+    }
 }
 
 pub trait RespDecode: Sized {
@@ -46,6 +67,8 @@ pub enum RespError {
     InvalidFrameLength(isize),
     #[error("Frame not complete")]
     NotComplete,
+    #[error("{0}")]
+    LimitExceeded(&'static str),
 
     #[error("ParseIntError: {0}")]
     ParseIntError(#[from] std::num::ParseIntError),
@@ -55,89 +78,44 @@ pub enum RespError {
     ParseFloatError(#[from] std::num::ParseFloatError),
 }
 
-fn calc_total_length(buf: &[u8], len: usize, end: usize, prefix: &str) -> Result<usize, RespError> {
-    let mut total = end + CRLF_LEN;
-    let mut data = &buf[total..];
-    match prefix {
-        "*" | "~" => {
-            for _ in 0..len {
-                let len = RespFrame::expect_length(data)?;
-                total += len;
-                data = &data[len..];
-            }
-            Ok(total)
-        }
-        "%" => {
-            for _ in 0..len {
-                //key length
-                let len = SimpleString::expect_length(data)?;
-                total += len;
-                data = &data[len..];
-
-                //value length
-                let len = RespFrame::expect_length(data)?;
-                total += len;
-                data = &data[len..];
-            }
-            Ok(total)
-        }
-        _ => Ok(len + CRLF_LEN),
+// formats straight into the output buffer
+struct BufWriter<'a, B>(&'a mut B);
+
+impl<B: BufMut> fmt::Write for BufWriter<'_, B> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.put_slice(s.as_bytes());
+        Ok(())
     }
 }
 
-fn extract_fixed_data(
-    buf: &mut BytesMut,
-    expect: &str,
-    expect_type: &str,
-) -> Result<(), RespError> {
-    if buf.len() < expect.len() {
-        return Err(RespError::NotComplete);
-    }
+struct LenCounter(usize);
 
-    if !buf.starts_with(expect.as_bytes()) {
-        return Err(RespError::InvalidFrameType(format!(
-            "expect: {}, got: {:?}",
-            expect_type, buf
-        )));
+impl fmt::Write for LenCounter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
     }
-    buf.advance(expect.len());
-
-    Ok(())
 }
 
-fn extract_simple_frame_data(buf: &[u8], prefix: &str) -> Result<usize, RespError> {
-    if buf.len() < 3 {
-        return Err(RespError::NotComplete);
-    }
-
-    if !buf.starts_with(prefix.as_bytes()) {
-        return Err(RespError::InvalidFrameType(format!(
-            "expect: SimpleString, got: {:?}",
-            buf
-        )));
-    }
+fn put_display<B: BufMut>(buf: &mut B, value: impl fmt::Display) {
+    let _ = fmt::write(&mut BufWriter(buf), format_args!("{}", value));
+}
 
-    find_crlf(buf, 1).ok_or(RespError::NotComplete)
+fn display_len(value: impl fmt::Display) -> usize {
+    let mut counter = LenCounter(0);
+    let _ = fmt::write(&mut counter, format_args!("{}", value));
+    counter.0
 }
 
-fn find_crlf(buf: &[u8], nth: usize) -> Option<usize> {
-    //search for \r\n
-    let mut count = 0;
-    for i in 0..buf.len() - 1 {
-        if buf[i] == b'\r' && buf[i + 1] == b'\n' {
-            count += 1;
-            if count == nth {
-                return Some(i);
-            }
-        }
-    }
-    None
+// "<prefix><len>\r\n", the header shared by bulk and aggregate frames
+fn put_header<B: BufMut>(buf: &mut B, prefix: u8, len: usize) {
+    buf.put_u8(prefix);
+    put_display(buf, len);
+    buf.put_slice(CRLF);
 }
 
-fn parse_length(buf: &[u8], prefix: &str) -> Result<(usize, usize), RespError> {
-    let end = extract_simple_frame_data(buf, prefix)?;
-    let len = String::from_utf8_lossy(&buf[prefix.len()..end]).parse()?;
-    Ok((end, len))
+fn header_len(len: usize) -> usize {
+    1 + display_len(len) + CRLF_LEN
 }
 
 #[cfg(test)]
@@ -149,13 +127,30 @@ mod tests {
     #[test]
     fn test_calc_array_length() -> Result<()> {
         let buf = b"*2\r\n$3\r\nset\r\n$5\r\nhello\r\n";
-        let (end, len) = parse_length(buf, "*")?;
-        let total_len = calc_total_length(buf, len, end, "*")?;
-        assert_eq!(total_len, buf.len());
+        assert_eq!(RespArray::expect_length(buf)?, buf.len());
 
         let buf = b"*2\r\n$3\r\nset\r\n";
-        let (end, len) = parse_length(buf, "*")?;
-        let ret = calc_total_length(buf, len, end, "*");
+        let ret = RespArray::expect_length(buf);
+        assert_eq!(ret.unwrap_err(), RespError::NotComplete);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_calc_streamed_length() -> Result<()> {
+        let buf = b"$?\r\n;4\r\nHell\r\n;1\r\no\r\n;0\r\n";
+        assert_eq!(BulkString::expect_length(buf)?, buf.len());
+
+        let buf = b"*?\r\n:+1\r\n$3\r\nfoo\r\n.\r\n";
+        assert_eq!(RespArray::expect_length(buf)?, buf.len());
+
+        let buf = b"%?\r\n+a\r\n:+1\r\n.\r\n";
+        assert_eq!(RespMap::expect_length(buf)?, buf.len());
+
+        let ret = RespArray::expect_length(b"*?\r\n:+1\r\n");
+        assert_eq!(ret.unwrap_err(), RespError::NotComplete);
+
+        let ret = BulkString::expect_length(b"$?\r\n;4\r\nHe");
         assert_eq!(ret.unwrap_err(), RespError::NotComplete);
 
         Ok(())
@@ -1,17 +1,22 @@
 use crate::RespDecode;
 use crate::RespEncode;
 use crate::RespError;
-use bytes::BytesMut;
+use crate::RespFrame;
+use bytes::{BufMut, BytesMut};
 
-use super::extract_fixed_data;
+use super::decoder::{decode_frame, frame_length, unexpected_frame};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct RespNull;
 
 //- null: "_\r\n"
 impl RespEncode for RespNull {
-    fn encode(self) -> Vec<u8> {
-        b"_\r\n".to_vec()
+    fn encode_to<B: BufMut>(&self, buf: &mut B) {
+        buf.put_slice(b"_\r\n");
+    }
+
+    fn encoded_len(&self) -> usize {
+        3
     }
 }
 
@@ -19,12 +24,14 @@ impl RespDecode for RespNull {
     const PREFIX: &'static str = "_";
 
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        extract_fixed_data(buf, "_\r\n", "Null")?;
-        Ok(RespNull)
+        match decode_frame(buf, Self::PREFIX)? {
+            RespFrame::Null(n) => Ok(n),
+            frame => Err(unexpected_frame("Null", frame)),
+        }
     }
 
-    fn expect_length(_buf: &[u8]) -> Result<usize, RespError> {
-        Ok(3)
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        frame_length(buf, Self::PREFIX)
     }
 }
 
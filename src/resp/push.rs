@@ -0,0 +1,90 @@
+use crate::RespDecode;
+use crate::RespEncode;
+use crate::RespError;
+use crate::RespFrame;
+
+use bytes::{BufMut, BytesMut};
+
+use std::ops::Deref;
+
+use super::decoder::{decode_frame, frame_length, unexpected_frame};
+use super::{header_len, put_header};
+
+/// Out-of-band data sent by the server, e.g. pub/sub messages in RESP3.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct RespPush(pub(crate) Vec<RespFrame>);
+
+//- push: "><number-of-elements>\r\n<element-1>...<element-n>"
+impl RespEncode for RespPush {
+    fn encode_to<B: BufMut>(&self, buf: &mut B) {
+        put_header(buf, b'>', self.len());
+        for frame in &self.0 {
+            frame.encode_to(buf);
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        header_len(self.len()) + self.iter().map(|f| f.encoded_len()).sum::<usize>()
+    }
+}
+
+impl RespDecode for RespPush {
+    const PREFIX: &'static str = ">";
+
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        match decode_frame(buf, Self::PREFIX)? {
+            RespFrame::Push(push) => Ok(push),
+            frame => Err(unexpected_frame("Push", frame)),
+        }
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        frame_length(buf, Self::PREFIX)
+    }
+}
+
+impl Deref for RespPush {
+    type Target = Vec<RespFrame>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl RespPush {
+    pub fn new(items: impl Into<Vec<RespFrame>>) -> Self {
+        RespPush(items.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_push() {
+        let frame: RespFrame =
+            RespPush::new([b"message".into(), b"news".into(), b"hello".into()]).into();
+        assert_eq!(
+            frame.encode(),
+            b">3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$5\r\nhello\r\n"
+        );
+    }
+
+    #[test]
+    fn test_push_decode() -> Result<()> {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(b">2\r\n$7\r\nmessage\r\n");
+
+        let ret = RespPush::decode(&mut buf);
+        assert_eq!(ret.unwrap_err(), RespError::NotComplete);
+
+        buf.extend_from_slice(b"$4\r\nnews\r\n");
+        let frame = RespPush::decode(&mut buf)?;
+        assert_eq!(frame, RespPush::new([b"message".into(), b"news".into()]));
+
+        Ok(())
+    }
+}
@@ -2,31 +2,28 @@ use crate::RespDecode;
 use crate::RespEncode;
 use crate::RespError;
 
-use bytes::Buf;
-use bytes::BytesMut;
+use bytes::{BufMut, BytesMut};
 
 use std::ops::Deref;
 
-use super::calc_total_length;
-
+use super::decoder::{decode_frame, frame_length, unexpected_frame};
 use super::frame::RespFrame;
-use super::parse_length;
-use super::BUF_CAP;
-use super::CRLF_LEN;
+use super::{header_len, put_header};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct RespSet(pub(crate) Vec<RespFrame>);
 
 //- set: "~<number-of-elements>\r\n<element-1>...<element-n>"
 impl RespEncode for RespSet {
-    fn encode(self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(BUF_CAP);
-
-        buf.extend_from_slice(format!("~{}\r\n", self.len()).as_bytes());
-        for frame in self.0 {
-            buf.extend_from_slice(&frame.encode());
+    fn encode_to<B: BufMut>(&self, buf: &mut B) {
+        put_header(buf, b'~', self.len());
+        for frame in &self.0 {
+            frame.encode_to(buf);
         }
-        buf
+    }
+
+    fn encoded_len(&self) -> usize {
+        header_len(self.len()) + self.iter().map(|f| f.encoded_len()).sum::<usize>()
     }
 }
 
@@ -34,26 +31,14 @@ impl RespDecode for RespSet {
     const PREFIX: &'static str = "~";
 
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        let total = calc_total_length(buf, len, end, Self::PREFIX)?;
-        if total > buf.len() {
-            return Err(RespError::NotComplete);
+        match decode_frame(buf, Self::PREFIX)? {
+            RespFrame::Set(set) => Ok(set),
+            frame => Err(unexpected_frame("Set", frame)),
         }
-        buf.advance(end + CRLF_LEN);
-
-        let mut set = Vec::with_capacity(len);
-        for _ in 0..len {
-            let frame = RespFrame::decode(buf)?;
-            set.push(frame);
-        }
-
-        Ok(RespSet::new(set))
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        let total = calc_total_length(buf, len, end, Self::PREFIX)?;
-        Ok(total)
+        frame_length(buf, Self::PREFIX)
     }
 }
 
@@ -1,21 +1,28 @@
 use crate::RespDecode;
 use crate::RespEncode;
 use crate::RespError;
+use crate::RespFrame;
 
-use bytes::BytesMut;
+use bytes::{BufMut, BytesMut};
 
 use std::ops::Deref;
 
-use super::extract_simple_frame_data;
-use super::CRLF_LEN;
+use super::decoder::{decode_frame, frame_length, unexpected_frame};
+use super::{CRLF, CRLF_LEN};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct SimpleError(pub(crate) String);
 
 //- error: "-Error message\r\n"
 impl RespEncode for SimpleError {
-    fn encode(self) -> Vec<u8> {
-        format!("-{}\r\n", self.0).into_bytes()
+    fn encode_to<B: BufMut>(&self, buf: &mut B) {
+        buf.put_u8(b'-');
+        buf.put_slice(self.0.as_bytes());
+        buf.put_slice(CRLF);
+    }
+
+    fn encoded_len(&self) -> usize {
+        1 + self.0.len() + CRLF_LEN
     }
 }
 
@@ -23,18 +30,14 @@ impl RespDecode for SimpleError {
     const PREFIX: &'static str = "-";
 
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-
-        let data = buf.split_to(end + CRLF_LEN);
-
-        let s = String::from_utf8_lossy(&data[Self::PREFIX.len()..end]);
-
-        Ok(SimpleError::new(s.to_string()))
+        match decode_frame(buf, Self::PREFIX)? {
+            RespFrame::Error(e) => Ok(e),
+            frame => Err(unexpected_frame("SimpleError", frame)),
+        }
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-        Ok(end + CRLF_LEN)
+        frame_length(buf, Self::PREFIX)
     }
 }
 
@@ -0,0 +1,151 @@
+use crate::BulkString;
+use crate::RespArray;
+use crate::RespEncode;
+use crate::RespFrame;
+use crate::RespMap;
+use crate::RespSet;
+
+use bytes::BufMut;
+
+use super::{header_len, put_header, CRLF, CRLF_LEN};
+
+/// A RESP3 streamed string or aggregate, whose size is not announced up front.
+///
+/// Encodes as a `$?` string of `;` chunks or as a `*?`/`%?`/`~?` aggregate terminated by
+/// `.`. Streamed frames read from the wire decode into the regular types. This only covers
+/// the wire format: the items are all held in memory, and no reply of the server is sent
+/// this way, they all go out sized.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum RespStreamed {
+    String(Vec<Vec<u8>>),
+    Array(Vec<RespFrame>),
+    Map(Vec<(RespFrame, RespFrame)>),
+    Set(Vec<RespFrame>),
+}
+
+//- streamed string: "$?\r\n;<length>\r\n<data>\r\n...;0\r\n"
+//- streamed aggregate: "*?\r\n<element-1>...<element-n>.\r\n", likewise for "%?" and "~?"
+impl RespEncode for RespStreamed {
+    fn encode_to<B: BufMut>(&self, buf: &mut B) {
+        match self {
+            RespStreamed::String(chunks) => {
+                buf.put_slice(b"$?\r\n");
+                // an empty chunk would end the string early
+                for chunk in chunks.iter().filter(|c| !c.is_empty()) {
+                    put_header(buf, b';', chunk.len());
+                    buf.put_slice(chunk);
+                    buf.put_slice(CRLF);
+                }
+                buf.put_slice(b";0\r\n");
+                return;
+            }
+            RespStreamed::Array(items) => {
+                buf.put_slice(b"*?\r\n");
+                for frame in items {
+                    frame.encode_to(buf);
+                }
+            }
+            RespStreamed::Map(entries) => {
+                buf.put_slice(b"%?\r\n");
+                for (key, value) in entries {
+                    key.encode_to(buf);
+                    value.encode_to(buf);
+                }
+            }
+            RespStreamed::Set(items) => {
+                buf.put_slice(b"~?\r\n");
+                for frame in items {
+                    frame.encode_to(buf);
+                }
+            }
+        }
+        buf.put_slice(STREAMED_END);
+    }
+
+    fn encoded_len(&self) -> usize {
+        // "$?\r\n", "*?\r\n"... are all four bytes
+        let header = 4;
+        match self {
+            RespStreamed::String(chunks) => {
+                header
+                    + chunks
+                        .iter()
+                        .filter(|c| !c.is_empty())
+                        .map(|c| header_len(c.len()) + c.len() + CRLF_LEN)
+                        .sum::<usize>()
+                    + b";0\r\n".len()
+            }
+            RespStreamed::Array(items) | RespStreamed::Set(items) => {
+                header + items.iter().map(|f| f.encoded_len()).sum::<usize>() + STREAMED_END.len()
+            }
+            RespStreamed::Map(entries) => {
+                header
+                    + entries
+                        .iter()
+                        .map(|(k, v)| k.encoded_len() + v.encoded_len())
+                        .sum::<usize>()
+                    + STREAMED_END.len()
+            }
+        }
+    }
+}
+
+const STREAMED_END: &[u8] = b".\r\n";
+
+impl RespStreamed {
+    /// The fully buffered equivalent, for RESP2 clients.
+    pub fn into_sized(self) -> RespFrame {
+        match self {
+            RespStreamed::String(chunks) => BulkString::new(chunks.concat()).into(),
+            RespStreamed::Array(items) => RespArray::new(items).into(),
+            RespStreamed::Map(entries) => entries.into_iter().collect::<RespMap>().into(),
+            RespStreamed::Set(items) => RespSet::new(items).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{RespDecode, RespError};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_streamed_string() -> Result<()> {
+        let frame: RespFrame =
+            RespStreamed::String(vec![b"Hell".to_vec(), vec![], b"o world".to_vec()]).into();
+        let encoded = frame.encode();
+        assert_eq!(encoded, b"$?\r\n;4\r\nHell\r\n;7\r\no world\r\n;0\r\n");
+
+        let frame = RespFrame::decode(&mut BytesMut::from(&encoded[..]))?;
+        assert_eq!(frame, b"Hello world".into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_streamed_aggregates() -> Result<()> {
+        let frame: RespFrame = RespStreamed::Array(vec![1.into(), b"foo".into()]).into();
+        let encoded = frame.encode();
+        assert_eq!(encoded, b"*?\r\n:+1\r\n$3\r\nfoo\r\n.\r\n");
+        let frame = RespFrame::decode(&mut BytesMut::from(&encoded[..]))?;
+        assert_eq!(frame, RespArray::new([1.into(), b"foo".into()]).into());
+
+        let frame: RespFrame = RespStreamed::Set(vec![true.into()]).into();
+        let frame = RespFrame::decode(&mut BytesMut::from(&frame.encode()[..]))?;
+        assert_eq!(frame, RespSet::new([true.into()]).into());
+
+        let mut buf = BytesMut::from(&b"%?\r\n+a\r\n:+1\r\n+b\r\n"[..]);
+        assert_eq!(RespFrame::decode(&mut buf), Err(RespError::NotComplete));
+        buf.extend_from_slice(b",2.5\r\n.\r\n");
+        let mut map = RespMap::new();
+        map.insert("a", 1.into());
+        map.insert("b", 2.5.into());
+        assert_eq!(RespFrame::decode(&mut buf)?, map.into());
+        assert!(buf.is_empty());
+
+        Ok(())
+    }
+}
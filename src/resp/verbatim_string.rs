@@ -0,0 +1,117 @@
+use crate::RespDecode;
+use crate::RespEncode;
+use crate::RespError;
+use crate::RespFrame;
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use super::decoder::{decode_frame, frame_length, unexpected_frame};
+use super::{header_len, put_header, CRLF, CRLF_LEN};
+
+// the three byte format, e.g. "txt" or "mkd", plus the ':' separator
+const FORMAT_LEN: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct VerbatimString {
+    pub(crate) format: [u8; 3],
+    pub(crate) data: Bytes,
+}
+
+//- verbatim string: "=<length>\r\n<encoding>:<data>\r\n"
+impl RespEncode for VerbatimString {
+    fn encode_to<B: BufMut>(&self, buf: &mut B) {
+        put_header(buf, b'=', self.data.len() + FORMAT_LEN);
+        buf.put_slice(&self.format);
+        buf.put_u8(b':');
+        buf.put_slice(&self.data);
+        buf.put_slice(CRLF);
+    }
+
+    fn encoded_len(&self) -> usize {
+        let len = self.data.len() + FORMAT_LEN;
+        header_len(len) + len + CRLF_LEN
+    }
+}
+
+impl RespDecode for VerbatimString {
+    const PREFIX: &'static str = "=";
+
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        match decode_frame(buf, Self::PREFIX)? {
+            RespFrame::VerbatimString(s) => Ok(s),
+            frame => Err(unexpected_frame("VerbatimString", frame)),
+        }
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        frame_length(buf, Self::PREFIX)
+    }
+}
+
+impl VerbatimString {
+    pub fn new(format: [u8; 3], data: impl Into<Bytes>) -> Self {
+        VerbatimString {
+            format,
+            data: data.into(),
+        }
+    }
+
+    /// Plain text, what redis uses for INFO and friends.
+    pub fn text(data: impl Into<Bytes>) -> Self {
+        VerbatimString::new(*b"txt", data)
+    }
+
+    // "<encoding>:<data>" as read off the wire
+    pub(crate) fn from_payload(payload: Bytes) -> Result<Self, RespError> {
+        if payload.len() < FORMAT_LEN || payload[FORMAT_LEN - 1] != b':' {
+            return Err(RespError::InvalidFrame(format!(
+                "verbatim string without format: {:?}",
+                payload
+            )));
+        }
+        Ok(VerbatimString {
+            format: [payload[0], payload[1], payload[2]],
+            data: payload.slice(FORMAT_LEN..),
+        })
+    }
+
+    pub fn format(&self) -> &[u8; 3] {
+        &self.format
+    }
+}
+
+impl AsRef<[u8]> for VerbatimString {
+    fn as_ref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::RespFrame;
+    use anyhow::Result;
+
+    #[test]
+    fn test_verbatim_string() {
+        let frame: RespFrame = VerbatimString::text("Some string").into();
+        assert_eq!(frame.encode(), b"=15\r\ntxt:Some string\r\n");
+    }
+
+    #[test]
+    fn test_verbatim_string_decode() -> Result<()> {
+        let mut buf = BytesMut::from("=15\r\nmkd:Some string\r\n");
+        let frame = VerbatimString::decode(&mut buf)?;
+        assert_eq!(frame, VerbatimString::new(*b"mkd", "Some string"));
+
+        let mut buf = BytesMut::from("=15\r\ntxt:Some str");
+        let ret = VerbatimString::decode(&mut buf);
+        assert_eq!(ret.unwrap_err(), RespError::NotComplete);
+
+        let mut buf = BytesMut::from("=3\r\ntxt\r\n");
+        assert!(VerbatimString::decode(&mut buf).is_err());
+
+        Ok(())
+    }
+}
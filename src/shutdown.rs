@@ -0,0 +1,424 @@
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use tokio::sync::{oneshot, Notify};
+use tokio::time::{sleep, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::{Backend, RespFrame, SimpleError};
+
+// how often the coordinator looks at connections it is waiting on
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How SHUTDOWN, or a signal, asked the server to go down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShutdownOptions {
+    /// SAVE is `Some(true)`, NOSAVE `Some(false)`; otherwise save if persistence is set up.
+    pub save: Option<bool>,
+    /// Don't wait for running commands and pending output before going down.
+    pub now: bool,
+    /// Go down even if saving fails.
+    pub force: bool,
+}
+
+/// Run when the server goes down and SHUTDOWN didn't say NOSAVE.
+pub type SaveHook = Box<dyn Fn(&Backend) -> Result<()> + Send + Sync>;
+
+/// Takes the server down in order: stop accepting connections, let running commands finish
+/// and their output flush, save, then close every connection.
+///
+/// Until the save is done the shutdown is pending and SHUTDOWN ABORT, or a failing save,
+/// puts the server back to work.
+pub struct ShutdownCoordinator {
+    state: Mutex<State>,
+    requested: Notify,
+    closing: CancellationToken,
+    busy: AtomicUsize,
+    save_hook: Mutex<Option<SaveHook>>,
+}
+
+/// Held while a connection runs a batch of commands and writes their replies.
+pub struct BusyGuard<'a>(&'a AtomicUsize);
+
+enum State {
+    Running,
+    Pending {
+        options: ShutdownOptions,
+        /// Clients blocked in SHUTDOWN; dropped to hang up on them once the server goes down.
+        waiters: Vec<oneshot::Sender<RespFrame>>,
+    },
+    Closing,
+}
+
+impl ShutdownOptions {
+    /// Like redis' shutdown-on-sigterm: "default", or any of "save", "nosave", "now" and
+    /// "force" separated by spaces.
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut options = ShutdownOptions::default();
+        for word in s.split_whitespace() {
+            match word.to_ascii_lowercase().as_str() {
+                "default" => {}
+                "save" if options.save.is_none() => options.save = Some(true),
+                "nosave" if options.save.is_none() => options.save = Some(false),
+                "now" => options.now = true,
+                "force" => options.force = true,
+                _ => return None,
+            }
+        }
+        Some(options)
+    }
+
+    // a later request adds to one already pending
+    fn merge(&mut self, other: ShutdownOptions) {
+        self.save = other.save.or(self.save);
+        self.now |= other.now;
+        self.force |= other.force;
+    }
+}
+
+impl fmt::Display for ShutdownOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let words = [
+            (self.save == Some(true), "save"),
+            (self.save == Some(false), "nosave"),
+            (self.now, "now"),
+            (self.force, "force"),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, word)| *word)
+        .collect::<Vec<_>>();
+        match words.is_empty() {
+            true => write!(f, "default"),
+            false => write!(f, "{}", words.join(" ")),
+        }
+    }
+}
+
+impl fmt::Debug for ShutdownCoordinator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShutdownCoordinator")
+            .field("busy", &self.busy)
+            .field("closing", &self.closing.is_cancelled())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for ShutdownCoordinator {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(State::Running),
+            requested: Notify::new(),
+            closing: CancellationToken::new(),
+            busy: AtomicUsize::new(0),
+            save_hook: Mutex::new(None),
+        }
+    }
+}
+
+impl Drop for BusyGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl ShutdownCoordinator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// What to run to persist the data on the way down.
+    pub fn set_save_hook(&self, hook: SaveHook) {
+        *self.save_hook.lock().unwrap() = Some(hook);
+    }
+
+    /// Ask for a shutdown; `waiter` gets the error if it fails, or is dropped once the
+    /// server goes down.
+    pub fn request(&self, options: ShutdownOptions, waiter: Option<oneshot::Sender<RespFrame>>) {
+        let mut state = self.state.lock().unwrap();
+        match &mut *state {
+            State::Running => {
+                *state = State::Pending {
+                    options,
+                    waiters: waiter.into_iter().collect(),
+                };
+            }
+            State::Pending {
+                options: pending,
+                waiters,
+            } => {
+                pending.merge(options);
+                waiters.extend(waiter);
+            }
+            State::Closing => return,
+        }
+        self.requested.notify_one();
+    }
+
+    /// SHUTDOWN ABORT: cancel a pending shutdown, false if there is none.
+    pub fn abort(&self) -> bool {
+        self.fail("shutdown aborted")
+    }
+
+    /// Whether new connections are taken; not once a shutdown is requested.
+    pub fn accepting(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), State::Running)
+    }
+
+    /// Resolves once the server is going down for good and connections should close.
+    pub async fn closing(&self) {
+        self.closing.cancelled().await
+    }
+
+    pub fn busy(&self) -> BusyGuard<'_> {
+        self.busy.fetch_add(1, Ordering::Relaxed);
+        BusyGuard(&self.busy)
+    }
+
+    /// Wait for shutdown requests and carry them out, returning once the server is down to
+    /// its last connection or the shutdown-timeout passed.
+    pub async fn run(&self, backend: &Backend) -> Result<()> {
+        loop {
+            let options = self.requested_options().await;
+            let deadline = Instant::now() + backend.config().shutdown_timeout;
+            info!("Shutting down ({})", options);
+
+            // a later request may have added NOW, or ABORT cancelled it
+            while let Some(options) = self.pending_options() {
+                if options.now || self.busy.load(Ordering::Relaxed) == 0 {
+                    break;
+                }
+                if Instant::now() >= deadline {
+                    warn!("Commands still running after the shutdown timeout");
+                    break;
+                }
+                sleep(POLL_INTERVAL).await;
+            }
+            let Some(options) = self.pending_options() else {
+                continue;
+            };
+
+            if let Err(e) = self.save(backend, options) {
+                match options.force {
+                    true => warn!("Error saving on shutdown, exiting anyway: {:?}", e),
+                    false => {
+                        error!("Errors trying to shut down the server: {:?}", e);
+                        self.fail("errors trying to shut down");
+                        continue;
+                    }
+                }
+            }
+            if self.commit() {
+                break;
+            }
+        }
+
+        let deadline = Instant::now() + backend.config().shutdown_timeout;
+        while !backend.clients().is_empty() && Instant::now() < deadline {
+            sleep(POLL_INTERVAL).await;
+        }
+        info!("Ready to exit, bye bye...");
+        Ok(())
+    }
+
+    async fn requested_options(&self) -> ShutdownOptions {
+        loop {
+            if let Some(options) = self.pending_options() {
+                return options;
+            }
+            // a request between the check and here left a permit, so this won't miss it
+            self.requested.notified().await;
+        }
+    }
+
+    fn pending_options(&self) -> Option<ShutdownOptions> {
+        match &*self.state.lock().unwrap() {
+            State::Pending { options, .. } => Some(*options),
+            _ => None,
+        }
+    }
+
+    fn save(&self, backend: &Backend, options: ShutdownOptions) -> Result<()> {
+        let hook = self.save_hook.lock().unwrap();
+        match (options.save, hook.as_ref()) {
+            (Some(false), _) | (None, None) => Ok(()),
+            (Some(true), None) => Err(anyhow!("SAVE asked for but no persistence is set up")),
+            (_, Some(save)) => {
+                info!("Saving the data before exiting");
+                save(backend)
+            }
+        }
+    }
+
+    // back to running, telling the clients blocked in SHUTDOWN
+    fn fail(&self, reason: &str) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let State::Pending { waiters, .. } = std::mem::replace(&mut *state, State::Running) else {
+            return false;
+        };
+        info!("Shutdown cancelled: {}", reason);
+        for waiter in waiters {
+            let reply = SimpleError::new("ERR Errors trying to SHUTDOWN. Check logs.");
+            let _ = waiter.send(reply.into());
+        }
+        true
+    }
+
+    // past the point of no return: drop the waiters so their clients see the connection go
+    fn commit(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if !matches!(*state, State::Pending { .. }) {
+            return false;
+        }
+        *state = State::Closing;
+        self.closing.cancel();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::stream_handler;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    async fn serve(backend: Backend) -> Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?.to_string();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(stream_handler(stream, backend.clone()));
+            }
+        });
+        Ok(addr)
+    }
+
+    async fn request(client: &mut TcpStream, command: &str) -> Result<String> {
+        client.write_all(command.as_bytes()).await?;
+        let mut buf = vec![0; 256];
+        let n = client.read(&mut buf).await?;
+        Ok(String::from_utf8_lossy(&buf[..n]).into_owned())
+    }
+
+    #[test]
+    fn test_shutdown_options() {
+        let options = ShutdownOptions::parse("NOSAVE now").unwrap();
+        assert_eq!(
+            options,
+            ShutdownOptions {
+                save: Some(false),
+                now: true,
+                force: false
+            }
+        );
+        assert_eq!(options.to_string(), "nosave now");
+        assert_eq!(
+            ShutdownOptions::parse("default"),
+            Some(ShutdownOptions::default())
+        );
+        assert_eq!(ShutdownOptions::default().to_string(), "default");
+        assert!(ShutdownOptions::parse("save nosave").is_none());
+        assert!(ShutdownOptions::parse("later").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_closes_connections() -> Result<()> {
+        let backend = Backend::new();
+        let addr = serve(backend.clone()).await?;
+        let mut idle = TcpStream::connect(&addr).await?;
+        assert_eq!(request(&mut idle, "PING\r\n").await?, "+PONG\r\n");
+
+        let coordinator = {
+            let backend = backend.clone();
+            tokio::spawn(async move { backend.shutdown().run(&backend).await })
+        };
+        let mut client = TcpStream::connect(&addr).await?;
+        // the client that asked gets no reply, the connection just goes away
+        assert_eq!(request(&mut client, "SHUTDOWN NOSAVE\r\n").await?, "");
+        coordinator.await??;
+        assert_eq!(request(&mut idle, "PING\r\n").await?, "");
+        assert!(backend.clients().is_empty());
+        assert!(!backend.shutdown().accepting());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_save_and_force() -> Result<()> {
+        let backend = Backend::new();
+        let saves = Arc::new(AtomicUsize::new(0));
+        let addr = serve(backend.clone()).await?;
+        let coordinator = {
+            let backend = backend.clone();
+            tokio::spawn(async move { backend.shutdown().run(&backend).await })
+        };
+        let mut client = TcpStream::connect(&addr).await?;
+
+        // nothing can save, so SAVE fails and the server keeps going
+        assert_eq!(
+            request(&mut client, "SHUTDOWN SAVE\r\n").await?,
+            "-ERR Errors trying to SHUTDOWN. Check logs.\r\n"
+        );
+        assert!(backend.shutdown().accepting());
+
+        let counter = saves.clone();
+        backend.shutdown().set_save_hook(Box::new(move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            Err(anyhow!("disk full"))
+        }));
+        assert_eq!(
+            request(&mut client, "SHUTDOWN\r\n").await?,
+            "-ERR Errors trying to SHUTDOWN. Check logs.\r\n"
+        );
+        assert_eq!(
+            request(&mut client, "SHUTDOWN ABORT\r\n").await?,
+            "-ERR No shutdown in progress.\r\n"
+        );
+        assert_eq!(request(&mut client, "SHUTDOWN FORCE\r\n").await?, "");
+        coordinator.await??;
+        assert_eq!(saves.load(Ordering::Relaxed), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_abort() -> Result<()> {
+        let backend = Backend::new();
+        let addr = serve(backend.clone()).await?;
+        let coordinator = {
+            let backend = backend.clone();
+            tokio::spawn(async move { backend.shutdown().run(&backend).await })
+        };
+        let mut first = TcpStream::connect(&addr).await?;
+        let mut second = TcpStream::connect(&addr).await?;
+
+        // something still running holds the shutdown pending
+        let busy = backend.shutdown().busy();
+        first.write_all(b"SHUTDOWN NOSAVE\r\n").await?;
+        while backend.shutdown().accepting() {
+            sleep(POLL_INTERVAL).await;
+        }
+        assert_eq!(request(&mut second, "SHUTDOWN ABORT\r\n").await?, "+OK\r\n");
+        let mut buf = vec![0; 256];
+        let n = first.read(&mut buf).await?;
+        assert_eq!(
+            &buf[..n],
+            b"-ERR Errors trying to SHUTDOWN. Check logs.\r\n"
+        );
+        assert!(backend.shutdown().accepting());
+        assert_eq!(
+            request(&mut second, "SHUTDOWN ABORT NOW\r\n").await?,
+            "-ERR syntax error\r\n"
+        );
+
+        // NOW doesn't wait for what is running
+        assert_eq!(request(&mut first, "SHUTDOWN NOW\r\n").await?, "");
+        coordinator.await??;
+        drop(busy);
+        Ok(())
+    }
+}
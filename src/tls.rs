@@ -0,0 +1,423 @@
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{version, RootCertStore, ServerConfig, SupportedProtocolVersion};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+use crate::network::ClientStream;
+
+// the client isn't registered until the handshake is done, so neither maxclients nor the idle
+// timeout reach one that never finishes it
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Like redis' tls-auth-clients.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TlsAuthClients {
+    No,
+    Optional,
+    #[default]
+    Yes,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    pub cert_file: PathBuf,
+    pub key_file: PathBuf,
+    /// Needed to verify client certificates unless `auth_clients` is `No`.
+    pub ca_cert_file: Option<PathBuf>,
+    pub auth_clients: TlsAuthClients,
+    /// "TLSv1.2" and "TLSv1.3", both when empty.
+    pub protocols: Vec<String>,
+    /// Rustls cipher suite names such as "TLS13_AES_256_GCM_SHA384", every safe one when empty.
+    pub ciphersuites: Vec<String>,
+}
+
+/// The server side TLS setup; [`TlsContext::reload`] swaps in new certificates without
+/// touching connections that are already established.
+#[derive(Debug)]
+pub struct TlsContext {
+    options: TlsOptions,
+    config: RwLock<Arc<ServerConfig>>,
+}
+
+impl TlsAuthClients {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "no" => Some(TlsAuthClients::No),
+            "optional" => Some(TlsAuthClients::Optional),
+            "yes" => Some(TlsAuthClients::Yes),
+            _ => None,
+        }
+    }
+}
+
+impl TlsContext {
+    pub fn new(options: TlsOptions) -> Result<Self> {
+        let config = build_config(&options)?;
+        Ok(Self {
+            options,
+            config: RwLock::new(Arc::new(config)),
+        })
+    }
+
+    /// Read the certificate, key and CA files again; on error the old ones stay in use.
+    pub fn reload(&self) -> Result<()> {
+        let config = build_config(&self.options)?;
+        *self.config.write().unwrap() = Arc::new(config);
+        Ok(())
+    }
+
+    /// Run the handshake with the configuration current at the time of the call, giving
+    /// up on a peer that doesn't complete it in time.
+    pub async fn accept<S>(&self, stream: S) -> Result<TlsStream<S>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        self.accept_within(stream, HANDSHAKE_TIMEOUT).await
+    }
+
+    async fn accept_within<S>(&self, stream: S, timeout: Duration) -> Result<TlsStream<S>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let acceptor = TlsAcceptor::from(self.config.read().unwrap().clone());
+        tokio::time::timeout(timeout, acceptor.accept(stream))
+            .await
+            .map_err(|_| anyhow!("TLS handshake timed out"))?
+            .map_err(Into::into)
+    }
+}
+
+impl<S: ClientStream> ClientStream for TlsStream<S> {
+    fn addrs(&self) -> Result<(String, String)> {
+        self.get_ref().0.addrs()
+    }
+
+    fn is_unix_socket(&self) -> bool {
+        self.get_ref().0.is_unix_socket()
+    }
+}
+
+fn build_config(options: &TlsOptions) -> Result<ServerConfig> {
+    let mut provider = ring::default_provider();
+    if !options.ciphersuites.is_empty() {
+        provider.cipher_suites.retain(|suite| {
+            let name = format!("{:?}", suite.suite());
+            options
+                .ciphersuites
+                .iter()
+                .any(|c| c.eq_ignore_ascii_case(&name))
+        });
+        if provider.cipher_suites.is_empty() {
+            return Err(anyhow!("none of the configured cipher suites is supported"));
+        }
+    }
+    let provider = Arc::new(provider);
+
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_protocol_versions(&protocol_versions(&options.protocols)?)?;
+    let builder = match (options.auth_clients, &options.ca_cert_file) {
+        (TlsAuthClients::No, _) => builder.with_no_client_auth(),
+        (auth, Some(ca_file)) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(ca_file)? {
+                roots.add(cert)?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = match auth {
+                TlsAuthClients::Optional => verifier.allow_unauthenticated(),
+                _ => verifier,
+            };
+            builder.with_client_cert_verifier(verifier.build()?)
+        }
+        (_, None) => {
+            return Err(anyhow!(
+                "a CA certificate file is needed to verify clients, or disable tls-auth-clients"
+            ))
+        }
+    };
+
+    let certs = load_certs(&options.cert_file)?;
+    let key = PrivateKeyDer::from_pem_file(&options.key_file)
+        .with_context(|| format!("reading key from {}", options.key_file.display()))?;
+    Ok(builder.with_single_cert(certs, key)?)
+}
+
+fn load_certs(path: &PathBuf) -> Result<Vec<CertificateDer<'static>>> {
+    CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("reading certificates from {}", path.display()))
+}
+
+fn protocol_versions(names: &[String]) -> Result<Vec<&'static SupportedProtocolVersion>> {
+    if names.is_empty() {
+        return Ok(vec![&version::TLS12, &version::TLS13]);
+    }
+    names
+        .iter()
+        .map(|name| match name.to_ascii_lowercase().as_str() {
+            "tlsv1.2" => Ok(&version::TLS12),
+            "tlsv1.3" => Ok(&version::TLS13),
+            _ => Err(anyhow!("unsupported TLS protocol '{}'", name)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::stream_handler;
+    use crate::Backend;
+    use rcgen::{BasicConstraints, CertificateParams, CertifiedIssuer, IsCa, KeyPair};
+    use std::path::Path;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_rustls::rustls::pki_types::ServerName;
+    use tokio_rustls::rustls::ClientConfig;
+    use tokio_rustls::TlsConnector;
+
+    struct Ca(CertifiedIssuer<'static, KeyPair>);
+
+    impl Ca {
+        fn new(name: &str) -> Result<Self> {
+            let mut params = CertificateParams::new(vec![])?;
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            params
+                .distinguished_name
+                .push(rcgen::DnType::CommonName, name);
+            Ok(Self(CertifiedIssuer::self_signed(
+                params,
+                KeyPair::generate()?,
+            )?))
+        }
+
+        // a leaf certificate and its key, as PEM files under `dir`
+        fn issue(&self, dir: &Path, name: &str) -> Result<(PathBuf, PathBuf)> {
+            let key = KeyPair::generate()?;
+            let cert =
+                CertificateParams::new(vec!["localhost".to_string()])?.signed_by(&key, &self.0)?;
+            let (cert_file, key_file) = (
+                dir.join(format!("{name}.crt")),
+                dir.join(format!("{name}.key")),
+            );
+            std::fs::write(&cert_file, cert.pem())?;
+            std::fs::write(&key_file, key.serialize_pem())?;
+            Ok((cert_file, key_file))
+        }
+
+        fn write(&self, dir: &Path, name: &str) -> Result<PathBuf> {
+            let path = dir.join(format!("{name}.crt"));
+            std::fs::write(&path, self.0.pem())?;
+            Ok(path)
+        }
+    }
+
+    fn test_dir(name: &str) -> Result<PathBuf> {
+        let dir =
+            std::env::temp_dir().join(format!("simple-redis-tls-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    async fn serve(tls: Arc<TlsContext>) -> Result<u16> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let backend = Backend::new();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (tls, backend) = (tls.clone(), backend.clone());
+                tokio::spawn(async move {
+                    if let Ok(stream) = tls.accept(stream).await {
+                        let _ = stream_handler(stream, backend).await;
+                    }
+                });
+            }
+        });
+        Ok(port)
+    }
+
+    // connect and PING, with a client certificate when `identity` is given
+    async fn ping(
+        port: u16,
+        ca_file: &Path,
+        identity: Option<(PathBuf, PathBuf)>,
+        protocols: &[&'static SupportedProtocolVersion],
+    ) -> Result<String> {
+        let mut roots = RootCertStore::empty();
+        for cert in load_certs(&ca_file.to_path_buf())? {
+            roots.add(cert)?;
+        }
+        let builder = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_protocol_versions(protocols)?
+            .with_root_certificates(roots);
+        let config = match identity {
+            Some((cert_file, key_file)) => builder.with_client_auth_cert(
+                load_certs(&cert_file)?,
+                PrivateKeyDer::from_pem_file(key_file)?,
+            )?,
+            None => builder.with_no_client_auth(),
+        };
+        let stream = TcpStream::connect(("127.0.0.1", port)).await?;
+        let mut stream = TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost")?, stream)
+            .await?;
+        stream.write_all(b"*1\r\n$4\r\nPING\r\n").await?;
+        let mut buf = vec![0; 64];
+        let n = stream.read(&mut buf).await?;
+        Ok(String::from_utf8_lossy(&buf[..n]).into_owned())
+    }
+
+    const ALL: &[&SupportedProtocolVersion] = &[&version::TLS12, &version::TLS13];
+
+    #[tokio::test]
+    async fn test_tls_ping() -> Result<()> {
+        let dir = test_dir("ping")?;
+        let ca = Ca::new("test ca")?;
+        let ca_file = ca.write(&dir, "ca")?;
+        let (cert_file, key_file) = ca.issue(&dir, "server")?;
+        let tls = TlsContext::new(TlsOptions {
+            cert_file,
+            key_file,
+            auth_clients: TlsAuthClients::No,
+            ..Default::default()
+        })?;
+        let port = serve(Arc::new(tls)).await?;
+        assert_eq!(ping(port, &ca_file, None, ALL).await?, "+PONG\r\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tls_handshake_timeout() -> Result<()> {
+        let dir = test_dir("timeout")?;
+        let (cert_file, key_file) = Ca::new("test ca")?.issue(&dir, "server")?;
+        let tls = TlsContext::new(TlsOptions {
+            cert_file,
+            key_file,
+            auth_clients: TlsAuthClients::No,
+            ..Default::default()
+        })?;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        // connected, but never a ClientHello
+        let _client = TcpStream::connect(listener.local_addr()?).await?;
+        let (stream, _) = listener.accept().await?;
+        let err = tls
+            .accept_within(stream, Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "TLS handshake timed out");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tls_client_certificates() -> Result<()> {
+        let dir = test_dir("mtls")?;
+        let ca = Ca::new("test ca")?;
+        let ca_file = ca.write(&dir, "ca")?;
+        let (cert_file, key_file) = ca.issue(&dir, "server")?;
+        let client = ca.issue(&dir, "client")?;
+        let other = Ca::new("other ca")?.issue(&dir, "other")?;
+        let options = TlsOptions {
+            cert_file,
+            key_file,
+            ca_cert_file: Some(ca_file.clone()),
+            ..Default::default()
+        };
+
+        let port = serve(Arc::new(TlsContext::new(options.clone())?)).await?;
+        // with TLS 1.3 the server rejects the certificate after the client's handshake is done
+        assert!(ping(port, &ca_file, None, ALL).await.is_err());
+        assert!(ping(port, &ca_file, Some(other.clone()), ALL)
+            .await
+            .is_err());
+        assert_eq!(
+            ping(port, &ca_file, Some(client.clone()), ALL).await?,
+            "+PONG\r\n"
+        );
+
+        let port = serve(Arc::new(TlsContext::new(TlsOptions {
+            auth_clients: TlsAuthClients::Optional,
+            ..options
+        })?))
+        .await?;
+        assert_eq!(ping(port, &ca_file, None, ALL).await?, "+PONG\r\n");
+        assert_eq!(ping(port, &ca_file, Some(client), ALL).await?, "+PONG\r\n");
+        assert!(ping(port, &ca_file, Some(other), ALL).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tls_protocols_and_ciphers() -> Result<()> {
+        let dir = test_dir("protocols")?;
+        let ca = Ca::new("test ca")?;
+        let ca_file = ca.write(&dir, "ca")?;
+        let (cert_file, key_file) = ca.issue(&dir, "server")?;
+        let options = TlsOptions {
+            cert_file,
+            key_file,
+            auth_clients: TlsAuthClients::No,
+            protocols: vec!["TLSv1.3".to_string()],
+            ciphersuites: vec!["TLS13_CHACHA20_POLY1305_SHA256".to_string()],
+            ..Default::default()
+        };
+        let port = serve(Arc::new(TlsContext::new(options.clone())?)).await?;
+        assert!(ping(port, &ca_file, None, &[&version::TLS12])
+            .await
+            .is_err());
+        assert_eq!(
+            ping(port, &ca_file, None, &[&version::TLS13]).await?,
+            "+PONG\r\n"
+        );
+
+        let err = TlsContext::new(TlsOptions {
+            protocols: vec!["SSLv3".to_string()],
+            ..options.clone()
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "unsupported TLS protocol 'SSLv3'");
+        // a TLS 1.2 only suite leaves nothing for TLS 1.3
+        assert!(TlsContext::new(TlsOptions {
+            ciphersuites: vec!["TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256".to_string()],
+            ..options
+        })
+        .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tls_reload() -> Result<()> {
+        let dir = test_dir("reload")?;
+        let (old_ca, new_ca) = (Ca::new("old ca")?, Ca::new("new ca")?);
+        let old_ca_file = old_ca.write(&dir, "old-ca")?;
+        let new_ca_file = new_ca.write(&dir, "new-ca")?;
+        let (cert_file, key_file) = old_ca.issue(&dir, "server")?;
+        let tls = Arc::new(TlsContext::new(TlsOptions {
+            cert_file: cert_file.clone(),
+            key_file: key_file.clone(),
+            auth_clients: TlsAuthClients::No,
+            ..Default::default()
+        })?);
+        let port = serve(tls.clone()).await?;
+        assert_eq!(ping(port, &old_ca_file, None, ALL).await?, "+PONG\r\n");
+
+        new_ca.issue(&dir, "server")?;
+        // nothing changes until the reload
+        assert_eq!(ping(port, &old_ca_file, None, ALL).await?, "+PONG\r\n");
+        tls.reload()?;
+        assert!(ping(port, &old_ca_file, None, ALL).await.is_err());
+        assert_eq!(ping(port, &new_ca_file, None, ALL).await?, "+PONG\r\n");
+
+        // a broken key keeps the certificates in use
+        std::fs::write(&key_file, "not a key")?;
+        assert!(tls.reload().is_err());
+        assert_eq!(ping(port, &new_ca_file, None, ALL).await?, "+PONG\r\n");
+        Ok(())
+    }
+}